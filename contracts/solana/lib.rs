@@ -1,5 +1,10 @@
 use anchor_lang::prelude::*;
-use anchor_spl::token::{self, Token, TokenAccount, Transfer};
+use anchor_lang::solana_program::{ed25519_program, hash::hash, program::invoke_signed, sysvar};
+use anchor_lang::system_program;
+use anchor_spl::associated_token::{get_associated_token_address, AssociatedToken};
+use anchor_spl::token::{self, Mint, MintTo, Token, TokenAccount, Transfer};
+use mpl_token_metadata::instruction::{builders::TransferBuilder, InstructionBuilder, TransferArgs};
+use mpl_token_metadata::state::{Metadata, TokenMetadataAccount};
 use std::collections::HashMap;
 
 declare_id!("NaffStk1111111111111111111111111111111111111");
@@ -15,9 +20,104 @@ pub mod naffles_staking {
     
     // Emergency controls
     pub const EMERGENCY_DELAY: i64 = 24 * 60 * 60;
+    pub const MAX_ACTIVE_EMERGENCY_REQUESTS: usize = 32;
+    
+    // Default and ceiling for StakingProgram::max_batch_size, the number of items a single
+    // batch instruction may process
+    pub const MAX_BATCH_SIZE: usize = 10;
+    pub const MAX_BATCH_SIZE_CEILING: usize = 32;
+    
+    // Programs allowed to reach CPI-gated instructions
+    pub const MAX_CPI_CALLERS: usize = 8;
     pub const AUTO_UNPAUSE_DELAY: i64 = 7 * 24 * 60 * 60;
+    pub const MAX_UNPAUSE_APPROVERS: usize = 10;
+    pub const MAX_PROPOSAL_CONFIRMATIONS: usize = 10;
+    // Finished deactivation periods a collection remembers individually; older ones are
+    // folded into a running total (see CollectionAccount::fold_inactive_period)
+    pub const MAX_INACTIVE_PERIODS: usize = 8;
+    
+    // Accounts per NFT in stake_nft_batch's remaining_accounts
+    pub const STAKE_BATCH_GROUP_LEN: usize = 6;
+    
+    // Compensation grants
+    pub const MAX_BONUS_PER_POSITION: u64 = 100_000;
+    pub const MAX_GRANT_REASON_LEN: usize = 64;
+    
+    // Longest reason an admin instruction accepts; sizes EmergencyRequest's reason field
+    pub const MAX_REASON_LEN: usize = 196;
+    
+    // How long past unlock an unclaimed position may sit before it stops counting as staked
+    pub const STALE_POSITION_GRACE: i64 = 90 * 24 * 60 * 60;
+    // Accounts per position in sweep_stale_positions' remaining_accounts
+    pub const SWEEP_GROUP_LEN: usize = 3;
+    // Accounts per position in credit_rewards' and grant_bonus_rewards' remaining_accounts
+    pub const REWARD_GROUP_LEN: usize = 2;
+    
+    // Chains (EVM chain ids) rewards can be attributed to, and the longest external
+    // address accepted for them
+    pub const SUPPORTED_REWARD_CHAINS: [u64; 5] = [1, 56, 137, 8453, 42161];
+    pub const MAX_EXTERNAL_ADDRESS_LEN: usize = 64;
+    
+    // Time unit helpers for read-only countdowns
+    pub const SECONDS_PER_HOUR: u64 = 60 * 60;
+    pub const SECONDS_PER_DAY: u64 = 24 * 60 * 60;
+    
+    // A "month" held past unlock, for the loyalty bonus
+    pub const LOYALTY_MONTH: i64 = 30 * 24 * 60 * 60;
+    
+    // Reward math
+    pub const BASIS_POINTS: u64 = 10_000;
+    
+    // Per-instruction pause bits for `paused_instructions`
+    pub const PAUSE_STAKE: u32 = 1 << 0;
+    pub const PAUSE_CLAIM_NFT: u32 = 1 << 1;
+    pub const PAUSE_CLAIM_REWARDS: u32 = 1 << 2;
+    
+    // Reward-staking multipliers in basis points, indexed by duration (6m, 12m, 3y)
+    pub const REWARD_STAKE_MULTIPLIERS: [u64; 3] = [10_500, 11_200, 13_000];
+    
+    // Default computed multiplier ladder: 1.1x, ~1.29x, ~1.51x
+    pub const DEFAULT_MULTIPLIER_BASE: u64 = 11_000;
+    pub const DEFAULT_MULTIPLIER_GROWTH: u64 = 11_700;
+    
+    // Reward distribution curves (see calculate_rewards)
+    pub const CURVE_LINEAR: u8 = 0;
+    pub const CURVE_CLIFF: u8 = 1;
+    pub const CURVE_BACK_LOADED: u8 = 2;
+    
+    // Migration controls
+    pub const MAX_MIGRATION_WINDOW: i64 = 30 * 24 * 60 * 60;
+    
+    // StakeRejected reason codes
+    pub const REJECT_PAUSED: u8 = 0;
+    pub const REJECT_INVALID_DURATION: u8 = 1;
+    pub const REJECT_COLLECTION_INACTIVE: u8 = 2;
+    pub const REJECT_NOT_IN_COLLECTION: u8 = 3;
+    pub const REJECT_LOCK_TOO_LONG: u8 = 4;
+    pub const REJECT_PROGRAM_NOT_READY: u8 = 5;
+    pub const REJECT_TERMS_NOT_ACCEPTED: u8 = 6;
+    pub const REJECT_MINT_BLOCKED: u8 = 7;
+    pub const REJECT_NOT_STARTED: u8 = 8;
+    pub const REJECT_STAKE_CAP_REACHED: u8 = 9;
+    pub const REJECT_ACCOUNT_NOT_ELIGIBLE: u8 = 10;
+    pub const REJECT_COLLECTION_NOT_VALIDATED: u8 = 11;
+    
+    // ClaimEligibility reason codes (0 = claimable)
+    pub const CLAIM_OK: u8 = 0;
+    pub const CLAIM_BLOCKED_PAUSED: u8 = 1;
+    pub const CLAIM_BLOCKED_INACTIVE: u8 = 2;
+    pub const CLAIM_BLOCKED_NOT_OWNER: u8 = 3;
+    pub const CLAIM_BLOCKED_LOCKED: u8 = 4;
+    pub const CLAIM_BLOCKED_REWARDS_STAKED: u8 = 5;
+    pub const CLAIM_BLOCKED_TICKETS_UNPAID: u8 = 6;
 
     pub fn initialize(ctx: Context<Initialize>, multi_sig_threshold: u8) -> Result<()> {
+        // A proposal can't hold more confirmations than this, so a higher threshold could never execute
+        require!(
+            multi_sig_threshold as usize <= MAX_PROPOSAL_CONFIRMATIONS,
+            StakingError::InvalidThreshold
+        );
+        
         let staking_program = &mut ctx.accounts.staking_program;
         staking_program.authority = ctx.accounts.authority.key();
         staking_program.multi_sig_threshold = multi_sig_threshold;
@@ -25,18 +125,46 @@ pub mod naffles_staking {
         staking_program.total_collections = 0;
         staking_program.is_paused = false;
         staking_program.paused_at = 0;
+        staking_program.migration_ends_at = 0;
+        staking_program.paused_instructions = 0;
+        staking_program.stake_fee_lamports = 0;
+        staking_program.treasury = ctx.accounts.authority.key();
+        staking_program.max_lock_duration = THREE_YEARS;
+        staking_program.cpi_whitelist = Vec::new();
+        staking_program.validated_collections = 0;
+        staking_program.min_validated_collections = 0;
+        staking_program.claim_fee_lamports = 0;
+        staking_program.current_terms_hash = [0u8; 32];
+        staking_program.reward_oracle = Pubkey::default();
+        staking_program.oracle_nonce = 0;
+        staking_program.unpause_threshold = 1;
+        staking_program.multiplier_base = DEFAULT_MULTIPLIER_BASE;
+        staking_program.multiplier_growth = DEFAULT_MULTIPLIER_GROWTH;
+        staking_program.staking_starts_at = 0;
+        staking_program.ticket_mint = Pubkey::default();
+        staking_program.early_unstake_penalty_bps = 0;
+        staking_program.collection_onboarding_fee = 0;
+        staking_program.reward_token_mint = Pubkey::default();
+        staking_program.vault_balance = 0;
+        staking_program.outstanding_rewards = 0;
+        staking_program.min_staker_lamports = 0;
+        staking_program.max_batch_size = MAX_BATCH_SIZE as u16;
+        staking_program.require_validation = false;
+        staking_program.max_tickets_per_day = 0;
+        staking_program.tickets_emitted_today = 0;
+        staking_program.emission_window_start = 0;
         staking_program.bump = *ctx.bumps.get("staking_program").unwrap();
         
         Ok(())
     }
 
     pub fn add_admin(ctx: Context<AddAdmin>, admin: Pubkey) -> Result<()> {
-        require!(!ctx.accounts.staking_program.is_paused, StakingError::ContractPaused);
+        require!(!ctx.accounts.staking_program.is_paused_at(current_timestamp()?), StakingError::ContractPaused);
         
         let admin_account = &mut ctx.accounts.admin_account;
         admin_account.admin = admin;
         admin_account.is_active = true;
-        admin_account.added_at = Clock::get()?.unix_timestamp;
+        admin_account.added_at = current_timestamp()?;
         admin_account.bump = *ctx.bumps.get("admin_account").unwrap();
         
         emit!(AdminAction {
@@ -54,36 +182,54 @@ pub mod naffles_staking {
         six_month_tickets: u64,
         twelve_month_tickets: u64,
         three_year_tickets: u64,
+        max_stake: u64, // 0 = unlimited
     ) -> Result<()> {
-        require!(!ctx.accounts.staking_program.is_paused, StakingError::ContractPaused);
+        require!(!ctx.accounts.staking_program.is_paused_at(current_timestamp()?), StakingError::ContractPaused);
+        require_single_signer(&ctx.accounts.staking_program)?;
+        let is_authority = ctx.accounts.authority.key() == ctx.accounts.staking_program.authority;
+        require!(is_authority || ctx.accounts.admin_account.is_some(), StakingError::Unauthorized);
         
-        let collection_account = &mut ctx.accounts.collection_account;
-        collection_account.collection_mint = collection_mint;
-        collection_account.six_month_tickets = six_month_tickets;
-        collection_account.twelve_month_tickets = twelve_month_tickets;
-        collection_account.three_year_tickets = three_year_tickets;
-        collection_account.six_month_multiplier = 11000; // 1.1x in basis points
-        collection_account.twelve_month_multiplier = 12500; // 1.25x
-        collection_account.three_year_multiplier = 15000; // 1.5x
-        collection_account.is_active = true;
-        collection_account.is_validated = false;
-        collection_account.total_staked = 0;
-        collection_account.bump = *ctx.bumps.get("collection_account").unwrap();
+        ctx.accounts.collection_account.set_inner(CollectionAccount::new(
+            collection_mint,
+            [six_month_tickets, twelve_month_tickets, three_year_tickets],
+            *ctx.bumps.get("collection_account").unwrap(),
+        ));
+        ctx.accounts.collection_account.max_stake = max_stake;
+        
+        // The top-level authority onboards collections for free
+        let onboarding_fee = if is_authority {
+            0
+        } else {
+            ctx.accounts.staking_program.collection_onboarding_fee
+        };
+        collect_fee(
+            ctx.accounts.authority.to_account_info(),
+            ctx.accounts.treasury.to_account_info(),
+            ctx.accounts.system_program.to_account_info(),
+            onboarding_fee,
+        )?;
         
         let staking_program = &mut ctx.accounts.staking_program;
-        staking_program.total_collections += 1;
+        staking_program.total_collections = staking_program
+            .total_collections
+            .checked_add(1)
+            .ok_or(StakingError::ArithmeticOverflow)?;
         
         emit!(CollectionAdded {
             collection_mint,
             six_month_tickets,
             twelve_month_tickets,
             three_year_tickets,
+            onboarding_fee,
         });
         
         emit!(AdminAction {
             admin: ctx.accounts.authority.key(),
             action: "addCollection".to_string(),
-            data: format!("{},{},{},{}", collection_mint, six_month_tickets, twelve_month_tickets, three_year_tickets),
+            data: format!(
+                "{},{},{},{},{}",
+                collection_mint, six_month_tickets, twelve_month_tickets, three_year_tickets, max_stake
+            ),
         });
         
         Ok(())
@@ -92,24 +238,50 @@ pub mod naffles_staking {
     pub fn stake_nft(
         ctx: Context<StakeNft>,
         duration: u8, // 0=6months, 1=12months, 2=3years
+        terms_hash: [u8; 32],
     ) -> Result<()> {
-        require!(!ctx.accounts.staking_program.is_paused, StakingError::ContractPaused);
-        require!(duration <= 2, StakingError::InvalidDuration);
-        require!(ctx.accounts.collection_account.is_active, StakingError::CollectionNotActive);
+        let current_time = current_timestamp()?;
         
-        let clock = Clock::get()?;
-        let current_time = clock.unix_timestamp;
+        check_stake_allowed(
+            ctx.accounts.user.key(),
+            &ctx.accounts.staking_program,
+            &ctx.accounts.collection_account,
+            ctx.accounts.verified_nft.as_deref(),
+            duration,
+            current_time,
+            &terms_hash,
+        )?;
+        check_nft_membership(
+            ctx.accounts.user.key(),
+            &ctx.accounts.nft_mint.key(),
+            &ctx.accounts.collection_account.collection_mint,
+            ctx.accounts.verified_nft.as_deref(),
+            ctx.accounts.nft_metadata.as_deref(),
+        )?;
+        check_mint_not_blocked(
+            ctx.accounts.user.key(),
+            ctx.accounts.collection_account.collection_mint,
+            &ctx.accounts.blocked_mint,
+        )?;
+        check_account_eligible(
+            &ctx.accounts.user,
+            &ctx.accounts.staking_program,
+            ctx.accounts.collection_account.collection_mint,
+        )?;
         
         // Calculate unlock time
-        let staking_duration = match duration {
-            0 => SIX_MONTHS,
-            1 => TWELVE_MONTHS,
-            2 => THREE_YEARS,
-            _ => return Err(StakingError::InvalidDuration.into()),
-        };
+        let staking_duration = staking_duration_seconds(duration)?;
         
         let unlock_at = current_time + staking_duration;
         
+        // Collect the staking fee, if configured
+        collect_fee(
+            ctx.accounts.user.to_account_info(),
+            ctx.accounts.treasury.to_account_info(),
+            ctx.accounts.system_program.to_account_info(),
+            ctx.accounts.staking_program.stake_fee_lamports,
+        )?;
+        
         // Transfer NFT to program
         let cpi_accounts = Transfer {
             from: ctx.accounts.user_token_account.to_account_info(),
@@ -120,24 +292,35 @@ pub mod naffles_staking {
         let cpi_ctx = CpiContext::new(cpi_program, cpi_accounts);
         token::transfer(cpi_ctx, 1)?;
         
-        // Create staking position
+        // Create staking position, locking in the multiplier that governs its payout
         let staking_position = &mut ctx.accounts.staking_position;
-        staking_position.owner = ctx.accounts.user.key();
-        staking_position.nft_mint = ctx.accounts.nft_mint.key();
-        staking_position.collection_mint = ctx.accounts.collection_account.collection_mint;
-        staking_position.staked_at = current_time;
-        staking_position.unlock_at = unlock_at;
-        staking_position.duration = duration;
-        staking_position.is_active = true;
-        staking_position.total_rewards_earned = 0;
-        staking_position.bump = *ctx.bumps.get("staking_position").unwrap();
+        staking_position.open(
+            ctx.accounts.user.key(),
+            ctx.accounts.nft_mint.key(),
+            &ctx.accounts.collection_account,
+            duration,
+            current_time,
+            *ctx.bumps.get("staking_position").unwrap(),
+        )?;
+        staking_position.accepted_terms_hash = terms_hash;
+        let locked_multiplier = staking_position.locked_multiplier;
+        
+        ctx.accounts.user_stake_count.record_stake(
+            &ctx.accounts.collection_account,
+            ctx.accounts.user.key(),
+            *ctx.bumps.get("user_stake_count").unwrap(),
+        )?;
+        ctx.accounts.user_stats.record_stake(
+            ctx.accounts.user.key(),
+            *ctx.bumps.get("user_stats").unwrap(),
+        )?;
         
         // Update statistics
         let staking_program = &mut ctx.accounts.staking_program;
-        staking_program.total_staked += 1;
+        staking_program.record_stake()?;
         
         let collection_account = &mut ctx.accounts.collection_account;
-        collection_account.total_staked += 1;
+        collection_account.record_stake()?;
         
         emit!(NftStaked {
             user: ctx.accounts.user.key(),
@@ -145,6 +328,8 @@ pub mod naffles_staking {
             collection_mint: ctx.accounts.collection_account.collection_mint,
             duration,
             unlock_at,
+            display_multiplier: locked_multiplier,
+            multiplier_computed: ctx.accounts.collection_account.computed_multipliers,
         });
         
         emit!(AdminAction {
@@ -157,21 +342,16 @@ pub mod naffles_staking {
     }
 
     pub fn claim_nft(ctx: Context<ClaimNft>) -> Result<()> {
-        require!(!ctx.accounts.staking_program.is_paused, StakingError::ContractPaused);
-        
+        let current_time = current_timestamp()?;
         let staking_position = &mut ctx.accounts.staking_position;
-        require!(staking_position.is_active, StakingError::PositionNotActive);
-        require!(staking_position.owner == ctx.accounts.user.key(), StakingError::NotPositionOwner);
-        
-        let clock = Clock::get()?;
-        require!(clock.unix_timestamp >= staking_position.unlock_at, StakingError::StakingPeriodNotCompleted);
+        check_claim_allowed(&ctx.accounts.staking_program, staking_position, &ctx.accounts.user.key(), current_time)?;
         
         // Mark position as inactive
         staking_position.is_active = false;
         
-        // Transfer NFT back to user
+        // Transfer NFT back to the owner, even when a delegate signed
         let seeds = &[
-            b"staking_program",
+            b"staking_program".as_ref(),
             &[ctx.accounts.staking_program.bump],
         ];
         let signer = &[&seeds[..]];
@@ -185,17 +365,26 @@ pub mod naffles_staking {
         let cpi_ctx = CpiContext::new_with_signer(cpi_program, cpi_accounts, signer);
         token::transfer(cpi_ctx, 1)?;
         
-        // Update statistics
+        // Update statistics; a swept position was already taken out of the counters
         let staking_program = &mut ctx.accounts.staking_program;
-        staking_program.total_staked -= 1;
-        
         let collection_account = &mut ctx.accounts.collection_account;
-        collection_account.total_staked -= 1;
+        let user_stats = &mut ctx.accounts.user_stats;
+        user_stats.fill(staking_position.owner, *ctx.bumps.get("user_stats").unwrap());
+        if !staking_position.swept {
+            staking_program.record_unstake()?;
+            collection_account.record_unstake()?;
+            ctx.accounts.user_stake_count.record_unstake()?;
+            user_stats.record_unstake()?;
+        }
         
         emit!(NftClaimed {
-            user: ctx.accounts.user.key(),
+            user: staking_position.owner,
             nft_mint: staking_position.nft_mint,
             collection_mint: staking_position.collection_mint,
+            total_rewards_earned: staking_position.total_rewards_earned,
+            staked_at: staking_position.staked_at,
+            unlock_at: staking_position.unlock_at,
+            actual_duration_held: elapsed_since(staking_position.staked_at, current_time),
         });
         
         emit!(AdminAction {
@@ -211,13 +400,13 @@ pub mod naffles_staking {
         ctx: Context<AdminUnlock>,
         reason: String,
     ) -> Result<()> {
-        require!(!reason.is_empty(), StakingError::ReasonRequired);
+        validate_reason(&reason, MAX_REASON_LEN)?;
+        require_single_signer(&ctx.accounts.staking_program)?;
         
         let staking_position = &mut ctx.accounts.staking_position;
         require!(staking_position.is_active, StakingError::PositionNotActive);
         
-        let clock = Clock::get()?;
-        let current_time = clock.unix_timestamp;
+        let current_time = current_timestamp()?;
         
         // Check if emergency request exists
         let emergency_request = &mut ctx.accounts.emergency_request;
@@ -229,6 +418,10 @@ pub mod naffles_staking {
             emergency_request.executed = false;
             emergency_request.bump = *ctx.bumps.get("emergency_request").unwrap();
             
+            let registry = &mut ctx.accounts.active_emergency_requests;
+            registry.bump = *ctx.bumps.get("active_emergency_requests").unwrap();
+            registry.add(emergency_request.key())?;
+            
             emit!(EmergencyAction {
                 admin: ctx.accounts.admin.key(),
                 action: "emergencyUnlockRequested".to_string(),
@@ -243,13 +436,14 @@ pub mod naffles_staking {
         
         // Mark request as executed
         emergency_request.executed = true;
+        ctx.accounts.active_emergency_requests.remove(&emergency_request.key());
         
         // Mark position as inactive
         staking_position.is_active = false;
         
         // Transfer NFT back to owner
         let seeds = &[
-            b"staking_program",
+            b"staking_program".as_ref(),
             &[ctx.accounts.staking_program.bump],
         ];
         let signer = &[&seeds[..]];
@@ -263,12 +457,17 @@ pub mod naffles_staking {
         let cpi_ctx = CpiContext::new_with_signer(cpi_program, cpi_accounts, signer);
         token::transfer(cpi_ctx, 1)?;
         
-        // Update statistics
+        // Update statistics; a swept position was already taken out of the counters
         let staking_program = &mut ctx.accounts.staking_program;
-        staking_program.total_staked -= 1;
-        
         let collection_account = &mut ctx.accounts.collection_account;
-        collection_account.total_staked -= 1;
+        let user_stats = &mut ctx.accounts.user_stats;
+        user_stats.fill(staking_position.owner, *ctx.bumps.get("user_stats").unwrap());
+        if !staking_position.swept {
+            staking_program.record_unstake()?;
+            collection_account.record_unstake()?;
+            ctx.accounts.user_stake_count.record_unstake()?;
+            user_stats.record_unstake()?;
+        }
         
         emit!(EmergencyUnlock {
             admin: ctx.accounts.admin.key(),
@@ -287,9 +486,13 @@ pub mod naffles_staking {
     }
 
     pub fn pause_contract(ctx: Context<PauseContract>) -> Result<()> {
+        let current_time = current_timestamp()?;
         let staking_program = &mut ctx.accounts.staking_program;
+        // Re-pausing would move `paused_at`, restarting the auto-unpause clock and
+        // orphaning the approvals already collected for this pause
+        require!(!staking_program.is_paused_at(current_time), StakingError::ContractPaused);
         staking_program.is_paused = true;
-        staking_program.paused_at = Clock::get()?.unix_timestamp;
+        staking_program.paused_at = current_time;
         
         emit!(EmergencyAction {
             admin: ctx.accounts.admin.key(),
@@ -306,7 +509,29 @@ pub mod naffles_staking {
         Ok(())
     }
 
+    // Pausing takes one admin; unpausing needs `unpause_threshold` distinct admins to
+    // approve. Approvals are scoped to the current pause (keyed by `paused_at`), and the
+    // approval that reaches the threshold performs the unpause.
     pub fn unpause_contract(ctx: Context<UnpauseContract>) -> Result<()> {
+        require!(ctx.accounts.staking_program.is_paused, StakingError::ContractNotPaused);
+        
+        let unpause_approval = &mut ctx.accounts.unpause_approval;
+        unpause_approval.paused_at = ctx.accounts.staking_program.paused_at;
+        unpause_approval.bump = *ctx.bumps.get("unpause_approval").unwrap();
+        unpause_approval.approve(ctx.accounts.admin.key())?;
+        
+        let approvals = unpause_approval.approvers.len() as u8;
+        let threshold = ctx.accounts.staking_program.unpause_threshold;
+        emit!(UnpauseApprovalProgress {
+            admin: ctx.accounts.admin.key(),
+            paused_at: unpause_approval.paused_at,
+            approvals,
+            threshold,
+        });
+        if approvals < threshold {
+            return Ok(());
+        }
+        
         let staking_program = &mut ctx.accounts.staking_program;
         staking_program.is_paused = false;
         staking_program.paused_at = 0;
@@ -332,7 +557,8 @@ pub mod naffles_staking {
         twelve_month_tickets: u64,
         three_year_tickets: u64,
     ) -> Result<()> {
-        require!(!ctx.accounts.staking_program.is_paused, StakingError::ContractPaused);
+        require!(!ctx.accounts.staking_program.is_paused_at(current_timestamp()?), StakingError::ContractPaused);
+        require_single_signer(&ctx.accounts.staking_program)?;
         
         let collection_account = &mut ctx.accounts.collection_account;
         collection_account.six_month_tickets = six_month_tickets;
@@ -359,10 +585,38 @@ pub mod naffles_staking {
         ctx: Context<ValidateCollection>,
         validated: bool,
     ) -> Result<()> {
-        require!(!ctx.accounts.staking_program.is_paused, StakingError::ContractPaused);
+        require!(!ctx.accounts.staking_program.is_paused_at(current_timestamp()?), StakingError::ContractPaused);
         
+        let current_time = current_timestamp()?;
         let collection_account = &mut ctx.accounts.collection_account;
+        let staking_program = &mut ctx.accounts.staking_program;
+        if validated && !collection_account.is_validated {
+            staking_program.validated_collections = staking_program
+                .validated_collections
+                .checked_add(1)
+                .ok_or(StakingError::ArithmeticOverflow)?;
+            // Only the first validation defines the early-adopter window
+            if collection_account.validated_at == 0 {
+                collection_account.validated_at = current_time;
+            }
+        } else if !validated && collection_account.is_validated {
+            staking_program.validated_collections = staking_program
+                .validated_collections
+                .checked_sub(1)
+                .ok_or(StakingError::ArithmeticOverflow)?;
+        }
         collection_account.is_validated = validated;
+        collection_account.validation_expires_at = if validated {
+            collection_account.next_validation_expiry(current_time)
+        } else {
+            0
+        };
+        
+        emit!(CollectionValidated {
+            collection_mint: collection_account.collection_mint,
+            validated,
+            validation_expires_at: collection_account.validation_expires_at,
+        });
         
         emit!(AdminAction {
             admin: ctx.accounts.authority.key(),
@@ -372,368 +626,10758 @@ pub mod naffles_staking {
         
         Ok(())
     }
-}
 
-// Account structures
-#[account]
-pub struct StakingProgram {
-    pub authority: Pubkey,
-    pub multi_sig_threshold: u8,
-    pub total_staked: u64,
-    pub total_collections: u64,
-    pub is_paused: bool,
-    pub paused_at: i64,
-    pub bump: u8,
-}
+    pub fn time_until_unlock(ctx: Context<TimeUntilUnlock>) -> Result<()> {
+        let staking_position = &ctx.accounts.staking_position;
+        let current_time = current_timestamp()?;
+        
+        let seconds_remaining = staking_position.unlock_at.saturating_sub(current_time).max(0) as u64;
+        
+        emit!(UnlockTimeRemaining {
+            nft_mint: staking_position.nft_mint,
+            unlock_at: staking_position.unlock_at,
+            days_remaining: seconds_remaining / SECONDS_PER_DAY,
+            hours_remaining: (seconds_remaining % SECONDS_PER_DAY) / SECONDS_PER_HOUR,
+            is_unlocked: seconds_remaining == 0,
+        });
+        
+        Ok(())
+    }
 
-#[account]
-pub struct AdminAccount {
-    pub admin: Pubkey,
-    pub is_active: bool,
-    pub added_at: i64,
-    pub bump: u8,
-}
+    pub fn set_migration_window(ctx: Context<UpdateProgramConfig>, migration_ends_at: i64) -> Result<()> {
+        let current_time = current_timestamp()?;
+        // A value in the past (e.g. 0) closes the window immediately
+        require!(migration_ends_at <= current_time + MAX_MIGRATION_WINDOW, StakingError::InvalidMigrationWindow);
+        
+        let staking_program = &mut ctx.accounts.staking_program;
+        staking_program.migration_ends_at = migration_ends_at;
+        
+        emit!(AdminAction {
+            admin: ctx.accounts.authority.key(),
+            action: "setMigrationWindow".to_string(),
+            data: migration_ends_at.to_string(),
+        });
+        
+        Ok(())
+    }
 
-#[account]
-pub struct CollectionAccount {
-    pub collection_mint: Pubkey,
-    pub six_month_tickets: u64,
-    pub twelve_month_tickets: u64,
-    pub three_year_tickets: u64,
-    pub six_month_multiplier: u64,
-    pub twelve_month_multiplier: u64,
-    pub three_year_multiplier: u64,
-    pub is_active: bool,
-    pub is_validated: bool,
-    pub total_staked: u64,
-    pub bump: u8,
-}
+    // Recreates a position from a previous staking contract. The NFT must already sit in
+    // the program vault, so no transfer happens here.
+    pub fn admin_create_position(
+        ctx: Context<AdminCreatePosition>,
+        owner: Pubkey,
+        nft_mint: Pubkey,
+        duration: u8,
+        staked_at: i64,
+    ) -> Result<()> {
+        let current_time = current_timestamp()?;
+        require!(current_time < ctx.accounts.staking_program.migration_ends_at, StakingError::MigrationWindowClosed);
+        require!(staked_at > 0 && staked_at <= current_time, StakingError::InvalidTimestamp);
+        let unlock_at = staked_at + staking_duration_seconds(duration)?;
+        ctx.accounts.staking_program.check_lock_duration(unlock_at, current_time)?;
+        
+        let staking_position = &mut ctx.accounts.staking_position;
+        staking_position.open(
+            owner,
+            nft_mint,
+            &ctx.accounts.collection_account,
+            duration,
+            staked_at,
+            *ctx.bumps.get("staking_position").unwrap(),
+        )?;
+        
+        // Update statistics
+        let staking_program = &mut ctx.accounts.staking_program;
+        staking_program.record_stake()?;
+        
+        let collection_account = &mut ctx.accounts.collection_account;
+        collection_account.record_stake()?;
+        
+        ctx.accounts.user_stake_count.record_stake(
+            &ctx.accounts.collection_account,
+            owner,
+            *ctx.bumps.get("user_stake_count").unwrap(),
+        )?;
+        ctx.accounts.user_stats.record_stake(owner, *ctx.bumps.get("user_stats").unwrap())?;
+        
+        let collection_account = &ctx.accounts.collection_account;
+        emit!(PositionMigrated {
+            admin: ctx.accounts.authority.key(),
+            owner,
+            nft_mint,
+            collection_mint: collection_account.collection_mint,
+            duration,
+            staked_at,
+            unlock_at,
+        });
+        
+        emit!(AdminAction {
+            admin: ctx.accounts.authority.key(),
+            action: "adminCreatePosition".to_string(),
+            data: format!("{},{},{},{}", owner, nft_mint, duration, staked_at),
+        });
+        
+        Ok(())
+    }
 
-#[account]
-pub struct StakingPosition {
-    pub owner: Pubkey,
-    pub nft_mint: Pubkey,
-    pub collection_mint: Pubkey,
-    pub staked_at: i64,
-    pub unlock_at: i64,
-    pub duration: u8,
-    pub is_active: bool,
-    pub total_rewards_earned: u64,
-    pub bump: u8,
-}
+    // Settles the tickets accrued so far. Tickets are credited off-chain from the
+    // RewardsClaimed event; `min_rewards_out` protects against the collection's
+    // reward config changing between transaction build and execution.
+    pub fn claim_rewards(ctx: Context<ClaimRewards>, min_rewards_out: u64) -> Result<()> {
+        require!(
+            !ctx.accounts.staking_program.is_instruction_paused(PAUSE_CLAIM_REWARDS, current_timestamp()?),
+            StakingError::ContractPaused
+        );
+        check_cpi_caller(&ctx.accounts.staking_program, &ctx.accounts.sysvar_instructions)?;
+        
+        let current_time = current_timestamp()?;
+        let collection_account = &mut ctx.accounts.collection_account;
+        let staking_position = &mut ctx.accounts.staking_position;
+        require!(
+            collection_account.reward_activation_threshold == 0 || collection_account.rewards_activated,
+            StakingError::CollectionNotActivated
+        );
+        require!(
+            current_time >= collection_account.next_claim_at(staking_position),
+            StakingError::ClaimCooldownActive
+        );
+        
+        let (accrued, mut amount) = unsettled_rewards(staking_position, collection_account, current_time)?;
+        // The rate this claim's new accrual is settled at, taken before the claim draws
+        // down the pool
+        let base_multiplier = collection_account.settlement_multiplier(staking_position, current_time)?;
+        let settlement_multiplier = collection_account.apply_dynamic_multiplier(base_multiplier);
+        let decay_floored = collection_account.is_decay_floored(base_multiplier);
+        if collection_account.dynamic_multiplier {
+            emit!(DynamicMultiplierApplied {
+                collection_mint: collection_account.collection_mint,
+                nft_mint: staking_position.nft_mint,
+                dynamic_multiplier: settlement_multiplier,
+                reward_pool_remaining: collection_account.reward_pool_remaining,
+                reward_pool: collection_account.reward_pool,
+            });
+        }
+        
+        // Claiming before the term ends forfeits a share of the claim
+        let penalty_bps = early_claim_penalty_bps(staking_position, collection_account, current_time);
+        let penalty = (amount as u128 * penalty_bps as u128 / BASIS_POINTS as u128) as u64;
+        amount -= penalty;
+        
+        // Collections with a reward pool only pay out what is left in it; anything
+        // accrued beyond the remaining budget is forfeited
+        if collection_account.reward_pool > 0 {
+            amount = amount.min(collection_account.reward_pool_remaining);
+        }
+        require!(amount >= min_rewards_out, StakingError::SlippageExceeded);
+        
+        let fee_lamports = ctx.accounts.staking_program.claim_fee_lamports;
+        require!(ctx.accounts.user.lamports() >= fee_lamports, StakingError::InsufficientFeeBalance);
+        collect_fee(
+            ctx.accounts.user.to_account_info(),
+            ctx.accounts.treasury.to_account_info(),
+            ctx.accounts.system_program.to_account_info(),
+            fee_lamports,
+        )?;
+        
+        // Treasury match on top of the claim, paid until the match budget runs out
+        let matched = ((amount as u128 * collection_account.reward_match_bps as u128 / BASIS_POINTS as u128) as u64)
+            .min(collection_account.match_budget);
+        collection_account.match_budget -= matched;
+        
+        staking_position.total_rewards_earned = staking_position
+            .total_rewards_earned
+            .checked_add(amount)
+            .and_then(|total| total.checked_add(matched))
+            .ok_or(StakingError::ArithmeticOverflow)?;
+        let previous_watermark = staking_position.rewards_claimed;
+        staking_position.mark_settled(accrued);
+        staking_position.last_claim_at = current_time;
+        
+        emit!(RewardsAccrued {
+            nft_mint: staking_position.nft_mint,
+            accrued,
+            settled: amount + matched,
+            total_rewards_earned: staking_position.total_rewards_earned,
+            accrued_at: current_time,
+        });
+        ctx.accounts.staking_program.record_settled(amount + matched)?;
+        ctx.accounts.user_stats.fill(ctx.accounts.user.key(), *ctx.bumps.get("user_stats").unwrap());
+        ctx.accounts.user_stats.record_rewards(amount + matched)?;
+        
+        if collection_account.reward_pool > 0 && amount > 0 {
+            collection_account.reward_pool_remaining -= amount;
+            if collection_account.reward_pool_remaining == 0 {
+                emit!(RewardPoolDepleted {
+                    collection_mint: collection_account.collection_mint,
+                    reward_pool: collection_account.reward_pool,
+                });
+            }
+        }
+        
+        let floored_multiplier = collection_account.effective_multiplier(staking_position.locked_multiplier);
+        if decay_floored {
+            emit!(MultiplierFloored {
+                nft_mint: staking_position.nft_mint,
+                locked_multiplier: staking_position.locked_multiplier,
+                floored_multiplier: settlement_multiplier,
+            });
+        } else if floored_multiplier != staking_position.locked_multiplier {
+            emit!(MultiplierFloored {
+                nft_mint: staking_position.nft_mint,
+                locked_multiplier: staking_position.locked_multiplier,
+                floored_multiplier,
+            });
+        }
+        if let Some(reward_receipt) = ctx.accounts.reward_receipt.as_ref() {
+            let receipt = RewardReceipt {
+                staking_position: staking_position.key(),
+                owner: staking_position.owner,
+                nft_mint: staking_position.nft_mint,
+                amount,
+                matched,
+                settlement_multiplier,
+                claimed_at: current_time,
+                bump: 0,
+            };
+            create_reward_receipt(
+                ctx.accounts.user.to_account_info(),
+                reward_receipt,
+                ctx.accounts.system_program.to_account_info(),
+                receipt,
+                staking_position.staked_at,
+                previous_watermark,
+            )?;
+        }
+        
+        emit!(RewardsClaimed {
+            user: ctx.accounts.user.key(),
+            nft_mint: staking_position.nft_mint,
+            collection_mint: staking_position.collection_mint,
+            amount,
+            total_rewards_earned: staking_position.total_rewards_earned,
+            settlement_multiplier,
+            cliff_passed: vesting_cliff_passed(staking_position, collection_account, current_time),
+            fee_lamports,
+            distribution_curve: collection_account.distribution_curve,
+            early_adopter_bonus_bps: collection_account.early_adopter_bonus_for(staking_position, current_time),
+            pending_validation_boost_bps: collection_account.pending_validation_boost(current_time),
+            loyalty_bonus_bps: collection_account.loyalty_bonus_for(staking_position, current_time),
+            accrual_start: collection_account.accrual_start(staking_position, current_time),
+            penalty,
+            matched,
+            next_claim_at: collection_account.next_claim_at(staking_position),
+            favor_user: collection_account.favor_user,
+        });
+        
+        emit!(AdminAction {
+            admin: ctx.accounts.user.key(),
+            action: "claimRewards".to_string(),
+            data: format!("{},{}", staking_position.nft_mint, amount),
+        });
+        
+        Ok(())
+    }
 
-#[account]
-pub struct EmergencyRequest {
-    pub requester: Pubkey,
-    pub requested_at: i64,
-    pub reason: String,
-    pub executed: bool,
-    pub bump: u8,
-}
+    // Adds budget to a collection's reward pool. A collection with no pool (the default)
+    // pays rewards without limit; the first refill turns the budget on.
+    pub fn refill_reward_pool(ctx: Context<UpdateCollectionConfig>, amount: u64) -> Result<()> {
+        require!(amount > 0, StakingError::InvalidAmount);
+        
+        let collection_account = &mut ctx.accounts.collection_account;
+        collection_account.reward_pool = collection_account
+            .reward_pool
+            .checked_add(amount)
+            .ok_or(StakingError::ArithmeticOverflow)?;
+        collection_account.reward_pool_remaining = collection_account
+            .reward_pool_remaining
+            .checked_add(amount)
+            .ok_or(StakingError::ArithmeticOverflow)?;
+        
+        emit!(AdminAction {
+            admin: ctx.accounts.admin.key(),
+            action: "refillRewardPool".to_string(),
+            data: format!("{},{},{}", collection_account.collection_mint, amount, collection_account.reward_pool_remaining),
+        });
+        
+        Ok(())
+    }
 
-// Context structures
-#[derive(Accounts)]
-pub struct Initialize<'info> {
-    #[account(
-        init,
-        payer = authority,
-        space = 8 + 32 + 1 + 8 + 8 + 1 + 8 + 1,
-        seeds = [b"staking_program"],
-        bump
-    )]
-    pub staking_program: Account<'info, StakingProgram>,
-    
-    #[account(mut)]
-    pub authority: Signer<'info>,
-    
-    pub system_program: Program<'info, System>,
-}
+    pub fn set_paused_instructions(ctx: Context<SetPausedInstructions>, paused_instructions: u32) -> Result<()> {
+        let staking_program = &mut ctx.accounts.staking_program;
+        staking_program.paused_instructions = paused_instructions;
+        
+        emit!(PausedInstructionsUpdated {
+            admin: ctx.accounts.admin.key(),
+            paused_instructions,
+        });
+        
+        emit!(AdminAction {
+            admin: ctx.accounts.admin.key(),
+            action: "setPausedInstructions".to_string(),
+            data: format!("{:#x}", paused_instructions),
+        });
+        
+        Ok(())
+    }
 
-#[derive(Accounts)]
-pub struct AddAdmin<'info> {
-    #[account(mut)]
-    pub staking_program: Account<'info, StakingProgram>,
-    
-    #[account(
-        init,
-        payer = authority,
-        space = 8 + 32 + 1 + 8 + 1,
-        seeds = [b"admin", admin.key().as_ref()],
-        bump
-    )]
-    pub admin_account: Account<'info, AdminAccount>,
-    
-    /// CHECK: This is the admin being added
-    pub admin: AccountInfo<'info>,
-    
-    #[account(mut)]
-    pub authority: Signer<'info>,
-    
-    pub system_program: Program<'info, System>,
-}
+    pub fn set_stake_fee(ctx: Context<UpdateProgramConfig>, stake_fee_lamports: u64) -> Result<()> {
+        let staking_program = &mut ctx.accounts.staking_program;
+        staking_program.stake_fee_lamports = stake_fee_lamports;
+        
+        emit!(AdminAction {
+            admin: ctx.accounts.authority.key(),
+            action: "setStakeFee".to_string(),
+            data: stake_fee_lamports.to_string(),
+        });
+        
+        Ok(())
+    }
 
-#[derive(Accounts)]
-pub struct AddCollection<'info> {
-    #[account(mut)]
-    pub staking_program: Account<'info, StakingProgram>,
+    pub fn set_collection_onboarding_fee(ctx: Context<UpdateProgramConfig>, collection_onboarding_fee: u64) -> Result<()> {
+        let staking_program = &mut ctx.accounts.staking_program;
+        staking_program.collection_onboarding_fee = collection_onboarding_fee;
+        
+        emit!(AdminAction {
+            admin: ctx.accounts.authority.key(),
+            action: "setCollectionOnboardingFee".to_string(),
+            data: collection_onboarding_fee.to_string(),
+        });
+        
+        Ok(())
+    }
+
+    pub fn set_min_staker_lamports(ctx: Context<UpdateProgramConfig>, min_staker_lamports: u64) -> Result<()> {
+        let staking_program = &mut ctx.accounts.staking_program;
+        staking_program.min_staker_lamports = min_staker_lamports;
+        
+        emit!(AdminAction {
+            admin: ctx.accounts.authority.key(),
+            action: "setMinStakerLamports".to_string(),
+            data: min_staker_lamports.to_string(),
+        });
+        
+        Ok(())
+    }
+
+    // Lower it if batches start running out of compute, raise it as limits allow
+    pub fn set_max_batch_size(ctx: Context<UpdateProgramConfig>, max_batch_size: u16) -> Result<()> {
+        require!(
+            max_batch_size > 0 && max_batch_size as usize <= MAX_BATCH_SIZE_CEILING,
+            StakingError::InvalidAmount
+        );
+        let staking_program = &mut ctx.accounts.staking_program;
+        staking_program.max_batch_size = max_batch_size;
+        
+        emit!(AdminAction {
+            admin: ctx.accounts.authority.key(),
+            action: "setMaxBatchSize".to_string(),
+            data: max_batch_size.to_string(),
+        });
+        
+        Ok(())
+    }
+
+    // Existing positions in unvalidated collections are unaffected; only new stakes are gated
+    pub fn set_require_validation(ctx: Context<UpdateProgramConfig>, require_validation: bool) -> Result<()> {
+        let staking_program = &mut ctx.accounts.staking_program;
+        staking_program.require_validation = require_validation;
+        
+        emit!(AdminAction {
+            admin: ctx.accounts.authority.key(),
+            action: "setRequireValidation".to_string(),
+            data: require_validation.to_string(),
+        });
+        
+        Ok(())
+    }
+
+    pub fn set_max_tickets_per_day(ctx: Context<UpdateProgramConfig>, max_tickets_per_day: u64) -> Result<()> {
+        let staking_program = &mut ctx.accounts.staking_program;
+        staking_program.max_tickets_per_day = max_tickets_per_day;
+        
+        emit!(AdminAction {
+            admin: ctx.accounts.authority.key(),
+            action: "setMaxTicketsPerDay".to_string(),
+            data: max_tickets_per_day.to_string(),
+        });
+        
+        Ok(())
+    }
+
+    pub fn set_claim_fee(ctx: Context<UpdateProgramConfig>, claim_fee_lamports: u64) -> Result<()> {
+        let staking_program = &mut ctx.accounts.staking_program;
+        staking_program.claim_fee_lamports = claim_fee_lamports;
+        
+        emit!(AdminAction {
+            admin: ctx.accounts.authority.key(),
+            action: "setClaimFee".to_string(),
+            data: claim_fee_lamports.to_string(),
+        });
+        
+        Ok(())
+    }
+
+    pub fn set_treasury(ctx: Context<UpdateProgramConfig>, treasury: Pubkey) -> Result<()> {
+        require!(treasury != Pubkey::default(), StakingError::InvalidRecipient);
+        
+        let staking_program = &mut ctx.accounts.staking_program;
+        staking_program.treasury = treasury;
+        
+        emit!(AdminAction {
+            admin: ctx.accounts.authority.key(),
+            action: "setTreasury".to_string(),
+            data: treasury.to_string(),
+        });
+        
+        Ok(())
+    }
+
+    // Total lamports a user needs on top of transaction fees to open a position in the
+    // collection, including the rent of the per-user counters a first stake creates
+    pub fn stake_cost(ctx: Context<GetStakeCost>) -> Result<()> {
+        let rent = Rent::get()?;
+        let position_rent = rent.minimum_balance(StakingPosition::LEN);
+        let mut counter_rent = 0u64;
+        if ctx.accounts.user_stake_count.data_is_empty() {
+            counter_rent += rent.minimum_balance(UserStakeCount::LEN);
+        }
+        if ctx.accounts.user_stats.data_is_empty() {
+            counter_rent += rent.minimum_balance(UserStats::LEN);
+        }
+        let stake_fee_lamports = ctx.accounts.staking_program.stake_fee_lamports;
+        
+        emit!(StakeCost {
+            position_rent,
+            counter_rent,
+            stake_fee_lamports,
+            total_lamports: position_rent
+                .saturating_add(counter_rent)
+                .saturating_add(stake_fee_lamports),
+        });
+        
+        Ok(())
+    }
+
+    // Checks the NFT's Metaplex metadata once and caches the result so stake_nft
+    // can skip the metadata lookup. Re-running it refreshes the cache, which is how
+    // a stale entry is replaced after a collection is re-pointed.
+    pub fn verify_nft_membership(ctx: Context<VerifyNftMembership>) -> Result<()> {
+        let nft_mint = ctx.accounts.nft_mint.key();
+        let collection_mint = ctx.accounts.collection_account.collection_mint;
+        verify_collection_membership(&ctx.accounts.nft_metadata, &nft_mint, &collection_mint)?;
+        
+        let verified_nft = &mut ctx.accounts.verified_nft;
+        verified_nft.nft_mint = nft_mint;
+        verified_nft.collection_mint = collection_mint;
+        verified_nft.verified_at = current_timestamp()?;
+        verified_nft.bump = *ctx.bumps.get("verified_nft").unwrap();
+        
+        emit!(NftVerified {
+            nft_mint,
+            collection_mint,
+            verifier: ctx.accounts.payer.key(),
+        });
+        
+        Ok(())
+    }
+
+    // Goodwill compensation: raises the reward tier of a position without touching its
+    // unlock time. Goes through the same delayed two-step flow as admin_unlock; the
+    // request PDA is keyed by the target tier so the executed tier is the one requested.
+    pub fn admin_upgrade_tier(
+        ctx: Context<AdminUpgradeTier>,
+        new_tier: u8,
+        reason: String,
+    ) -> Result<()> {
+        validate_reason(&reason, MAX_REASON_LEN)?;
+        require!(new_tier <= 2, StakingError::InvalidDuration);
+        
+        let staking_position = &mut ctx.accounts.staking_position;
+        require!(staking_position.is_active, StakingError::PositionNotActive);
+        require!(new_tier > staking_position.reward_tier, StakingError::TierDowngradeNotAllowed);
+        
+        let current_time = current_timestamp()?;
+        
+        let emergency_request = &mut ctx.accounts.emergency_request;
+        if emergency_request.requested_at == 0 {
+            // First request - start emergency delay
+            emergency_request.requester = ctx.accounts.admin.key();
+            emergency_request.requested_at = current_time;
+            emergency_request.reason = reason.clone();
+            emergency_request.executed = false;
+            emergency_request.bump = *ctx.bumps.get("emergency_request").unwrap();
+            
+            let registry = &mut ctx.accounts.active_emergency_requests;
+            registry.bump = *ctx.bumps.get("active_emergency_requests").unwrap();
+            registry.add(emergency_request.key())?;
+            
+            emit!(EmergencyAction {
+                admin: ctx.accounts.admin.key(),
+                action: "tierUpgradeRequested".to_string(),
+                reason,
+            });
+            
+            return Ok(());
+        }
+        
+        require!(current_time >= emergency_request.requested_at + EMERGENCY_DELAY, StakingError::EmergencyDelayNotMet);
+        require!(!emergency_request.executed, StakingError::EmergencyRequestAlreadyExecuted);
+        
+        emergency_request.executed = true;
+        ctx.accounts.active_emergency_requests.remove(&emergency_request.key());
+        
+        let old_tier = staking_position.reward_tier;
+        staking_position.reward_tier = new_tier;
+        
+        // Never lower the locked multiplier, even if the collection config has since changed
+        let tier_multiplier = ctx.accounts.collection_account.multiplier_for(new_tier)?;
+        staking_position.locked_multiplier = staking_position.locked_multiplier.max(tier_multiplier);
+        
+        emit!(TierUpgraded {
+            admin: ctx.accounts.admin.key(),
+            nft_mint: staking_position.nft_mint,
+            old_tier,
+            new_tier,
+            reason: emergency_request.reason.clone(),
+        });
+        
+        emit!(AdminAction {
+            admin: ctx.accounts.admin.key(),
+            action: "adminUpgradeTier".to_string(),
+            data: format!("{},{},{}", staking_position.nft_mint, old_tier, new_tier),
+        });
+        
+        Ok(())
+    }
+
+    // Rewards stay at zero until `vesting_cliff_bps` of the term has elapsed, then vest
+    // linearly from stake time. A position that exits early before its cliff has accrued
+    // nothing, so there is nothing left for an early exit to forfeit.
+    pub fn set_vesting_cliff(ctx: Context<UpdateCollectionConfig>, vesting_cliff_bps: u16) -> Result<()> {
+        require!(vesting_cliff_bps as u64 <= BASIS_POINTS, StakingError::InvalidBasisPoints);
+        
+        let collection_account = &mut ctx.accounts.collection_account;
+        collection_account.vesting_cliff_bps = vesting_cliff_bps;
+        
+        emit!(AdminAction {
+            admin: ctx.accounts.admin.key(),
+            action: "setVestingCliff".to_string(),
+            data: format!("{},{}", collection_account.collection_mint, vesting_cliff_bps),
+        });
+        
+        Ok(())
+    }
+
+    pub fn list_emergency_requests(ctx: Context<ListEmergencyRequests>) -> Result<()> {
+        emit!(ActiveEmergencyRequestsListed {
+            requests: ctx.accounts.active_emergency_requests.requests.clone(),
+        });
+        
+        Ok(())
+    }
+
+    // Lets the owner authorize another key (e.g. a claiming bot) to call claim_nft.
+    // The NFT is always returned to the owner's token account.
+    pub fn set_claim_delegate(ctx: Context<SetClaimDelegate>, delegate: Option<Pubkey>) -> Result<()> {
+        let staking_position = &mut ctx.accounts.staking_position;
+        require!(staking_position.is_active, StakingError::PositionNotActive);
+        staking_position.claim_delegate = delegate;
+        
+        emit!(ClaimDelegateUpdated {
+            owner: ctx.accounts.owner.key(),
+            nft_mint: staking_position.nft_mint,
+            delegate,
+        });
+        
+        Ok(())
+    }
+
+    // Deactivating refuses new stakes; existing positions stay claimable, accruing as set
+    // by set_deactivation_policy. Reactivating records the finished period in
+    // `inactive_periods`, so it stays unpaid, and clears `deactivated_at`. Once the list
+    // is full the oldest period is folded into `folded_inactive_seconds`.
+    pub fn set_collection_active(ctx: Context<SetCollectionActive>, active: bool) -> Result<()> {
+        let current_time = current_timestamp()?;
+        let collection_account = &mut ctx.accounts.collection_account;
+        if collection_account.is_active != active {
+            if active {
+                let period = InactivePeriod {
+                    start: collection_account.deactivated_at,
+                    end: current_time,
+                };
+                if collection_account.inactive_periods.len() >= MAX_INACTIVE_PERIODS {
+                    let oldest = collection_account.inactive_periods.remove(0);
+                    collection_account.fold_inactive_period(oldest)?;
+                }
+                collection_account.inactive_periods.push(period);
+                collection_account.deactivated_at = 0;
+            } else {
+                collection_account.deactivated_at = current_time;
+            }
+            collection_account.is_active = active;
+        }
+        
+        emit!(AdminAction {
+            admin: ctx.accounts.authority.key(),
+            action: "setCollectionActive".to_string(),
+            data: format!("{},{}", collection_account.collection_mint, active),
+        });
+        
+        Ok(())
+    }
+
+    // Decides what deactivation means for positions already staked in the collection:
+    // when honored, they keep accruing under their locked terms; otherwise accrual
+    // stops at `deactivated_at`. New stakes are refused either way.
+    pub fn set_deactivation_policy(
+        ctx: Context<UpdateCollectionConfig>,
+        honor_existing_on_deactivate: bool,
+    ) -> Result<()> {
+        let collection_account = &mut ctx.accounts.collection_account;
+        collection_account.honor_existing_on_deactivate = honor_existing_on_deactivate;
+        
+        emit!(AdminAction {
+            admin: ctx.accounts.admin.key(),
+            action: "setDeactivationPolicy".to_string(),
+            data: format!("{},{}", collection_account.collection_mint, honor_existing_on_deactivate),
+        });
+        
+        Ok(())
+    }
+
+    // Sums accrued-but-unclaimed tickets for the positions passed in `remaining_accounts`
+    // (all belonging to `collection_account`). At most max_batch_size positions are read
+    // per call, so for larger sets this is a partial sum that callers add up off-chain.
+    pub fn total_liability(ctx: Context<TotalLiability>) -> Result<()> {
+        ctx.accounts.staking_program.check_batch_size(ctx.remaining_accounts.len())?;
+        
+        let collection_account = &ctx.accounts.collection_account;
+        let current_time = current_timestamp()?;
+        
+        let mut seen: Vec<Pubkey> = Vec::with_capacity(ctx.remaining_accounts.len());
+        let mut liability: u64 = 0;
+        for account_info in ctx.remaining_accounts.iter() {
+            require!(!seen.contains(account_info.key), StakingError::InvalidPosition);
+            seen.push(*account_info.key);
+            
+            let position = read_position(account_info)?;
+            require!(position.collection_mint == collection_account.collection_mint, StakingError::CollectionNotFound);
+            
+            let (_, unsettled) = unsettled_rewards(&position, collection_account, current_time)?;
+            liability = liability
+                .checked_add(unsettled)
+                .ok_or(StakingError::ArithmeticOverflow)?;
+        }
+        
+        emit!(ProgramLiability {
+            collection_mint: collection_account.collection_mint,
+            positions_counted: seen.len() as u32,
+            liability,
+        });
+        
+        Ok(())
+    }
+
+    // Programmable NFTs (pNFTs) can't move with a plain SPL transfer, so they are staked
+    // through Token Metadata's Transfer, which enforces the collection's rule set.
+    // The vault is the staking program's associated token account for the mint.
+    pub fn stake_pnft(ctx: Context<StakePnft>, duration: u8, terms_hash: [u8; 32]) -> Result<()> {
+        let current_time = current_timestamp()?;
+        
+        check_stake_allowed(
+            ctx.accounts.user.key(),
+            &ctx.accounts.staking_program,
+            &ctx.accounts.collection_account,
+            ctx.accounts.verified_nft.as_deref(),
+            duration,
+            current_time,
+            &terms_hash,
+        )?;
+        check_nft_membership(
+            ctx.accounts.user.key(),
+            &ctx.accounts.nft_mint.key(),
+            &ctx.accounts.collection_account.collection_mint,
+            ctx.accounts.verified_nft.as_deref(),
+            Some(&ctx.accounts.nft_metadata),
+        )?;
+        check_mint_not_blocked(
+            ctx.accounts.user.key(),
+            ctx.accounts.collection_account.collection_mint,
+            &ctx.accounts.blocked_mint,
+        )?;
+        check_account_eligible(
+            &ctx.accounts.user,
+            &ctx.accounts.staking_program,
+            ctx.accounts.collection_account.collection_mint,
+        )?;
+        
+        collect_fee(
+            ctx.accounts.user.to_account_info(),
+            ctx.accounts.treasury.to_account_info(),
+            ctx.accounts.system_program.to_account_info(),
+            ctx.accounts.staking_program.stake_fee_lamports,
+        )?;
+        
+        transfer_pnft(
+            PnftTransferAccounts {
+                token: ctx.accounts.user_token_account.to_account_info(),
+                token_owner: ctx.accounts.user.to_account_info(),
+                destination: ctx.accounts.program_token_account.to_account_info(),
+                destination_owner: ctx.accounts.staking_program.to_account_info(),
+                mint: ctx.accounts.nft_mint.to_account_info(),
+                metadata: ctx.accounts.nft_metadata.to_account_info(),
+                edition: ctx.accounts.nft_edition.to_account_info(),
+                owner_token_record: ctx.accounts.owner_token_record.to_account_info(),
+                destination_token_record: ctx.accounts.destination_token_record.to_account_info(),
+                authority: ctx.accounts.user.to_account_info(),
+                payer: ctx.accounts.user.to_account_info(),
+                system_program: ctx.accounts.system_program.to_account_info(),
+                sysvar_instructions: ctx.accounts.sysvar_instructions.to_account_info(),
+                spl_token_program: ctx.accounts.token_program.to_account_info(),
+                spl_ata_program: ctx.accounts.associated_token_program.to_account_info(),
+                authorization_rules_program: ctx.accounts.authorization_rules_program.to_account_info(),
+                authorization_rules: ctx.accounts.authorization_rules.to_account_info(),
+                token_metadata_program: ctx.accounts.token_metadata_program.to_account_info(),
+            },
+            &[],
+        )?;
+        
+        let staking_position = &mut ctx.accounts.staking_position;
+        staking_position.open(
+            ctx.accounts.user.key(),
+            ctx.accounts.nft_mint.key(),
+            &ctx.accounts.collection_account,
+            duration,
+            current_time,
+            *ctx.bumps.get("staking_position").unwrap(),
+        )?;
+        staking_position.accepted_terms_hash = terms_hash;
+        let unlock_at = staking_position.unlock_at;
+        let locked_multiplier = staking_position.locked_multiplier;
+        
+        ctx.accounts.user_stake_count.record_stake(
+            &ctx.accounts.collection_account,
+            ctx.accounts.user.key(),
+            *ctx.bumps.get("user_stake_count").unwrap(),
+        )?;
+        ctx.accounts.user_stats.record_stake(
+            ctx.accounts.user.key(),
+            *ctx.bumps.get("user_stats").unwrap(),
+        )?;
+        
+        // Update statistics
+        let staking_program = &mut ctx.accounts.staking_program;
+        staking_program.record_stake()?;
+        
+        let collection_account = &mut ctx.accounts.collection_account;
+        collection_account.record_stake()?;
+        
+        emit!(NftStaked {
+            user: ctx.accounts.user.key(),
+            nft_mint: ctx.accounts.nft_mint.key(),
+            collection_mint: collection_account.collection_mint,
+            duration,
+            unlock_at,
+            display_multiplier: locked_multiplier,
+            multiplier_computed: collection_account.computed_multipliers,
+        });
+        
+        emit!(AdminAction {
+            admin: ctx.accounts.user.key(),
+            action: "stakePnft".to_string(),
+            data: format!("{},{}", ctx.accounts.nft_mint.key(), duration),
+        });
+        
+        Ok(())
+    }
+
+    // Counterpart of stake_pnft: a staked pNFT can only leave the vault through Token
+    // Metadata as well, signed by the staking program PDA.
+    pub fn claim_pnft(ctx: Context<ClaimPnft>) -> Result<()> {
+        let current_time = current_timestamp()?;
+        let staking_position = &mut ctx.accounts.staking_position;
+        check_claim_allowed(&ctx.accounts.staking_program, staking_position, &ctx.accounts.user.key(), current_time)?;
+        
+        // Mark position as inactive
+        staking_position.is_active = false;
+        
+        let bump = ctx.accounts.staking_program.bump;
+        let seeds: &[&[u8]] = &[b"staking_program".as_ref(), &[bump]];
+        transfer_pnft(
+            PnftTransferAccounts {
+                token: ctx.accounts.program_token_account.to_account_info(),
+                token_owner: ctx.accounts.staking_program.to_account_info(),
+                destination: ctx.accounts.owner_token_account.to_account_info(),
+                destination_owner: ctx.accounts.owner.to_account_info(),
+                mint: ctx.accounts.nft_mint.to_account_info(),
+                metadata: ctx.accounts.nft_metadata.to_account_info(),
+                edition: ctx.accounts.nft_edition.to_account_info(),
+                owner_token_record: ctx.accounts.owner_token_record.to_account_info(),
+                destination_token_record: ctx.accounts.destination_token_record.to_account_info(),
+                authority: ctx.accounts.staking_program.to_account_info(),
+                payer: ctx.accounts.user.to_account_info(),
+                system_program: ctx.accounts.system_program.to_account_info(),
+                sysvar_instructions: ctx.accounts.sysvar_instructions.to_account_info(),
+                spl_token_program: ctx.accounts.token_program.to_account_info(),
+                spl_ata_program: ctx.accounts.associated_token_program.to_account_info(),
+                authorization_rules_program: ctx.accounts.authorization_rules_program.to_account_info(),
+                authorization_rules: ctx.accounts.authorization_rules.to_account_info(),
+                token_metadata_program: ctx.accounts.token_metadata_program.to_account_info(),
+            },
+            &[seeds],
+        )?;
+        
+        // Update statistics; a swept position was already taken out of the counters
+        let staking_program = &mut ctx.accounts.staking_program;
+        let collection_account = &mut ctx.accounts.collection_account;
+        let user_stats = &mut ctx.accounts.user_stats;
+        user_stats.fill(staking_position.owner, *ctx.bumps.get("user_stats").unwrap());
+        if !staking_position.swept {
+            staking_program.record_unstake()?;
+            collection_account.record_unstake()?;
+            ctx.accounts.user_stake_count.record_unstake()?;
+            user_stats.record_unstake()?;
+        }
+        
+        emit!(NftClaimed {
+            user: staking_position.owner,
+            nft_mint: staking_position.nft_mint,
+            collection_mint: staking_position.collection_mint,
+            total_rewards_earned: staking_position.total_rewards_earned,
+            staked_at: staking_position.staked_at,
+            unlock_at: staking_position.unlock_at,
+            actual_duration_held: elapsed_since(staking_position.staked_at, current_time),
+        });
+        
+        emit!(AdminAction {
+            admin: ctx.accounts.user.key(),
+            action: "claimPnft".to_string(),
+            data: staking_position.nft_mint.to_string(),
+        });
+        
+        Ok(())
+    }
+
+    // Upper bound on how far ahead any new stake may unlock, guarding users against
+    // accidentally locking an NFT for decades.
+    pub fn set_max_lock_duration(ctx: Context<UpdateProgramConfig>, max_lock_duration: i64) -> Result<()> {
+        require!(max_lock_duration > 0, StakingError::InvalidDuration);
+        
+        let staking_program = &mut ctx.accounts.staking_program;
+        staking_program.max_lock_duration = max_lock_duration;
+        
+        emit!(AdminAction {
+            admin: ctx.accounts.authority.key(),
+            action: "setMaxLockDuration".to_string(),
+            data: max_lock_duration.to_string(),
+        });
+        
+        Ok(())
+    }
+
+    // Locks part of a position's earned rewards for a fixed term. At maturity the owner
+    // gets the principal back plus yield at the reward-staking multiplier: the principal
+    // is unlocked and the yield is settled onto the position, like claim_rewards.
+    pub fn stake_rewards(ctx: Context<StakeRewards>, duration: u8, amount: u64) -> Result<()> {
+        require!(
+            !ctx.accounts.staking_program.is_instruction_paused(PAUSE_STAKE, current_timestamp()?),
+            StakingError::ContractPaused
+        );
+        require!(amount > 0, StakingError::InvalidAmount);
+        require!(ctx.accounts.collection_account.is_active, StakingError::CollectionNotActive);
+        let multiplier = *REWARD_STAKE_MULTIPLIERS.get(duration as usize).ok_or(StakingError::InvalidDuration)?;
+        
+        let staking_position = &mut ctx.accounts.staking_position;
+        require!(staking_position.is_active, StakingError::PositionNotActive);
+        let available = staking_position.mintable_tickets();
+        require!(amount <= available, StakingError::InsufficientRewards);
+        staking_position.rewards_staked = staking_position
+            .rewards_staked
+            .checked_add(amount)
+            .ok_or(StakingError::ArithmeticOverflow)?;
+        
+        let current_time = current_timestamp()?;
+        let unlock_at = current_time + staking_duration_seconds(duration)?;
+        ctx.accounts.staking_program.check_lock_duration(unlock_at, current_time)?;
+        
+        let reward_stake = &mut ctx.accounts.reward_stake;
+        reward_stake.owner = ctx.accounts.user.key();
+        reward_stake.staking_position = staking_position.key();
+        reward_stake.amount = amount;
+        reward_stake.staked_at = current_time;
+        reward_stake.unlock_at = unlock_at;
+        reward_stake.duration = duration;
+        reward_stake.locked_multiplier = multiplier;
+        reward_stake.bump = *ctx.bumps.get("reward_stake").unwrap();
+        
+        emit!(RewardsStaked {
+            user: ctx.accounts.user.key(),
+            nft_mint: staking_position.nft_mint,
+            amount,
+            duration,
+            unlock_at,
+            multiplier,
+        });
+        
+        Ok(())
+    }
+
+    // Closes a matured reward stake: the principal becomes mintable again and the yield
+    // (payout - principal) is credited to the position as newly settled tickets
+    pub fn claim_reward_stake(ctx: Context<ClaimRewardStake>) -> Result<()> {
+        require!(
+            !ctx.accounts.staking_program.is_instruction_paused(PAUSE_CLAIM_REWARDS, current_timestamp()?),
+            StakingError::ContractPaused
+        );
+        
+        let reward_stake = &ctx.accounts.reward_stake;
+        let current_time = current_timestamp()?;
+        require!(current_time >= reward_stake.unlock_at, StakingError::StakingPeriodNotCompleted);
+        
+        let payout = (reward_stake.amount as u128)
+            .checked_mul(reward_stake.locked_multiplier as u128)
+            .ok_or(StakingError::ArithmeticOverflow)?
+            / BASIS_POINTS as u128;
+        let payout = u64::try_from(payout).map_err(|_| StakingError::ArithmeticOverflow)?;
+        
+        let yield_rewards = payout.saturating_sub(reward_stake.amount);
+        
+        let staking_position = &mut ctx.accounts.staking_position;
+        staking_position.rewards_staked = staking_position.rewards_staked.saturating_sub(reward_stake.amount);
+        staking_position.total_rewards_earned = staking_position
+            .total_rewards_earned
+            .checked_add(yield_rewards)
+            .ok_or(StakingError::ArithmeticOverflow)?;
+        ctx.accounts.staking_program.record_settled(yield_rewards)?;
+        ctx.accounts.user_stats.fill(ctx.accounts.user.key(), *ctx.bumps.get("user_stats").unwrap());
+        ctx.accounts.user_stats.record_rewards(yield_rewards)?;
+        
+        emit!(RewardStakeClaimed {
+            user: ctx.accounts.user.key(),
+            nft_mint: staking_position.nft_mint,
+            principal: reward_stake.amount,
+            payout,
+        });
+        
+        Ok(())
+    }
+
+    pub fn add_cpi_caller(ctx: Context<UpdateProgramConfig>, program_id: Pubkey) -> Result<()> {
+        require!(program_id != crate::ID, StakingError::InvalidRecipient);
+        
+        let staking_program = &mut ctx.accounts.staking_program;
+        if !staking_program.cpi_whitelist.contains(&program_id) {
+            require!(
+                staking_program.cpi_whitelist.len() < MAX_CPI_CALLERS,
+                StakingError::CpiWhitelistFull
+            );
+            staking_program.cpi_whitelist.push(program_id);
+        }
+        
+        emit!(AdminAction {
+            admin: ctx.accounts.authority.key(),
+            action: "addCpiCaller".to_string(),
+            data: program_id.to_string(),
+        });
+        
+        Ok(())
+    }
+
+    pub fn remove_cpi_caller(ctx: Context<UpdateProgramConfig>, program_id: Pubkey) -> Result<()> {
+        let staking_program = &mut ctx.accounts.staking_program;
+        staking_program.cpi_whitelist.retain(|key| *key != program_id);
+        
+        emit!(AdminAction {
+            admin: ctx.accounts.authority.key(),
+            action: "removeCpiCaller".to_string(),
+            data: program_id.to_string(),
+        });
+        
+        Ok(())
+    }
+
+    // Records a user's earned rewards under a campaign id for off-chain airdrop
+    // distribution. The user's positions are passed (writable) in remaining_accounts; a
+    // snapshot can only be written once per (snapshot_id, user). Campaign ids increase,
+    // and each position records the last one it was counted in, so a position moved to
+    // another owner can't be counted twice for the same campaign.
+    pub fn snapshot_user_rewards(ctx: Context<SnapshotUserRewards>, snapshot_id: u64, user: Pubkey) -> Result<()> {
+        let mut seen: Vec<Pubkey> = Vec::with_capacity(ctx.remaining_accounts.len());
+        let mut total_rewards_earned: u64 = 0;
+        for account_info in ctx.remaining_accounts.iter() {
+            require!(!seen.contains(account_info.key), StakingError::InvalidPosition);
+            require!(account_info.is_writable, StakingError::InvalidPosition);
+            seen.push(*account_info.key);
+            
+            let mut position = read_position(account_info)?;
+            require!(position.owner == user, StakingError::NotPositionOwner);
+            require!(snapshot_id > position.last_snapshot_id, StakingError::PositionAlreadySnapshotted);
+            position.last_snapshot_id = snapshot_id;
+            write_position(account_info, &position)?;
+            
+            total_rewards_earned = total_rewards_earned
+                .checked_add(position.total_rewards_earned)
+                .ok_or(StakingError::ArithmeticOverflow)?;
+        }
+        
+        let taken_at = current_timestamp()?;
+        let reward_snapshot = &mut ctx.accounts.reward_snapshot;
+        reward_snapshot.snapshot_id = snapshot_id;
+        reward_snapshot.user = user;
+        reward_snapshot.total_rewards_earned = total_rewards_earned;
+        reward_snapshot.positions_counted = seen.len() as u32;
+        reward_snapshot.taken_at = taken_at;
+        reward_snapshot.bump = *ctx.bumps.get("reward_snapshot").unwrap();
+        
+        emit!(RewardSnapshotTaken {
+            snapshot_id,
+            user,
+            total_rewards_earned,
+            positions_counted: seen.len() as u32,
+            taken_at,
+        });
+        
+        Ok(())
+    }
+
+    pub fn set_positions_transferable(ctx: Context<UpdateCollectionConfig>, positions_transferable: bool) -> Result<()> {
+        let collection_account = &mut ctx.accounts.collection_account;
+        collection_account.positions_transferable = positions_transferable;
+        
+        emit!(AdminAction {
+            admin: ctx.accounts.admin.key(),
+            action: "setPositionsTransferable".to_string(),
+            data: format!("{},{}", collection_account.collection_mint, positions_transferable),
+        });
+        
+        Ok(())
+    }
+
+    // Reassigns every position in `remaining_accounts` (writable, owned by the signer, all
+    // in `collection_account`) to `new_owner` in one instruction, for bundle settlement.
+    // The position PDA keeps its original seeds; only the `owner` field changes, and any
+    // claim delegate is cleared. Counters move as in transfer_position.
+    pub fn batch_transfer_positions(ctx: Context<BatchTransferPositions>, new_owner: Pubkey) -> Result<()> {
+        ctx.accounts.staking_program.check_batch_size(ctx.remaining_accounts.len())?;
+        require!(ctx.accounts.collection_account.positions_transferable, StakingError::PositionNotTransferable);
+        require!(new_owner != Pubkey::default(), StakingError::InvalidRecipient);
+        require!(new_owner != ctx.accounts.owner.key(), StakingError::InvalidRecipient);
+        
+        let owner = ctx.accounts.owner.key();
+        let collection_mint = ctx.accounts.collection_account.collection_mint;
+        
+        let mut seen: Vec<Pubkey> = Vec::with_capacity(ctx.remaining_accounts.len());
+        let mut moved: u64 = 0;
+        for account_info in ctx.remaining_accounts.iter() {
+            require!(!seen.contains(account_info.key), StakingError::InvalidPosition);
+            require!(account_info.is_writable, StakingError::InvalidPosition);
+            seen.push(*account_info.key);
+            
+            let mut position = read_position(account_info)?;
+            require!(position.owner == owner, StakingError::NotPositionOwner);
+            require!(position.collection_mint == collection_mint, StakingError::CollectionNotFound);
+            position.transfer_to(new_owner)?;
+            write_position(account_info, &position)?;
+            if !position.swept {
+                moved += 1;
+            }
+            
+            emit!(PositionTransferred {
+                nft_mint: position.nft_mint,
+                from: owner,
+                to: new_owner,
+            });
+        }
+        
+        ctx.accounts.owner_stake_count.record_transfer_out(moved);
+        ctx.accounts.new_owner_stake_count.record_transfer_in(
+            &ctx.accounts.collection_account,
+            new_owner,
+            *ctx.bumps.get("new_owner_stake_count").unwrap(),
+            moved,
+        )?;
+        ctx.accounts.owner_stats.fill(ctx.accounts.owner.key(), *ctx.bumps.get("owner_stats").unwrap());
+        ctx.accounts.owner_stats.record_moved_out(moved);
+        ctx.accounts.new_owner_stats.record_moved_in(new_owner, *ctx.bumps.get("new_owner_stats").unwrap(), moved)?;
+        
+        emit!(PositionsBatchTransferred {
+            collection_mint,
+            from: owner,
+            to: new_owner,
+            count: seen.len() as u32,
+        });
+        
+        Ok(())
+    }
+
+    // Rewrites a position created under an older layout in the current one, growing the
+    // account and topping up rent from `payer`. Anyone may call it; the result is fully
+    // determined by the old data and the position's collection. The layout is identified
+    // by the account size (see StakingPosition::LAYOUT_LENS), since layouts 1 to 6 all
+    // stored version 1; a current-size account only has its version brought up to date.
+    pub fn migrate_position(ctx: Context<MigratePosition>) -> Result<()> {
+        let position_info = ctx.accounts.staking_position.to_account_info();
+        require!(*position_info.owner == crate::ID, StakingError::InvalidPosition);
+        
+        let (mut position, from_version) = {
+            let data = position_info.try_borrow_data()?;
+            require!(
+                data.len() >= 8 && data[..8] == <StakingPosition as anchor_lang::Discriminator>::DISCRIMINATOR,
+                StakingError::InvalidPosition
+            );
+            if data.len() == StakingPositionV0::LEN {
+                let legacy = StakingPositionV0::deserialize(&mut &data[8..])?;
+                require!(
+                    legacy.collection_mint == ctx.accounts.collection_account.collection_mint,
+                    StakingError::CollectionNotFound
+                );
+                (legacy.upgrade(&ctx.accounts.collection_account)?, 0)
+            } else {
+                let layout = StakingPosition::LAYOUT_LENS
+                    .iter()
+                    .position(|len| *len == data.len())
+                    .ok_or(StakingError::InvalidPosition)? as u8
+                    + 1;
+                let position = StakingPosition::read_layout(layout, &data[8..])?;
+                require!(
+                    layout < StakingPosition::CURRENT_VERSION || position.version < StakingPosition::CURRENT_VERSION,
+                    StakingError::PositionAlreadyMigrated
+                );
+                require!(
+                    position.collection_mint == ctx.accounts.collection_account.collection_mint,
+                    StakingError::CollectionNotFound
+                );
+                let from_version = if layout == StakingPosition::CURRENT_VERSION { position.version } else { layout };
+                (position, from_version)
+            }
+        };
+        position.version = StakingPosition::CURRENT_VERSION;
+        
+        let rent_needed = Rent::get()?
+            .minimum_balance(StakingPosition::LEN)
+            .saturating_sub(position_info.lamports());
+        if rent_needed > 0 {
+            let rent_accounts = system_program::Transfer {
+                from: ctx.accounts.payer.to_account_info(),
+                to: position_info.clone(),
+            };
+            let rent_ctx = CpiContext::new(ctx.accounts.system_program.to_account_info(), rent_accounts);
+            system_program::transfer(rent_ctx, rent_needed)?;
+        }
+        position_info.realloc(StakingPosition::LEN, false)?;
+        write_position(&position_info, &position)?;
+        
+        emit!(PositionVersionUpgraded {
+            nft_mint: position.nft_mint,
+            from_version,
+            to_version: position.version,
+        });
+        
+        Ok(())
+    }
+
+    pub fn set_min_validated_collections(ctx: Context<UpdateProgramConfig>, min_validated_collections: u64) -> Result<()> {
+        let staking_program = &mut ctx.accounts.staking_program;
+        staking_program.min_validated_collections = min_validated_collections;
+        
+        emit!(AdminAction {
+            admin: ctx.accounts.authority.key(),
+            action: "setMinValidatedCollections".to_string(),
+            data: min_validated_collections.to_string(),
+        });
+        
+        Ok(())
+    }
+
+    // Creates several collections in one transaction. `remaining_accounts` holds the
+    // collection PDAs in the same order as `collection_mints` and `tickets` (6m, 12m, 3y
+    // per entry). The whole batch is one authority action, exactly like a single
+    // add_collection.
+    pub fn batch_add_collections<'info>(
+        ctx: Context<'_, '_, '_, 'info, BatchAddCollections<'info>>,
+        collection_mints: Vec<Pubkey>,
+        tickets: Vec<[u64; 3]>,
+    ) -> Result<()> {
+        require!(!ctx.accounts.staking_program.is_paused_at(current_timestamp()?), StakingError::ContractPaused);
+        ctx.accounts.staking_program.check_batch_size(collection_mints.len())?;
+        require!(
+            tickets.len() == collection_mints.len() && ctx.remaining_accounts.len() == collection_mints.len(),
+            StakingError::InvalidAmount
+        );
+        require_single_signer(&ctx.accounts.staking_program)?;
+        
+        for ((collection_mint, collection_tickets), collection_info) in collection_mints
+            .iter()
+            .zip(tickets.iter())
+            .zip(ctx.remaining_accounts.iter())
+        {
+            create_collection_account(
+                ctx.accounts.authority.to_account_info(),
+                collection_info,
+                ctx.accounts.system_program.to_account_info(),
+                *collection_mint,
+                *collection_tickets,
+            )?;
+            
+            emit!(CollectionAdded {
+                collection_mint: *collection_mint,
+                six_month_tickets: collection_tickets[0],
+                twelve_month_tickets: collection_tickets[1],
+                three_year_tickets: collection_tickets[2],
+                onboarding_fee: 0,
+            });
+        }
+        
+        let staking_program = &mut ctx.accounts.staking_program;
+        staking_program.total_collections = staking_program
+            .total_collections
+            .checked_add(collection_mints.len() as u64)
+            .ok_or(StakingError::ArithmeticOverflow)?;
+        
+        emit!(AdminAction {
+            admin: ctx.accounts.authority.key(),
+            action: "batchAddCollections".to_string(),
+            data: collection_mints.len().to_string(),
+        });
+        
+        Ok(())
+    }
+
+    // Dry run of the NFT claim checks for `claimer`. Emits ClaimEligibility with the first
+    // blocker (see the CLAIM_* codes) so clients can explain a failed claim up front.
+    pub fn can_claim(ctx: Context<CanClaim>, claimer: Pubkey) -> Result<()> {
+        let staking_position = &ctx.accounts.staking_position;
+        let current_time = current_timestamp()?;
+        let reason = claim_blocker(&ctx.accounts.staking_program, staking_position, &claimer, current_time)
+            .map_or(CLAIM_OK, |(reason, _)| reason);
+        
+        emit!(ClaimEligibility {
+            nft_mint: staking_position.nft_mint,
+            claimer,
+            can_claim: reason == CLAIM_OK,
+            reason,
+            unlock_at: staking_position.unlock_at,
+        });
+        
+        Ok(())
+    }
+
+    // Freezes or resumes reward accrual on a single disputed position without touching
+    // its lock. Pausing an already paused position (or resuming a running one) is a no-op.
+    pub fn set_position_accrual_paused(ctx: Context<SetPositionAccrualPaused>, paused: bool) -> Result<()> {
+        let current_time = current_timestamp()?;
+        let staking_position = &mut ctx.accounts.staking_position;
+        if staking_position.accrual_paused == paused {
+            return Ok(());
+        }
+        
+        if paused {
+            staking_position.accrual_paused = true;
+            staking_position.accrual_paused_at = current_time;
+            
+            emit!(PositionAccrualPaused {
+                admin: ctx.accounts.admin.key(),
+                nft_mint: staking_position.nft_mint,
+                paused_at: current_time,
+            });
+        } else {
+            let paused_for = staking_position.resume_accrual(current_time);
+            
+            emit!(PositionAccrualResumed {
+                admin: ctx.accounts.admin.key(),
+                nft_mint: staking_position.nft_mint,
+                paused_for,
+                accrual_paused_total: staking_position.accrual_paused_total,
+            });
+        }
+        
+        Ok(())
+    }
+
+    // The floor can't exceed the collection's lowest tier multiplier, so it only ever
+    // lifts positions that locked in below the current schedule.
+    pub fn set_min_multiplier(ctx: Context<UpdateCollectionConfig>, min_multiplier_bps: u64) -> Result<()> {
+        let collection_account = &mut ctx.accounts.collection_account;
+        let base_multiplier = collection_account
+            .six_month_multiplier
+            .min(collection_account.twelve_month_multiplier)
+            .min(collection_account.three_year_multiplier);
+        require!(min_multiplier_bps <= base_multiplier, StakingError::InvalidBasisPoints);
+        
+        collection_account.min_multiplier_bps = min_multiplier_bps;
+        
+        emit!(AdminAction {
+            admin: ctx.accounts.admin.key(),
+            action: "setMinMultiplier".to_string(),
+            data: format!("{},{}", collection_account.collection_mint, min_multiplier_bps),
+        });
+        
+        Ok(())
+    }
+
+    // Compares what the vault token account for a mint holds against the active positions
+    // for that mint, passed in `remaining_accounts`. Read-only; emits VaultAudit with any
+    // surplus or deficit.
+    pub fn audit_vault(ctx: Context<AuditVault>) -> Result<()> {
+        ctx.accounts.staking_program.check_batch_size(ctx.remaining_accounts.len())?;
+        
+        let nft_mint = ctx.accounts.program_token_account.mint;
+        let mut seen: Vec<Pubkey> = Vec::with_capacity(ctx.remaining_accounts.len());
+        let mut expected: u64 = 0;
+        for account_info in ctx.remaining_accounts.iter() {
+            require!(!seen.contains(account_info.key), StakingError::InvalidPosition);
+            seen.push(*account_info.key);
+            
+            let position = read_position(account_info)?;
+            require!(position.nft_mint == nft_mint, StakingError::InvalidPosition);
+            if position.is_active {
+                expected += 1;
+            }
+        }
+        
+        let vault_amount = ctx.accounts.program_token_account.amount;
+        emit!(VaultAudit {
+            nft_mint,
+            vault: ctx.accounts.program_token_account.key(),
+            vault_amount,
+            expected,
+            surplus: vault_amount.saturating_sub(expected),
+            deficit: expected.saturating_sub(vault_amount),
+        });
+        
+        Ok(())
+    }
+
+    // Changing the curve re-shapes accrual for open positions too, since rewards are
+    // computed from the curve at settlement time.
+    pub fn set_distribution_curve(ctx: Context<UpdateCollectionConfig>, distribution_curve: u8) -> Result<()> {
+        require!(distribution_curve <= CURVE_BACK_LOADED, StakingError::InvalidDistributionCurve);
+        
+        let collection_account = &mut ctx.accounts.collection_account;
+        collection_account.distribution_curve = distribution_curve;
+        
+        emit!(AdminAction {
+            admin: ctx.accounts.admin.key(),
+            action: "setDistributionCurve".to_string(),
+            data: format!("{},{}", collection_account.collection_mint, distribution_curve),
+        });
+        
+        Ok(())
+    }
+
+    // Re-points a collection at a re-minted collection NFT. The CollectionAccount keeps its
+    // address (still derived from the original mint); only `collection_mint` changes.
+    // Positions still carrying the old mint are moved over in batches through
+    // `remaining_accounts`, here or later with sync_position_collection_mint.
+    pub fn migrate_collection_mint(ctx: Context<MigrateCollectionMint>, new_mint: Pubkey) -> Result<()> {
+        let collection_account = &mut ctx.accounts.collection_account;
+        let old_mint = collection_account.collection_mint;
+        require!(new_mint != old_mint && new_mint != Pubkey::default(), StakingError::InvalidCollectionMint);
+        
+        collection_account.previous_collection_mint = old_mint;
+        collection_account.collection_mint = new_mint;
+        
+        ctx.accounts.staking_program.check_batch_size(ctx.remaining_accounts.len())?;
+        let positions_updated = retarget_positions(collection_account, ctx.remaining_accounts)?;
+        
+        emit!(CollectionMintMigrated {
+            old_mint,
+            new_mint,
+            positions_updated,
+        });
+        
+        emit!(AdminAction {
+            admin: ctx.accounts.authority.key(),
+            action: "migrateCollectionMint".to_string(),
+            data: format!("{},{}", old_mint, new_mint),
+        });
+        
+        Ok(())
+    }
+
+    // Moves further batches of positions onto a migrated collection's new mint. Anyone may
+    // call it: it only applies the migration the authority already recorded.
+    pub fn sync_position_collection_mint(ctx: Context<SyncPositionCollectionMint>) -> Result<()> {
+        let collection_account = &ctx.accounts.collection_account;
+        require!(
+            collection_account.previous_collection_mint != Pubkey::default(),
+            StakingError::InvalidCollectionMint
+        );
+        
+        ctx.accounts.staking_program.check_batch_size(ctx.remaining_accounts.len())?;
+        let positions_updated = retarget_positions(collection_account, ctx.remaining_accounts)?;
+        
+        emit!(CollectionMintMigrated {
+            old_mint: collection_account.previous_collection_mint,
+            new_mint: collection_account.collection_mint,
+            positions_updated,
+        });
+        
+        Ok(())
+    }
+
+    pub fn set_terms_hash(ctx: Context<UpdateProgramConfig>, terms_hash: [u8; 32]) -> Result<()> {
+        let staking_program = &mut ctx.accounts.staking_program;
+        staking_program.current_terms_hash = terms_hash;
+        
+        emit!(AdminAction {
+            admin: ctx.accounts.authority.key(),
+            action: "setTermsHash".to_string(),
+            data: terms_hash.iter().map(|byte| format!("{:02x}", byte)).collect(),
+        });
+        
+        Ok(())
+    }
+
+    pub fn set_reward_oracle(ctx: Context<UpdateProgramConfig>, reward_oracle: Pubkey) -> Result<()> {
+        let staking_program = &mut ctx.accounts.staking_program;
+        staking_program.reward_oracle = reward_oracle;
+        
+        emit!(AdminAction {
+            admin: ctx.accounts.authority.key(),
+            action: "setRewardOracle".to_string(),
+            data: reward_oracle.to_string(),
+        });
+        
+        Ok(())
+    }
+
+    // Credits amounts computed off-chain to positions (`amounts[i]` goes to the i-th
+    // position). `remaining_accounts` holds one group per position: the position, then
+    // its owner's UserStats PDA. The instruction right before this one must be an
+    // Ed25519 program instruction in which the reward oracle signs
+    // `program_id || nonce || (position || amount)*`, and `nonce` must be higher than the
+    // last one used, so a signed batch can't be replayed.
+    pub fn credit_rewards(ctx: Context<CreditRewards>, nonce: u64, amounts: Vec<u64>) -> Result<()> {
+        ctx.accounts.staking_program.check_batch_size(amounts.len())?;
+        require!(
+            amounts.len() * REWARD_GROUP_LEN == ctx.remaining_accounts.len(),
+            StakingError::InvalidAmount
+        );
+        let groups = ctx.remaining_accounts.chunks(REWARD_GROUP_LEN);
+        
+        let staking_program = &mut ctx.accounts.staking_program;
+        require!(staking_program.reward_oracle != Pubkey::default(), StakingError::InvalidOracleSignature);
+        require!(nonce > staking_program.oracle_nonce, StakingError::StaleNonce);
+        
+        let mut message = Vec::with_capacity(32 + 8 + amounts.len() * 40);
+        message.extend_from_slice(crate::ID.as_ref());
+        message.extend_from_slice(&nonce.to_le_bytes());
+        for (group, amount) in groups.clone().zip(amounts.iter()) {
+            message.extend_from_slice(group[0].key.as_ref());
+            message.extend_from_slice(&amount.to_le_bytes());
+        }
+        verify_oracle_signature(&ctx.accounts.sysvar_instructions, &staking_program.reward_oracle, &message)?;
+        staking_program.oracle_nonce = nonce;
+        
+        for (group, amount) in groups.zip(amounts.iter()) {
+            let (account_info, user_stats) = (&group[0], &group[1]);
+            require!(account_info.is_writable, StakingError::InvalidPosition);
+            let mut position = read_position(account_info)?;
+            position.total_rewards_earned = position
+                .total_rewards_earned
+                .checked_add(*amount)
+                .ok_or(StakingError::ArithmeticOverflow)?;
+            write_position(account_info, &position)?;
+            update_user_stats(user_stats, &position.owner, |user_stats| user_stats.record_rewards(*amount))?;
+            
+            emit!(RewardsCredited {
+                nft_mint: position.nft_mint,
+                owner: position.owner,
+                amount: *amount,
+                total_rewards_earned: position.total_rewards_earned,
+                nonce,
+            });
+        }
+        
+        Ok(())
+    }
+
+    // Blocks staking for a single flagged NFT (e.g. reported stolen). Positions already
+    // open for the mint are not affected.
+    pub fn block_mint(ctx: Context<BlockMint>) -> Result<()> {
+        let blocked_mint = &mut ctx.accounts.blocked_mint;
+        blocked_mint.nft_mint = ctx.accounts.nft_mint.key();
+        blocked_mint.blocked_by = ctx.accounts.admin.key();
+        blocked_mint.blocked_at = current_timestamp()?;
+        blocked_mint.bump = *ctx.bumps.get("blocked_mint").unwrap();
+        
+        emit!(MintBlocked {
+            admin: ctx.accounts.admin.key(),
+            nft_mint: blocked_mint.nft_mint,
+        });
+        
+        Ok(())
+    }
+
+    pub fn unblock_mint(ctx: Context<UnblockMint>) -> Result<()> {
+        emit!(MintUnblocked {
+            admin: ctx.accounts.admin.key(),
+            nft_mint: ctx.accounts.blocked_mint.nft_mint,
+        });
+        
+        Ok(())
+    }
+
+    // Off by default (0). Takes effect at settlement, so it also covers positions opened
+    // before it was configured, as long as they predate the first validation.
+    pub fn set_early_adopter_bonus(ctx: Context<UpdateCollectionConfig>, early_adopter_bonus_bps: u16) -> Result<()> {
+        require!(early_adopter_bonus_bps as u64 <= BASIS_POINTS, StakingError::InvalidBasisPoints);
+        
+        let collection_account = &mut ctx.accounts.collection_account;
+        collection_account.early_adopter_bonus_bps = early_adopter_bonus_bps;
+        
+        emit!(AdminAction {
+            admin: ctx.accounts.admin.key(),
+            action: "setEarlyAdopterBonus".to_string(),
+            data: format!("{},{}", collection_account.collection_mint, early_adopter_bonus_bps),
+        });
+        
+        Ok(())
+    }
+
+    // Canonical position address for (nft_mint, user), so integrators can check their
+    // own derivation against the program's
+    pub fn derive_position(_ctx: Context<DerivePosition>, nft_mint: Pubkey, user: Pubkey) -> Result<()> {
+        let (position, bump) = Pubkey::find_program_address(
+            &[b"staking_position", nft_mint.as_ref(), user.as_ref()],
+            &crate::ID,
+        );
+        
+        emit!(PositionPda {
+            nft_mint,
+            user,
+            position,
+            bump,
+        });
+        
+        Ok(())
+    }
+
+    pub fn set_accrue_from_validation(ctx: Context<UpdateCollectionConfig>, accrue_from_validation: bool) -> Result<()> {
+        let collection_account = &mut ctx.accounts.collection_account;
+        collection_account.accrue_from_validation = accrue_from_validation;
+        
+        emit!(AdminAction {
+            admin: ctx.accounts.admin.key(),
+            action: "setAccrueFromValidation".to_string(),
+            data: format!("{},{}", collection_account.collection_mint, accrue_from_validation),
+        });
+        
+        Ok(())
+    }
+
+    pub fn set_unpause_threshold(ctx: Context<UpdateProgramConfig>, unpause_threshold: u8) -> Result<()> {
+        require!(
+            unpause_threshold >= 1 && unpause_threshold as usize <= MAX_UNPAUSE_APPROVERS,
+            StakingError::InvalidThreshold
+        );
+        
+        let staking_program = &mut ctx.accounts.staking_program;
+        staking_program.unpause_threshold = unpause_threshold;
+        
+        emit!(AdminAction {
+            admin: ctx.accounts.authority.key(),
+            action: "setUnpauseThreshold".to_string(),
+            data: unpause_threshold.to_string(),
+        });
+        
+        Ok(())
+    }
+
+    pub fn set_early_claim_penalty(ctx: Context<UpdateCollectionConfig>, early_claim_penalty_bps: u16) -> Result<()> {
+        require!(early_claim_penalty_bps as u64 <= BASIS_POINTS, StakingError::InvalidBasisPoints);
+        
+        let collection_account = &mut ctx.accounts.collection_account;
+        collection_account.early_claim_penalty_bps = early_claim_penalty_bps;
+        
+        emit!(AdminAction {
+            admin: ctx.accounts.admin.key(),
+            action: "setEarlyClaimPenalty".to_string(),
+            data: format!("{},{}", collection_account.collection_mint, early_claim_penalty_bps),
+        });
+        
+        Ok(())
+    }
+
+    // Returns the position's key fields as return data for calling programs. Anchor
+    // writes the Borsh encoding of PositionView via set_return_data; callers read it
+    // with get_return_data (program id = this program) and deserialize PositionView.
+    pub fn get_position(ctx: Context<GetPosition>) -> Result<PositionView> {
+        let staking_position = &ctx.accounts.staking_position;
+        Ok(PositionView {
+            position: staking_position.key(),
+            owner: staking_position.owner,
+            nft_mint: staking_position.nft_mint,
+            collection_mint: staking_position.collection_mint,
+            staked_at: staking_position.staked_at,
+            unlock_at: staking_position.unlock_at,
+            duration: staking_position.duration,
+            reward_tier: staking_position.reward_tier,
+            is_active: staking_position.is_active,
+            total_rewards_earned: staking_position.total_rewards_earned,
+            locked_multiplier: staking_position.locked_multiplier,
+            version: staking_position.version,
+        })
+    }
+
+    // Renews an expiring validation for another validity period
+    pub fn revalidate_collection(ctx: Context<ValidateCollection>) -> Result<()> {
+        require!(!ctx.accounts.staking_program.is_paused_at(current_timestamp()?), StakingError::ContractPaused);
+        
+        let collection_account = &mut ctx.accounts.collection_account;
+        require!(collection_account.is_validated, StakingError::CollectionNotValidated);
+        collection_account.validation_expires_at = collection_account.next_validation_expiry(current_timestamp()?);
+        
+        emit!(CollectionValidated {
+            collection_mint: collection_account.collection_mint,
+            validated: true,
+            validation_expires_at: collection_account.validation_expires_at,
+        });
+        
+        emit!(AdminAction {
+            admin: ctx.accounts.authority.key(),
+            action: "revalidateCollection".to_string(),
+            data: format!("{},{}", collection_account.collection_mint, collection_account.validation_expires_at),
+        });
+        
+        Ok(())
+    }
+
+    // Anyone may clear a validation that has lapsed, so it stops counting toward
+    // min_validated_collections. Revalidating before this keeps it counted.
+    pub fn expire_collection_validation(ctx: Context<ExpireCollectionValidation>) -> Result<()> {
+        let current_time = current_timestamp()?;
+        let collection_account = &mut ctx.accounts.collection_account;
+        require!(collection_account.is_validated, StakingError::CollectionNotValidated);
+        require!(
+            !collection_account.is_validated_at(current_time),
+            StakingError::ValidationNotExpired
+        );
+        
+        let staking_program = &mut ctx.accounts.staking_program;
+        staking_program.validated_collections = staking_program
+            .validated_collections
+            .checked_sub(1)
+            .ok_or(StakingError::ArithmeticOverflow)?;
+        collection_account.is_validated = false;
+        
+        emit!(CollectionValidated {
+            collection_mint: collection_account.collection_mint,
+            validated: false,
+            validation_expires_at: collection_account.validation_expires_at,
+        });
+        
+        Ok(())
+    }
+
+    // 0 (the default) makes validations permanent. Applies from the next
+    // validate/revalidate; the current expiry is left as is.
+    pub fn set_validation_validity_period(ctx: Context<UpdateCollectionConfig>, validity_period: i64) -> Result<()> {
+        require!(validity_period >= 0, StakingError::InvalidDuration);
+        
+        let collection_account = &mut ctx.accounts.collection_account;
+        collection_account.validation_validity_period = validity_period;
+        
+        emit!(AdminAction {
+            admin: ctx.accounts.admin.key(),
+            action: "setValidationValidityPeriod".to_string(),
+            data: format!("{},{}", collection_account.collection_mint, validity_period),
+        });
+        
+        Ok(())
+    }
+
+    // Replaces the NFT backing a position with another NFT from the same collection. The
+    // position is re-created at the address derived from the new mint, keeping its term and
+    // reward state, and the old account is closed to the owner. Membership is proven by the
+    // new mint's VerifiedNft or its metadata. pNFT positions swap through swap_staked_pnft.
+    pub fn swap_staked_nft(ctx: Context<SwapStakedNft>) -> Result<()> {
+        check_swap_allowed(
+            ctx.accounts.user.key(),
+            &ctx.accounts.staking_program,
+            &ctx.accounts.collection_account,
+            &ctx.accounts.staking_position,
+            &ctx.accounts.new_nft_mint.key(),
+            ctx.accounts.verified_nft.as_deref(),
+            ctx.accounts.new_nft_metadata.as_deref(),
+            &ctx.accounts.blocked_mint,
+            &ctx.accounts.emergency_request,
+        )?;
+        
+        // New NFT in
+        let cpi_accounts = Transfer {
+            from: ctx.accounts.user_new_token_account.to_account_info(),
+            to: ctx.accounts.program_new_token_account.to_account_info(),
+            authority: ctx.accounts.user.to_account_info(),
+        };
+        let cpi_ctx = CpiContext::new(ctx.accounts.token_program.to_account_info(), cpi_accounts);
+        token::transfer(cpi_ctx, 1)?;
+        
+        // Old NFT out
+        let bump = ctx.accounts.staking_program.bump;
+        let seeds: &[&[u8]] = &[b"staking_program".as_ref(), &[bump]];
+        let signer = &[seeds];
+        let cpi_accounts = Transfer {
+            from: ctx.accounts.program_old_token_account.to_account_info(),
+            to: ctx.accounts.user_old_token_account.to_account_info(),
+            authority: ctx.accounts.staking_program.to_account_info(),
+        };
+        let cpi_ctx = CpiContext::new_with_signer(ctx.accounts.token_program.to_account_info(), cpi_accounts, signer);
+        token::transfer(cpi_ctx, 1)?;
+        
+        let new_nft_mint = ctx.accounts.new_nft_mint.key();
+        let old_nft_mint = ctx.accounts.staking_position.nft_mint;
+        ctx.accounts.new_staking_position.set_inner(ctx.accounts.staking_position.swapped(
+            new_nft_mint,
+            *ctx.bumps.get("new_staking_position").unwrap(),
+        ));
+        
+        emit!(StakedNftSwapped {
+            owner: ctx.accounts.user.key(),
+            collection_mint: ctx.accounts.collection_account.collection_mint,
+            old_nft_mint,
+            new_nft_mint,
+        });
+        
+        Ok(())
+    }
+
+    // swap_staked_nft for programmable NFTs; both the staked and the replacement NFT move
+    // through Token Metadata.
+    pub fn swap_staked_pnft(ctx: Context<SwapStakedPnft>) -> Result<()> {
+        check_swap_allowed(
+            ctx.accounts.user.key(),
+            &ctx.accounts.staking_program,
+            &ctx.accounts.collection_account,
+            &ctx.accounts.staking_position,
+            &ctx.accounts.new_nft_mint.key(),
+            ctx.accounts.verified_nft.as_deref(),
+            Some(&ctx.accounts.new_nft_metadata),
+            &ctx.accounts.blocked_mint,
+            &ctx.accounts.emergency_request,
+        )?;
+        
+        transfer_pnft(
+            PnftTransferAccounts {
+                token: ctx.accounts.user_new_token_account.to_account_info(),
+                token_owner: ctx.accounts.user.to_account_info(),
+                destination: ctx.accounts.program_new_token_account.to_account_info(),
+                destination_owner: ctx.accounts.staking_program.to_account_info(),
+                mint: ctx.accounts.new_nft_mint.to_account_info(),
+                metadata: ctx.accounts.new_nft_metadata.to_account_info(),
+                edition: ctx.accounts.new_nft_edition.to_account_info(),
+                owner_token_record: ctx.accounts.new_owner_token_record.to_account_info(),
+                destination_token_record: ctx.accounts.new_destination_token_record.to_account_info(),
+                authority: ctx.accounts.user.to_account_info(),
+                payer: ctx.accounts.user.to_account_info(),
+                system_program: ctx.accounts.system_program.to_account_info(),
+                sysvar_instructions: ctx.accounts.sysvar_instructions.to_account_info(),
+                spl_token_program: ctx.accounts.token_program.to_account_info(),
+                spl_ata_program: ctx.accounts.associated_token_program.to_account_info(),
+                authorization_rules_program: ctx.accounts.authorization_rules_program.to_account_info(),
+                authorization_rules: ctx.accounts.authorization_rules.to_account_info(),
+                token_metadata_program: ctx.accounts.token_metadata_program.to_account_info(),
+            },
+            &[],
+        )?;
+        
+        let bump = ctx.accounts.staking_program.bump;
+        let seeds: &[&[u8]] = &[b"staking_program".as_ref(), &[bump]];
+        transfer_pnft(
+            PnftTransferAccounts {
+                token: ctx.accounts.program_old_token_account.to_account_info(),
+                token_owner: ctx.accounts.staking_program.to_account_info(),
+                destination: ctx.accounts.user_old_token_account.to_account_info(),
+                destination_owner: ctx.accounts.user.to_account_info(),
+                mint: ctx.accounts.old_nft_mint.to_account_info(),
+                metadata: ctx.accounts.old_nft_metadata.to_account_info(),
+                edition: ctx.accounts.old_nft_edition.to_account_info(),
+                owner_token_record: ctx.accounts.old_owner_token_record.to_account_info(),
+                destination_token_record: ctx.accounts.old_destination_token_record.to_account_info(),
+                authority: ctx.accounts.staking_program.to_account_info(),
+                payer: ctx.accounts.user.to_account_info(),
+                system_program: ctx.accounts.system_program.to_account_info(),
+                sysvar_instructions: ctx.accounts.sysvar_instructions.to_account_info(),
+                spl_token_program: ctx.accounts.token_program.to_account_info(),
+                spl_ata_program: ctx.accounts.associated_token_program.to_account_info(),
+                authorization_rules_program: ctx.accounts.authorization_rules_program.to_account_info(),
+                authorization_rules: ctx.accounts.authorization_rules.to_account_info(),
+                token_metadata_program: ctx.accounts.token_metadata_program.to_account_info(),
+            },
+            &[seeds],
+        )?;
+        
+        let new_nft_mint = ctx.accounts.new_nft_mint.key();
+        let old_nft_mint = ctx.accounts.staking_position.nft_mint;
+        ctx.accounts.new_staking_position.set_inner(ctx.accounts.staking_position.swapped(
+            new_nft_mint,
+            *ctx.bumps.get("new_staking_position").unwrap(),
+        ));
+        
+        emit!(StakedNftSwapped {
+            owner: ctx.accounts.user.key(),
+            collection_mint: ctx.accounts.collection_account.collection_mint,
+            old_nft_mint,
+            new_nft_mint,
+        });
+        
+        Ok(())
+    }
+
+    pub fn set_reward_match(ctx: Context<UpdateCollectionConfig>, reward_match_bps: u16) -> Result<()> {
+        require!(reward_match_bps as u64 <= BASIS_POINTS, StakingError::InvalidBasisPoints);
+        
+        let collection_account = &mut ctx.accounts.collection_account;
+        collection_account.reward_match_bps = reward_match_bps;
+        
+        emit!(AdminAction {
+            admin: ctx.accounts.admin.key(),
+            action: "setRewardMatch".to_string(),
+            data: format!("{},{}", collection_account.collection_mint, reward_match_bps),
+        });
+        
+        Ok(())
+    }
+
+    pub fn fund_match_budget(ctx: Context<UpdateCollectionConfig>, amount: u64) -> Result<()> {
+        require!(amount > 0, StakingError::InvalidAmount);
+        
+        let collection_account = &mut ctx.accounts.collection_account;
+        collection_account.match_budget = collection_account
+            .match_budget
+            .checked_add(amount)
+            .ok_or(StakingError::ArithmeticOverflow)?;
+        
+        emit!(AdminAction {
+            admin: ctx.accounts.admin.key(),
+            action: "fundMatchBudget".to_string(),
+            data: format!("{},{},{}", collection_account.collection_mint, amount, collection_account.match_budget),
+        });
+        
+        Ok(())
+    }
+
+    // Opens a proposal for one of the admin actions gated by `multi_sig_threshold`. The
+    // proposer's own confirmation counts toward the threshold.
+    pub fn propose_action(
+        ctx: Context<ProposeAction>,
+        nonce: u64,
+        action_hash: [u8; 32],
+        action: MultiSigAction,
+    ) -> Result<()> {
+        require!(action.hash(nonce)? == action_hash, StakingError::InvalidProposal);
+        
+        let proposal = &mut ctx.accounts.proposal;
+        proposal.action_hash = action_hash;
+        proposal.nonce = nonce;
+        proposal.action = action;
+        proposal.proposer = ctx.accounts.admin.key();
+        proposal.confirmations = Vec::new();
+        proposal.executed = false;
+        proposal.created_at = current_timestamp()?;
+        proposal.bump = *ctx.bumps.get("proposal").unwrap();
+        proposal.confirm(ctx.accounts.admin.key())?;
+        
+        // A multisig unlock is an emergency request like admin_unlock's, listed until it executes
+        let registry = &mut ctx.accounts.active_emergency_requests;
+        registry.bump = *ctx.bumps.get("active_emergency_requests").unwrap();
+        if matches!(proposal.action, MultiSigAction::AdminUnlock { .. }) {
+            registry.add(proposal.key())?;
+        }
+        
+        emit!(ActionProposed {
+            proposal: proposal.key(),
+            proposer: proposal.proposer,
+            action_hash,
+            threshold: ctx.accounts.staking_program.multi_sig_threshold,
+        });
+        
+        Ok(())
+    }
+
+    pub fn confirm_action(ctx: Context<ConfirmAction>) -> Result<()> {
+        let proposal = &mut ctx.accounts.proposal;
+        require!(!proposal.executed, StakingError::ProposalAlreadyExecuted);
+        proposal.confirm(ctx.accounts.admin.key())?;
+        
+        emit!(ActionConfirmed {
+            proposal: proposal.key(),
+            admin: ctx.accounts.admin.key(),
+            confirmations: proposal.confirmations.len() as u8,
+            threshold: ctx.accounts.staking_program.multi_sig_threshold,
+        });
+        
+        Ok(())
+    }
+
+    // Applies a proposal once it has `multi_sig_threshold` distinct confirmations. The
+    // accounts the action touches are passed through the optional slots of ExecuteAction.
+    // `remaining_accounts` starts with the AdminAccount PDAs of `multi_sig_threshold`
+    // confirmers, each still active; GrantBonusRewards positions follow them. The proposal
+    // is closed to its proposer.
+    pub fn execute_action(ctx: Context<ExecuteAction>) -> Result<()> {
+        let threshold = ctx.accounts.staking_program.multi_sig_threshold.max(1) as usize;
+        require!(ctx.remaining_accounts.len() >= threshold, StakingError::InsufficientMultiSigConfirmations);
+        let (confirmers, action_accounts) = ctx.remaining_accounts.split_at(threshold);
+        let proposal = &mut ctx.accounts.proposal;
+        require!(!proposal.executed, StakingError::ProposalAlreadyExecuted);
+        check_confirmers(proposal, confirmers)?;
+        proposal.executed = true;
+        let proposal_key = proposal.key();
+        let proposal_created_at = proposal.created_at;
+        let action = proposal.action.clone();
+        
+        let collection_info = ctx
+            .accounts
+            .collection_account
+            .as_ref()
+            .map(|account| account.to_account_info())
+            .ok_or(StakingError::MissingActionAccount);
+        
+        match action {
+            MultiSigAction::AddCollection { collection_mint, tickets } => {
+                let collection_info = collection_info?;
+                require!(!ctx.accounts.staking_program.is_paused_at(current_timestamp()?), StakingError::ContractPaused);
+                create_collection_account(
+                    ctx.accounts.admin.to_account_info(),
+                    &collection_info,
+                    ctx.accounts.system_program.to_account_info(),
+                    collection_mint,
+                    tickets,
+                )?;
+                let staking_program = &mut ctx.accounts.staking_program;
+                staking_program.total_collections = staking_program
+                    .total_collections
+                    .checked_add(1)
+                    .ok_or(StakingError::ArithmeticOverflow)?;
+                
+                emit!(CollectionAdded {
+                    collection_mint,
+                    six_month_tickets: tickets[0],
+                    twelve_month_tickets: tickets[1],
+                    three_year_tickets: tickets[2],
+                    onboarding_fee: 0,
+                });
+            }
+            MultiSigAction::UpdateCollectionRewards { collection_mint, tickets } => {
+                let collection_info = collection_info?;
+                require!(!ctx.accounts.staking_program.is_paused_at(current_timestamp()?), StakingError::ContractPaused);
+                let mut collection = Account::<CollectionAccount>::try_from(&collection_info)?;
+                require!(collection.collection_mint == collection_mint, StakingError::CollectionNotFound);
+                collection.six_month_tickets = tickets[0];
+                collection.twelve_month_tickets = tickets[1];
+                collection.three_year_tickets = tickets[2];
+                collection.exit(&crate::ID)?;
+                
+                emit!(CollectionUpdated {
+                    collection_mint,
+                    six_month_tickets: tickets[0],
+                    twelve_month_tickets: tickets[1],
+                    three_year_tickets: tickets[2],
+                });
+            }
+            MultiSigAction::AdminUnlock { staking_position } => {
+                let collection_info = collection_info?;
+                let accounts = &mut *ctx.accounts;
+                let (position, program_token_account, owner_token_account, token_program) = match (
+                    accounts.staking_position.as_mut(),
+                    accounts.program_token_account.as_ref(),
+                    accounts.owner_token_account.as_ref(),
+                    accounts.token_program.as_ref(),
+                ) {
+                    (Some(position), Some(from), Some(to), Some(token_program)) => (position, from, to, token_program),
+                    _ => return err!(StakingError::MissingActionAccount),
+                };
+                require!(position.key() == staking_position, StakingError::InvalidProposal);
+                require!(position.is_active, StakingError::PositionNotActive);
+                // Same delay as admin_unlock, counted from the proposal
+                require!(
+                    current_timestamp()? >= proposal_created_at + EMERGENCY_DELAY,
+                    StakingError::EmergencyDelayNotMet
+                );
+                require!(
+                    program_token_account.owner == accounts.staking_program.key()
+                        && program_token_account.mint == position.nft_mint,
+                    StakingError::InvalidPosition
+                );
+                require!(
+                    owner_token_account.owner == position.owner && owner_token_account.mint == position.nft_mint,
+                    StakingError::InvalidRecipient
+                );
+                let mut collection = Account::<CollectionAccount>::try_from(&collection_info)?;
+                require!(
+                    collection.collection_mint == position.collection_mint,
+                    StakingError::CollectionNotFound
+                );
+                
+                position.is_active = false;
+                
+                let seeds = &[
+                    b"staking_program".as_ref(),
+                    &[accounts.staking_program.bump],
+                ];
+                let cpi_accounts = Transfer {
+                    from: program_token_account.to_account_info(),
+                    to: owner_token_account.to_account_info(),
+                    authority: accounts.staking_program.to_account_info(),
+                };
+                token::transfer(
+                    CpiContext::new_with_signer(token_program.to_account_info(), cpi_accounts, &[&seeds[..]]),
+                    1,
+                )?;
+                
+                if !position.swept {
+                    accounts.staking_program.record_unstake()?;
+                    collection.record_unstake()?;
+                    let user_stake_count = accounts.user_stake_count.as_ref().ok_or(StakingError::MissingActionAccount)?;
+                    release_stake_count(user_stake_count, position)?;
+                    let user_stats = accounts.user_stats.as_ref().ok_or(StakingError::MissingActionAccount)?;
+                    update_user_stats(user_stats, &position.owner, |user_stats| user_stats.record_unstake())?;
+                }
+                collection.exit(&crate::ID)?;
+                accounts.active_emergency_requests.remove(&proposal_key);
+                
+                emit!(EmergencyUnlock {
+                    admin: accounts.admin.key(),
+                    user: position.owner,
+                    nft_mint: position.nft_mint,
+                    reason: "multiSigUnlock".to_string(),
+                });
+                
+                // Closed like claim_nft, unless an open reward stake or unpaid tickets still
+                // point at it; those can still be settled from the inactive position
+                let unpaid = accounts.staking_program.pays_out_on_chain() && position.mintable_tickets() > 0;
+                if position.rewards_staked == 0 && !unpaid {
+                    let position_owner = accounts.position_owner.as_ref().ok_or(StakingError::MissingActionAccount)?;
+                    require!(position_owner.key() == position.owner, StakingError::InvalidRecipient);
+                    position.close(position_owner.to_account_info())?;
+                }
+            }
+            MultiSigAction::GrantBonusRewards { amount, positions_hash: expected_hash, reason } => {
+                require!(positions_hash(action_accounts) == expected_hash, StakingError::InvalidProposal);
+                grant_bonus(&mut ctx.accounts.staking_program, action_accounts, amount, &reason)?;
+            }
+        }
+        
+        emit!(ActionExecuted {
+            proposal: proposal_key,
+            executor: ctx.accounts.admin.key(),
+            action_hash: ctx.accounts.proposal.action_hash,
+        });
+        
+        Ok(())
+    }
+
+    // Status of the positions passed in `remaining_accounts` (all belonging to
+    // `collection_account`) in one BatchPositionStatus event, for portfolio views.
+    // Capped at max_batch_size positions per call to keep the log within limits.
+    pub fn batch_position_status(ctx: Context<GetBatchPositionStatus>) -> Result<()> {
+        ctx.accounts.staking_program.check_batch_size(ctx.remaining_accounts.len())?;
+        
+        let collection_account = &ctx.accounts.collection_account;
+        let current_time = current_timestamp()?;
+        
+        let mut seen: Vec<Pubkey> = Vec::with_capacity(ctx.remaining_accounts.len());
+        let mut statuses: Vec<PositionStatus> = Vec::with_capacity(ctx.remaining_accounts.len());
+        for account_info in ctx.remaining_accounts.iter() {
+            require!(!seen.contains(account_info.key), StakingError::InvalidPosition);
+            seen.push(*account_info.key);
+            
+            let position = read_position(account_info)?;
+            require!(position.collection_mint == collection_account.collection_mint, StakingError::CollectionNotFound);
+            
+            let accrued_rewards = if position.is_active {
+                unsettled_rewards(&position, collection_account, current_time)?.1
+            } else {
+                0
+            };
+            statuses.push(PositionStatus {
+                nft_mint: position.nft_mint,
+                is_unlockable: position.is_active && current_time >= position.unlock_at,
+                seconds_remaining: position.unlock_at.saturating_sub(current_time).max(0) as u64,
+                accrued_rewards,
+            });
+        }
+        
+        emit!(BatchPositionStatus {
+            collection_mint: collection_account.collection_mint,
+            statuses,
+        });
+        
+        Ok(())
+    }
+
+    // Collections already on computed multipliers keep their current ladder until
+    // sync_computed_multipliers is run for them.
+    pub fn set_multiplier_curve(
+        ctx: Context<UpdateProgramConfig>,
+        multiplier_base: u64,
+        multiplier_growth: u64,
+    ) -> Result<()> {
+        require!(multiplier_base >= BASIS_POINTS, StakingError::InvalidBasisPoints);
+        // Longer locks never earn a lower multiplier
+        require!(multiplier_growth >= BASIS_POINTS, StakingError::InvalidBasisPoints);
+        
+        let staking_program = &mut ctx.accounts.staking_program;
+        staking_program.multiplier_base = multiplier_base;
+        staking_program.multiplier_growth = multiplier_growth;
+        // Reject curves whose top tier overflows
+        staking_program.computed_multiplier(2)?;
+        
+        emit!(AdminAction {
+            admin: ctx.accounts.authority.key(),
+            action: "setMultiplierCurve".to_string(),
+            data: format!("{},{}", multiplier_base, multiplier_growth),
+        });
+        
+        Ok(())
+    }
+
+    // Opting in replaces the collection's tier multipliers with the computed ladder; opting
+    // out keeps the last derived values as explicit ones. Only new stakes are affected,
+    // existing positions keep their locked multiplier.
+    pub fn set_computed_multipliers(ctx: Context<UpdateCollectionConfig>, enabled: bool) -> Result<()> {
+        let collection_account = &mut ctx.accounts.collection_account;
+        collection_account.computed_multipliers = enabled;
+        if enabled {
+            collection_account.apply_multiplier_curve(&ctx.accounts.staking_program)?;
+            emit_multipliers_derived(collection_account);
+        }
+        
+        emit!(AdminAction {
+            admin: ctx.accounts.admin.key(),
+            action: "setComputedMultipliers".to_string(),
+            data: format!("{},{}", collection_account.collection_mint, enabled),
+        });
+        
+        Ok(())
+    }
+
+    // Re-derives an opted-in collection's multipliers after the ladder changed. Anyone may
+    // call it: it only applies the curve the authority already set.
+    pub fn sync_computed_multipliers(ctx: Context<SyncComputedMultipliers>) -> Result<()> {
+        let collection_account = &mut ctx.accounts.collection_account;
+        require!(collection_account.computed_multipliers, StakingError::MultipliersNotComputed);
+        collection_account.apply_multiplier_curve(&ctx.accounts.staking_program)?;
+        emit_multipliers_derived(collection_account);
+        
+        Ok(())
+    }
+
+    // Clears a pause that has outlived AUTO_UNPAUSE_DELAY, so a paused program can't be
+    // held indefinitely. Anyone may call it.
+    pub fn try_auto_unpause(ctx: Context<TryAutoUnpause>) -> Result<()> {
+        let staking_program = &mut ctx.accounts.staking_program;
+        require!(staking_program.is_paused, StakingError::ContractNotPaused);
+        require!(
+            staking_program.auto_unpause_due(current_timestamp()?),
+            StakingError::AutoUnpauseNotDue
+        );
+        
+        staking_program.is_paused = false;
+        staking_program.paused_at = 0;
+        
+        emit!(EmergencyAction {
+            admin: ctx.accounts.caller.key(),
+            action: "autoUnpause".to_string(),
+            reason: "Pause exceeded the auto-unpause delay".to_string(),
+        });
+        
+        Ok(())
+    }
+
+    // Schedules the launch: stakes are rejected until `staking_starts_at`. 0 (or any
+    // past time) opens staking immediately.
+    pub fn set_staking_starts_at(ctx: Context<UpdateProgramConfig>, staking_starts_at: i64) -> Result<()> {
+        require!(staking_starts_at >= 0, StakingError::InvalidTimestamp);
+        
+        let staking_program = &mut ctx.accounts.staking_program;
+        staking_program.staking_starts_at = staking_starts_at;
+        
+        emit!(AdminAction {
+            admin: ctx.accounts.authority.key(),
+            action: "setStakingStartsAt".to_string(),
+            data: staking_starts_at.to_string(),
+        });
+        
+        Ok(())
+    }
+
+    // Records (or replaces) the staker's reward address on another chain, one PDA per
+    // chain. EVM addresses are passed as their 20 raw bytes.
+    pub fn set_reward_address(ctx: Context<SetRewardAddress>, chain_id: u64, address_bytes: Vec<u8>) -> Result<()> {
+        require!(SUPPORTED_REWARD_CHAINS.contains(&chain_id), StakingError::UnsupportedChain);
+        require!(
+            !address_bytes.is_empty() && address_bytes.len() <= MAX_EXTERNAL_ADDRESS_LEN,
+            StakingError::InvalidExternalAddress
+        );
+        
+        let reward_address = &mut ctx.accounts.reward_address;
+        reward_address.staker = ctx.accounts.staker.key();
+        reward_address.chain_id = chain_id;
+        reward_address.address = address_bytes;
+        reward_address.updated_at = current_timestamp()?;
+        reward_address.bump = *ctx.bumps.get("reward_address").unwrap();
+        
+        emit!(RewardAddressSet {
+            staker: reward_address.staker,
+            chain_id,
+            address: reward_address.address.clone(),
+        });
+        
+        Ok(())
+    }
+
+    // Mints the position's settled tickets (see claim_rewards) as tokens of the
+    // configured ticket mint to the owner's associated token account. Only tickets not
+    // yet minted or locked in a reward stake are minted, so a repeat call mints zero.
+    pub fn claim_tickets(ctx: Context<ClaimTickets>) -> Result<()> {
+        require!(
+            !ctx.accounts.staking_program.is_instruction_paused(PAUSE_CLAIM_REWARDS, current_timestamp()?),
+            StakingError::ContractPaused
+        );
+        
+        // Only what fits under the daily emission cap is minted; the rest stays mintable
+        let staking_position = &mut ctx.accounts.staking_position;
+        let amount = ctx
+            .accounts
+            .staking_program
+            .take_emission_allowance(staking_position.mintable_tickets(), current_timestamp()?)?;
+        staking_position.tickets_minted += amount;
+        ctx.accounts.staking_program.record_paid(amount);
+        
+        if amount > 0 {
+            let bump = *ctx.bumps.get("ticket_mint_authority").unwrap();
+            let seeds: &[&[u8]] = &[b"ticket_mint_authority".as_ref(), &[bump]];
+            let signer = &[seeds];
+            let cpi_accounts = MintTo {
+                mint: ctx.accounts.ticket_mint.to_account_info(),
+                to: ctx.accounts.user_ticket_account.to_account_info(),
+                authority: ctx.accounts.ticket_mint_authority.to_account_info(),
+            };
+            let cpi_ctx = CpiContext::new_with_signer(ctx.accounts.token_program.to_account_info(), cpi_accounts, signer);
+            token::mint_to(cpi_ctx, amount)?;
+        }
+        
+        emit!(TicketsClaimed {
+            user: ctx.accounts.user.key(),
+            nft_mint: staking_position.nft_mint,
+            amount,
+        });
+        
+        Ok(())
+    }
+
+    // The mint's authority must be the `ticket_mint_authority` PDA. Once set, indexers
+    // should credit tickets from TicketsClaimed rather than RewardsClaimed.
+    pub fn set_ticket_mint(ctx: Context<UpdateProgramConfig>, ticket_mint: Pubkey) -> Result<()> {
+        let staking_program = &mut ctx.accounts.staking_program;
+        staking_program.ticket_mint = ticket_mint;
+        
+        emit!(AdminAction {
+            admin: ctx.accounts.authority.key(),
+            action: "setTicketMint".to_string(),
+            data: ticket_mint.to_string(),
+        });
+        
+        Ok(())
+    }
+
+    // Lowering the threshold to or below the current participation activates rewards
+    // right away; raising it has no effect once the collection is activated.
+    pub fn set_reward_activation_threshold(ctx: Context<UpdateCollectionConfig>, threshold: u64) -> Result<()> {
+        let collection_account = &mut ctx.accounts.collection_account;
+        collection_account.reward_activation_threshold = threshold;
+        collection_account.check_rewards_activation();
+        
+        emit!(AdminAction {
+            admin: ctx.accounts.admin.key(),
+            action: "setRewardActivationThreshold".to_string(),
+            data: format!("{},{}", collection_account.collection_mint, threshold),
+        });
+        
+        Ok(())
+    }
+
+    // Raffle entry weight for the position (see entry_weight), returned as return data so
+    // the raffle program can read it after a CPI. Read-only.
+    pub fn compute_entry_weight(ctx: Context<ComputeEntryWeight>) -> Result<u64> {
+        entry_weight(&ctx.accounts.staking_position, &ctx.accounts.collection_account, current_timestamp()?)
+    }
+
+    // Pending tickets for the position right now as a PendingRewardsView, returned as
+    // return data (readable through simulateTransaction); see pending_rewards_view.
+    pub fn get_pending_rewards(ctx: Context<GetPendingRewards>) -> Result<PendingRewardsView> {
+        pending_rewards_view(&ctx.accounts.staking_position, &ctx.accounts.collection_account, current_timestamp()?)
+    }
+
+    pub fn set_claim_cooldown(ctx: Context<UpdateCollectionConfig>, claim_cooldown: i64) -> Result<()> {
+        require!(claim_cooldown >= 0, StakingError::InvalidDuration);
+        
+        let collection_account = &mut ctx.accounts.collection_account;
+        collection_account.claim_cooldown = claim_cooldown;
+        
+        emit!(AdminAction {
+            admin: ctx.accounts.admin.key(),
+            action: "setClaimCooldown".to_string(),
+            data: format!("{},{}", collection_account.collection_mint, claim_cooldown),
+        });
+        
+        Ok(())
+    }
+
+    // Caps how many NFTs can be staked in the collection at once (0 = unlimited). Lowering
+    // the cap below `total_staked` only blocks new stakes; existing positions are kept.
+    pub fn update_collection_cap(ctx: Context<UpdateCollectionConfig>, max_stake: u64) -> Result<()> {
+        let collection_account = &mut ctx.accounts.collection_account;
+        collection_account.max_stake = max_stake;
+        
+        emit!(AdminAction {
+            admin: ctx.accounts.admin.key(),
+            action: "updateCollectionCap".to_string(),
+            data: format!("{},{}", collection_account.collection_mint, max_stake),
+        });
+        
+        Ok(())
+    }
+
+    // Caps how many open positions one wallet may hold in the collection (0 = unlimited).
+    // Wallets already above a lowered cap keep their positions but can't add more.
+    pub fn set_max_per_user(ctx: Context<UpdateCollectionConfig>, max_per_user: u64) -> Result<()> {
+        let collection_account = &mut ctx.accounts.collection_account;
+        collection_account.max_per_user = max_per_user;
+        
+        emit!(AdminAction {
+            admin: ctx.accounts.admin.key(),
+            action: "setMaxPerUser".to_string(),
+            data: format!("{},{}", collection_account.collection_mint, max_per_user),
+        });
+        
+        Ok(())
+    }
+
+    // Chooses whether reward rounding favors the user (round up) or the protocol (round
+    // down). Applies to everything accrued from now on, including unsettled rewards.
+    pub fn set_favor_user(ctx: Context<UpdateCollectionConfig>, favor_user: bool) -> Result<()> {
+        let collection_account = &mut ctx.accounts.collection_account;
+        collection_account.favor_user = favor_user;
+        
+        emit!(AdminAction {
+            admin: ctx.accounts.admin.key(),
+            action: "setFavorUser".to_string(),
+            data: format!("{},{}", collection_account.collection_mint, favor_user),
+        });
+        
+        Ok(())
+    }
+
+    // Only collections with a reward pool can scale by it; without one the multiplier is
+    // left as is
+    pub fn set_dynamic_multiplier(ctx: Context<UpdateCollectionConfig>, dynamic_multiplier: bool) -> Result<()> {
+        let collection_account = &mut ctx.accounts.collection_account;
+        require!(
+            !dynamic_multiplier || collection_account.reward_pool > 0,
+            StakingError::RewardPoolNotConfigured
+        );
+        collection_account.dynamic_multiplier = dynamic_multiplier;
+        
+        emit!(AdminAction {
+            admin: ctx.accounts.admin.key(),
+            action: "setDynamicMultiplier".to_string(),
+            data: format!("{},{}", collection_account.collection_mint, dynamic_multiplier),
+        });
+        
+        Ok(())
+    }
+
+    // For collections found to be fraudulent: stops all reward accrual from now on. Rewards
+    // accrued before the freeze can still be settled, and NFTs stay claimable after unlock.
+    // There is no unfreeze; lifting it would backfill the frozen period.
+    pub fn freeze_collection_rewards(ctx: Context<UpdateCollectionConfig>, reason: String) -> Result<()> {
+        validate_reason(&reason, MAX_REASON_LEN)?;
+        
+        let collection_account = &mut ctx.accounts.collection_account;
+        require!(!collection_account.rewards_frozen, StakingError::CollectionRewardsFrozen);
+        collection_account.rewards_frozen = true;
+        collection_account.rewards_frozen_at = current_timestamp()?;
+        
+        emit!(SecurityViolation {
+            violation_type: "collectionFrozen".to_string(),
+            violator: collection_account.collection_mint,
+            details: reason.clone(),
+        });
+        
+        emit!(AdminAction {
+            admin: ctx.accounts.admin.key(),
+            action: "freezeCollectionRewards".to_string(),
+            data: format!("{},{}", collection_account.collection_mint, reason),
+        });
+        
+        Ok(())
+    }
+
+    // Revokes an admin. The PDA is kept (inactive) as an audit record and so the key can
+    // be reinstated with reactivate_admin.
+    pub fn remove_admin(ctx: Context<SetAdminStatus>) -> Result<()> {
+        let admin_account = &mut ctx.accounts.admin_account;
+        require!(
+            admin_account.admin != ctx.accounts.staking_program.authority,
+            StakingError::CannotRemoveAuthority
+        );
+        admin_account.is_active = false;
+        
+        emit!(AdminAction {
+            admin: ctx.accounts.authority.key(),
+            action: "removeAdmin".to_string(),
+            data: admin_account.admin.to_string(),
+        });
+        
+        Ok(())
+    }
+
+    pub fn reactivate_admin(ctx: Context<SetAdminStatus>) -> Result<()> {
+        let admin_account = &mut ctx.accounts.admin_account;
+        admin_account.is_active = true;
+        
+        emit!(AdminAction {
+            admin: ctx.accounts.authority.key(),
+            action: "reactivateAdmin".to_string(),
+            data: admin_account.admin.to_string(),
+        });
+        
+        Ok(())
+    }
+
+    // Off by default (0). Positions claiming while the collection is unvalidated settle
+    // with the boost; once validated, unclaimed accrual settles at the base multiplier.
+    pub fn set_pending_validation_boost(ctx: Context<UpdateCollectionConfig>, boost_bps: u16) -> Result<()> {
+        require!(boost_bps as u64 <= BASIS_POINTS, StakingError::InvalidBasisPoints);
+        
+        let collection_account = &mut ctx.accounts.collection_account;
+        collection_account.pending_validation_boost_bps = boost_bps;
+        
+        emit!(AdminAction {
+            admin: ctx.accounts.admin.key(),
+            action: "setPendingValidationBoost".to_string(),
+            data: format!("{},{}", collection_account.collection_mint, boost_bps),
+        });
+        
+        Ok(())
+    }
+
+    pub fn set_loyalty_bonus(
+        ctx: Context<UpdateCollectionConfig>,
+        loyalty_bonus_bps_per_month: u16,
+        max_loyalty_bps: u16,
+    ) -> Result<()> {
+        require!(max_loyalty_bps as u64 <= BASIS_POINTS, StakingError::InvalidBasisPoints);
+        require!(loyalty_bonus_bps_per_month <= max_loyalty_bps, StakingError::InvalidBasisPoints);
+        
+        let collection_account = &mut ctx.accounts.collection_account;
+        collection_account.loyalty_bonus_bps_per_month = loyalty_bonus_bps_per_month;
+        collection_account.max_loyalty_bps = max_loyalty_bps;
+        
+        emit!(AdminAction {
+            admin: ctx.accounts.admin.key(),
+            action: "setLoyaltyBonus".to_string(),
+            data: format!("{},{},{}", collection_account.collection_mint, loyalty_bonus_bps_per_month, max_loyalty_bps),
+        });
+        
+        Ok(())
+    }
+
+    // Returns the NFT before `unlock_at`. All unsettled accrual is forfeited and
+    // `early_unstake_penalty_bps` of the settled, not yet minted tickets is clawed back
+    // (indexers debit it from EarlyUnstake). The position is closed. After unlock,
+    // claim_nft is the way out and keeps full rewards.
+    pub fn unstake_early(ctx: Context<UnstakeEarly>) -> Result<()> {
+        let current_time = current_timestamp()?;
+        require!(
+            !ctx.accounts.staking_program.is_instruction_paused(PAUSE_CLAIM_NFT, current_time),
+            StakingError::ContractPaused
+        );
+        
+        let staking_position = &mut ctx.accounts.staking_position;
+        require!(staking_position.is_active, StakingError::PositionNotActive);
+        require!(current_time < staking_position.unlock_at, StakingError::AlreadyUnlocked);
+        // The position is closed, and an open reward stake still points at it
+        require!(staking_position.rewards_staked == 0, StakingError::RewardsStillStaked);
+        
+        let (_, forfeited_rewards) = unsettled_rewards(staking_position, &ctx.accounts.collection_account, current_time)?;
+        let penalty_bps = ctx.accounts.staking_program.early_unstake_penalty_bps as u128;
+        let penalty = (staking_position.mintable_tickets() as u128 * penalty_bps / BASIS_POINTS as u128) as u64;
+        staking_position.total_rewards_earned -= penalty;
+        staking_position.is_active = false;
+        
+        let seeds = &[
+            b"staking_program".as_ref(),
+            &[ctx.accounts.staking_program.bump],
+        ];
+        let signer = &[&seeds[..]];
+        
+        // The position is closed below, so settled tickets left after the penalty are paid
+        // out now when they are paid on-chain. The whole remainder must go out at once: a
+        // partial payout under the emission cap fails rather than losing the rest.
+        let paid_out = staking_position.mintable_tickets();
+        let staking_program = &mut ctx.accounts.staking_program;
+        if paid_out > 0 && staking_program.ticket_mint != Pubkey::default() {
+            let ticket_mint = ctx.accounts.ticket_mint.as_ref().ok_or(StakingError::TicketsNotPaidOut)?;
+            let user_ticket_account = ctx.accounts.user_ticket_account.as_ref().ok_or(StakingError::TicketsNotPaidOut)?;
+            let ticket_mint_authority = ctx.accounts.ticket_mint_authority.as_ref().ok_or(StakingError::TicketsNotPaidOut)?;
+            let amount = staking_program.take_emission_allowance(paid_out, current_time)?;
+            require!(amount == paid_out, StakingError::DailyEmissionCapReached);
+            
+            let bump = *ctx.bumps.get("ticket_mint_authority").unwrap();
+            let mint_seeds: &[&[u8]] = &[b"ticket_mint_authority".as_ref(), &[bump]];
+            let mint_signer = &[mint_seeds];
+            let cpi_accounts = MintTo {
+                mint: ticket_mint.to_account_info(),
+                to: user_ticket_account.to_account_info(),
+                authority: ticket_mint_authority.to_account_info(),
+            };
+            let cpi_ctx = CpiContext::new_with_signer(ctx.accounts.token_program.to_account_info(), cpi_accounts, mint_signer);
+            token::mint_to(cpi_ctx, paid_out)?;
+            staking_position.tickets_minted += paid_out;
+            staking_program.record_paid(paid_out);
+        } else if paid_out > 0 && staking_program.reward_token_mint != Pubkey::default() {
+            let reward_vault = ctx.accounts.reward_vault.as_ref().ok_or(StakingError::TicketsNotPaidOut)?;
+            let user_reward_account = ctx.accounts.user_reward_account.as_ref().ok_or(StakingError::TicketsNotPaidOut)?;
+            require!(paid_out <= staking_program.vault_balance, StakingError::InsufficientVaultBalance);
+            
+            let cpi_accounts = Transfer {
+                from: reward_vault.to_account_info(),
+                to: user_reward_account.to_account_info(),
+                authority: staking_program.to_account_info(),
+            };
+            let cpi_ctx = CpiContext::new_with_signer(ctx.accounts.token_program.to_account_info(), cpi_accounts, signer);
+            token::transfer(cpi_ctx, paid_out)?;
+            staking_position.tickets_minted += paid_out;
+            staking_program.vault_balance -= paid_out;
+            staking_program.record_paid(paid_out);
+        }
+        let cpi_accounts = Transfer {
+            from: ctx.accounts.program_token_account.to_account_info(),
+            to: ctx.accounts.user_token_account.to_account_info(),
+            authority: ctx.accounts.staking_program.to_account_info(),
+        };
+        let cpi_ctx = CpiContext::new_with_signer(ctx.accounts.token_program.to_account_info(), cpi_accounts, signer);
+        token::transfer(cpi_ctx, 1)?;
+        
+        ctx.accounts.staking_program.record_unstake()?;
+        ctx.accounts.collection_account.record_unstake()?;
+        ctx.accounts.user_stake_count.record_unstake()?;
+        ctx.accounts.user_stats.fill(ctx.accounts.user.key(), *ctx.bumps.get("user_stats").unwrap());
+        ctx.accounts.user_stats.record_unstake()?;
+        
+        emit!(EarlyUnstake {
+            user: ctx.accounts.user.key(),
+            nft_mint: staking_position.nft_mint,
+            forfeited_rewards,
+            penalty,
+            paid_out,
+        });
+        
+        Ok(())
+    }
+
+    pub fn set_early_unstake_penalty(ctx: Context<UpdateProgramConfig>, penalty_bps: u16) -> Result<()> {
+        require!(penalty_bps as u64 <= BASIS_POINTS, StakingError::InvalidBasisPoints);
+        
+        let staking_program = &mut ctx.accounts.staking_program;
+        staking_program.early_unstake_penalty_bps = penalty_bps;
+        
+        emit!(AdminAction {
+            admin: ctx.accounts.authority.key(),
+            action: "setEarlyUnstakePenalty".to_string(),
+            data: penalty_bps.to_string(),
+        });
+        
+        Ok(())
+    }
+
+    // Compensation after an incident: credits `amount` settled tickets to each position
+    // passed in `remaining_accounts`. With a multi-sig threshold above one this must go
+    // through a GrantBonusRewards proposal instead.
+    pub fn grant_bonus_rewards(ctx: Context<GrantBonusRewards>, amount: u64, reason: String) -> Result<()> {
+        require_single_signer(&ctx.accounts.staking_program)?;
+        grant_bonus(&mut ctx.accounts.staking_program, ctx.remaining_accounts, amount, &reason)?;
+        
+        emit!(AdminAction {
+            admin: ctx.accounts.authority.key(),
+            action: "grantBonusRewards".to_string(),
+            data: format!("{},{},{}", ctx.remaining_accounts.len(), amount, reason),
+        });
+        
+        Ok(())
+    }
+
+    // Moves a position to a longer lock without unstaking. The new unlock time counts
+    // from the original `staked_at`; the reward tier and locked multiplier are raised to
+    // the new duration's, never lowered. Accrual pending under the old term is settled
+    // first, with claim_rewards' early-claim penalty and pool cap. Over the longer term
+    // the accrual curve restarts lower, so nothing more settles until it passes the
+    // watermark, and the position is paid the new tier's full term exactly once.
+    pub fn extend_duration(ctx: Context<ExtendDuration>, new_duration: u8) -> Result<()> {
+        let current_time = current_timestamp()?;
+        require!(
+            !ctx.accounts.staking_program.is_instruction_paused(PAUSE_STAKE, current_time),
+            StakingError::ContractPaused
+        );
+        
+        let staking_position = &mut ctx.accounts.staking_position;
+        require!(staking_position.is_active, StakingError::PositionNotActive);
+        require!(new_duration > staking_position.duration, StakingError::InvalidDuration);
+        
+        let unlock_at = staking_position.staked_at + staking_duration_seconds(new_duration)?;
+        ctx.accounts.staking_program.check_lock_duration(unlock_at, current_time)?;
+        let collection_account = &mut ctx.accounts.collection_account;
+        let tier_multiplier = collection_account.multiplier_for(new_duration)?;
+        
+        let (accrued, unsettled) = unsettled_rewards(staking_position, collection_account, current_time)?;
+        let penalty_bps = early_claim_penalty_bps(staking_position, collection_account, current_time);
+        let mut settled_rewards = unsettled - (unsettled as u128 * penalty_bps as u128 / BASIS_POINTS as u128) as u64;
+        if collection_account.reward_pool > 0 {
+            settled_rewards = settled_rewards.min(collection_account.reward_pool_remaining);
+            collection_account.reward_pool_remaining -= settled_rewards;
+        }
+        staking_position.total_rewards_earned = staking_position
+            .total_rewards_earned
+            .checked_add(settled_rewards)
+            .ok_or(StakingError::ArithmeticOverflow)?;
+        staking_position.mark_settled(accrued);
+        ctx.accounts.staking_program.record_settled(settled_rewards)?;
+        ctx.accounts.user_stats.fill(ctx.accounts.user.key(), *ctx.bumps.get("user_stats").unwrap());
+        ctx.accounts.user_stats.record_rewards(settled_rewards)?;
+        
+        let old_duration = staking_position.duration;
+        staking_position.extend(new_duration, tier_multiplier)?;
+        
+        emit!(DurationExtended {
+            nft_mint: staking_position.nft_mint,
+            old_duration,
+            new_duration,
+            unlock_at,
+            settled_rewards,
+        });
+        
+        Ok(())
+    }
+
+    // Starts a new lock on a matured position without the NFT leaving custody. Unsettled
+    // accrual from the finished period is settled into total_rewards_earned (as
+    // claim_rewards would, with its pause flag, activation check and claim fee) and
+    // recorded in `carried_rewards`; the new period then accrues from zero under the new
+    // duration's multiplier. The settlement runs against the old `unlock_at`, so any
+    // loyalty bonus earned past it is carried before the new lock resets it. The
+    // collection must accept new stakes: active, validated when required, not frozen.
+    pub fn relock(ctx: Context<Relock>, duration: u8) -> Result<()> {
+        let current_time = current_timestamp()?;
+        require!(
+            !ctx.accounts.staking_program.is_instruction_paused(PAUSE_STAKE, current_time)
+                && !ctx.accounts.staking_program.is_instruction_paused(PAUSE_CLAIM_REWARDS, current_time),
+            StakingError::ContractPaused
+        );
+        
+        let collection_account = &mut ctx.accounts.collection_account;
+        let staking_position = &mut ctx.accounts.staking_position;
+        require!(staking_position.is_active, StakingError::PositionNotActive);
+        require!(current_time >= staking_position.unlock_at, StakingError::StakingPeriodNotCompleted);
+        require!(collection_account.is_active, StakingError::CollectionNotActive);
+        require!(
+            !ctx.accounts.staking_program.require_validation || collection_account.is_validated_at(current_time),
+            StakingError::CollectionNotValidated
+        );
+        require!(!collection_account.rewards_frozen, StakingError::CollectionRewardsFrozen);
+        require!(
+            collection_account.reward_activation_threshold == 0 || collection_account.rewards_activated,
+            StakingError::CollectionNotActivated
+        );
+        
+        let unlock_at = current_time + staking_duration_seconds(duration)?;
+        ctx.accounts.staking_program.check_lock_duration(unlock_at, current_time)?;
+        
+        let fee_lamports = ctx.accounts.staking_program.claim_fee_lamports;
+        require!(ctx.accounts.user.lamports() >= fee_lamports, StakingError::InsufficientFeeBalance);
+        collect_fee(
+            ctx.accounts.user.to_account_info(),
+            ctx.accounts.treasury.to_account_info(),
+            ctx.accounts.system_program.to_account_info(),
+            fee_lamports,
+        )?;
+        
+        let (_, mut carried) = unsettled_rewards(staking_position, collection_account, current_time)?;
+        if collection_account.reward_pool > 0 {
+            carried = carried.min(collection_account.reward_pool_remaining);
+            collection_account.reward_pool_remaining -= carried;
+        }
+        staking_position.total_rewards_earned = staking_position
+            .total_rewards_earned
+            .checked_add(carried)
+            .ok_or(StakingError::ArithmeticOverflow)?;
+        staking_position.carried_rewards = staking_position
+            .carried_rewards
+            .checked_add(carried)
+            .ok_or(StakingError::ArithmeticOverflow)?;
+        ctx.accounts.staking_program.record_settled(carried)?;
+        let user_stats_bump = *ctx.bumps.get("user_stats").unwrap();
+        ctx.accounts.user_stats.fill(ctx.accounts.user.key(), user_stats_bump);
+        ctx.accounts.user_stats.record_rewards(carried)?;
+        
+        // A swept position counts as staked again, within the collection's cap
+        if staking_position.swept {
+            require!(!collection_account.is_stake_cap_reached(), StakingError::CollectionStakeCapReached);
+            staking_position.swept = false;
+            ctx.accounts.staking_program.record_stake()?;
+            collection_account.record_stake()?;
+            ctx.accounts.user_stake_count.record_stake(
+                collection_account,
+                ctx.accounts.user.key(),
+                *ctx.bumps.get("user_stake_count").unwrap(),
+            )?;
+            ctx.accounts.user_stats.record_moved_in(ctx.accounts.user.key(), user_stats_bump, 1)?;
+        }
+        
+        staking_position.staked_at = current_time;
+        staking_position.unlock_at = unlock_at;
+        staking_position.duration = duration;
+        staking_position.reward_tier = duration;
+        staking_position.locked_multiplier = collection_account.multiplier_for(duration)?;
+        staking_position.rewards_claimed = 0;
+        staking_position.accrual_paused_total = 0;
+        if staking_position.accrual_paused {
+            staking_position.accrual_paused_at = current_time;
+        }
+        
+        emit!(PositionRelocked {
+            nft_mint: staking_position.nft_mint,
+            new_duration: duration,
+            unlock_at,
+            carried_rewards: carried,
+        });
+        
+        Ok(())
+    }
+
+    // Maintenance crank: positions of `collection_account` that are still unclaimed
+    // STALE_POSITION_GRACE after unlock stop counting toward total_staked and their
+    // owner's UserStakeCount and UserStats. `remaining_accounts` holds one group per
+    // position: the position, then its owner's UserStakeCount and UserStats PDAs.
+    // Nothing else changes; the NFT stays
+    // claimable through claim_nft. Positions that aren't stale (or were already swept)
+    // are skipped. Anyone may call it.
+    pub fn sweep_stale_positions(ctx: Context<SweepStalePositions>) -> Result<()> {
+        require!(ctx.remaining_accounts.len().is_multiple_of(SWEEP_GROUP_LEN), StakingError::InvalidAmount);
+        ctx.accounts.staking_program.check_batch_size(ctx.remaining_accounts.len() / SWEEP_GROUP_LEN)?;
+        
+        let current_time = current_timestamp()?;
+        let collection_mint = ctx.accounts.collection_account.collection_mint;
+        
+        let mut seen: Vec<Pubkey> = Vec::with_capacity(ctx.remaining_accounts.len() / SWEEP_GROUP_LEN);
+        let mut positions_swept: u32 = 0;
+        for group in ctx.remaining_accounts.chunks(SWEEP_GROUP_LEN) {
+            let (account_info, user_stake_count, user_stats) = (&group[0], &group[1], &group[2]);
+            require!(!seen.contains(account_info.key), StakingError::InvalidPosition);
+            require!(account_info.is_writable, StakingError::InvalidPosition);
+            seen.push(*account_info.key);
+            
+            let mut position = read_position(account_info)?;
+            require!(position.collection_mint == collection_mint, StakingError::CollectionNotFound);
+            if !position.is_active || position.swept || current_time < position.unlock_at + STALE_POSITION_GRACE {
+                continue;
+            }
+            
+            position.swept = true;
+            write_position(account_info, &position)?;
+            ctx.accounts.staking_program.record_unstake()?;
+            ctx.accounts.collection_account.record_unstake()?;
+            release_stake_count(user_stake_count, &position)?;
+            update_user_stats(user_stats, &position.owner, |user_stats| user_stats.record_unstake())?;
+            positions_swept += 1;
+        }
+        
+        emit!(StalePositionsSwept {
+            collection_mint,
+            positions_swept,
+            total_staked: ctx.accounts.collection_account.total_staked,
+        });
+        
+        Ok(())
+    }
+
+    // Gives a staked position to `new_owner` while the NFT stays locked. The PDA is not
+    // re-derived: it keeps the seeds it was created with (nft_mint, original owner), and
+    // every owner check reads the `owner` field instead, so the new owner is the only
+    // one who can claim. Closing the position on claim refunds its rent to the new owner.
+    // The position moves to the new owner's UserStakeCount (subject to max_per_user) and
+    // UserStats, which claims and unlocks read under the current owner.
+    pub fn transfer_position(ctx: Context<TransferPosition>, new_owner: Pubkey) -> Result<()> {
+        require!(ctx.accounts.collection_account.positions_transferable, StakingError::PositionNotTransferable);
+        require!(new_owner != Pubkey::default(), StakingError::InvalidRecipient);
+        require!(new_owner != ctx.accounts.owner.key(), StakingError::InvalidRecipient);
+        
+        let staking_position = &mut ctx.accounts.staking_position;
+        let old_owner = staking_position.owner;
+        staking_position.transfer_to(new_owner)?;
+        
+        // A swept position is already out of the counters
+        let moved = u64::from(!staking_position.swept);
+        ctx.accounts.owner_stake_count.record_transfer_out(moved);
+        ctx.accounts.new_owner_stake_count.record_transfer_in(
+            &ctx.accounts.collection_account,
+            new_owner,
+            *ctx.bumps.get("new_owner_stake_count").unwrap(),
+            moved,
+        )?;
+        ctx.accounts.owner_stats.fill(ctx.accounts.owner.key(), *ctx.bumps.get("owner_stats").unwrap());
+        ctx.accounts.owner_stats.record_moved_out(moved);
+        ctx.accounts.new_owner_stats.record_moved_in(new_owner, *ctx.bumps.get("new_owner_stats").unwrap(), moved)?;
+        
+        emit!(PositionTransferred {
+            nft_mint: staking_position.nft_mint,
+            from: old_owner,
+            to: new_owner,
+        });
+        
+        Ok(())
+    }
+
+    // Itemizes how the position's settlement multiplier is built up right now: locked
+    // multiplier, collection floor, then the bonus components added on top (see
+    // CollectionAccount::settlement_multiplier). Read-only.
+    pub fn multiplier_breakdown(ctx: Context<GetMultiplierBreakdown>) -> Result<()> {
+        let staking_position = &ctx.accounts.staking_position;
+        let collection_account = &ctx.accounts.collection_account;
+        let current_time = current_timestamp()?;
+        
+        emit!(MultiplierBreakdown {
+            nft_mint: staking_position.nft_mint,
+            locked_multiplier: staking_position.locked_multiplier,
+            min_multiplier_bps: collection_account.min_multiplier_bps,
+            floored_multiplier: collection_account.effective_multiplier(staking_position.locked_multiplier),
+            early_adopter_bonus_bps: collection_account.early_adopter_bonus_for(staking_position, current_time),
+            pending_validation_boost_bps: collection_account.pending_validation_boost(current_time),
+            loyalty_bonus_bps: collection_account.loyalty_bonus_for(staking_position, current_time),
+            settlement_multiplier: collection_account
+                .apply_dynamic_multiplier(collection_account.settlement_multiplier(staking_position, current_time)?),
+        });
+        
+        Ok(())
+    }
+
+    // Stakes several NFTs of `collection_account` in one transaction, one duration per
+    // NFT. `remaining_accounts` holds one group per NFT, in order: nft_mint, nft_metadata,
+    // blocked_mint PDA, user_token_account, program_token_account and the (not yet
+    // created) staking_position PDA. Each item goes through the same checks as stake_nft,
+    // with membership proven from the metadata.
+    pub fn stake_nft_batch<'info>(
+        ctx: Context<'_, '_, '_, 'info, StakeNftBatch<'info>>,
+        durations: Vec<u8>,
+        terms_hash: [u8; 32],
+    ) -> Result<()> {
+        ctx.accounts.staking_program.check_batch_size(durations.len())?;
+        require!(
+            ctx.remaining_accounts.len() == durations.len() * STAKE_BATCH_GROUP_LEN,
+            StakingError::InvalidAmount
+        );
+        
+        let current_time = current_timestamp()?;
+        let user = ctx.accounts.user.key();
+        let collection_mint = ctx.accounts.collection_account.collection_mint;
+        check_account_eligible(&ctx.accounts.user, &ctx.accounts.staking_program, collection_mint)?;
+        
+        let stake_fee_lamports = ctx
+            .accounts
+            .staking_program
+            .stake_fee_lamports
+            .checked_mul(durations.len() as u64)
+            .ok_or(StakingError::ArithmeticOverflow)?;
+        collect_fee(
+            ctx.accounts.user.to_account_info(),
+            ctx.accounts.treasury.to_account_info(),
+            ctx.accounts.system_program.to_account_info(),
+            stake_fee_lamports,
+        )?;
+        
+        let groups = ctx.remaining_accounts.chunks(STAKE_BATCH_GROUP_LEN);
+        for (duration, group) in durations.iter().zip(groups) {
+            let (nft_mint_info, metadata_info, blocked_mint_info) = (&group[0], &group[1], &group[2]);
+            let (user_token_info, program_token_info, position_info) = (&group[3], &group[4], &group[5]);
+            let nft_mint = nft_mint_info.key();
+            
+            check_stake_allowed(
+                user,
+                &ctx.accounts.staking_program,
+                &ctx.accounts.collection_account,
+                None,
+                *duration,
+                current_time,
+                &terms_hash,
+            )?;
+            check_nft_membership(user, &nft_mint, &collection_mint, None, Some(metadata_info))?;
+            let (expected_blocked_mint, _) =
+                Pubkey::find_program_address(&[b"blocked_mint", nft_mint.as_ref()], &crate::ID);
+            require!(blocked_mint_info.key() == expected_blocked_mint, StakingError::InvalidPosition);
+            check_mint_not_blocked(user, collection_mint, blocked_mint_info)?;
+            
+            let mint = Account::<Mint>::try_from(nft_mint_info)?;
+            require!(mint.decimals == 0 && mint.supply == 1, StakingError::NotAnNft);
+            let user_token_account = Account::<TokenAccount>::try_from(user_token_info)?;
+            require!(user_token_account.mint == nft_mint, StakingError::InvalidRecipient);
+            require!(user_token_account.amount >= 1, StakingError::NotAnNft);
+            let program_token_account = Account::<TokenAccount>::try_from(program_token_info)?;
+            require!(
+                program_token_account.owner == ctx.accounts.staking_program.key() && program_token_account.mint == nft_mint,
+                StakingError::InvalidRecipient
+            );
+            
+            let cpi_accounts = Transfer {
+                from: user_token_info.clone(),
+                to: program_token_info.clone(),
+                authority: ctx.accounts.user.to_account_info(),
+            };
+            token::transfer(CpiContext::new(ctx.accounts.token_program.to_account_info(), cpi_accounts), 1)?;
+            
+            let bump = create_position_account(
+                ctx.accounts.user.to_account_info(),
+                position_info,
+                ctx.accounts.system_program.to_account_info(),
+                &nft_mint,
+                &user,
+            )?;
+            // The account is freshly zeroed, so start from its blank layout and fill it in
+            let mut staking_position =
+                StakingPosition::try_deserialize_unchecked(&mut &position_info.try_borrow_data()?[..])?;
+            staking_position.open(user, nft_mint, &ctx.accounts.collection_account, *duration, current_time, bump)?;
+            staking_position.accepted_terms_hash = terms_hash;
+            write_position(position_info, &staking_position)?;
+            
+            ctx.accounts.user_stake_count.record_stake(
+                &ctx.accounts.collection_account,
+                user,
+                *ctx.bumps.get("user_stake_count").unwrap(),
+            )?;
+            ctx.accounts.user_stats.record_stake(user, *ctx.bumps.get("user_stats").unwrap())?;
+            ctx.accounts.staking_program.record_stake()?;
+            ctx.accounts.collection_account.record_stake()?;
+            
+            emit!(NftStaked {
+                user,
+                nft_mint,
+                collection_mint,
+                duration: *duration,
+                unlock_at: staking_position.unlock_at,
+                display_multiplier: staking_position.locked_multiplier,
+                multiplier_computed: ctx.accounts.collection_account.computed_multipliers,
+            });
+        }
+        
+        Ok(())
+    }
+
+    // Reports whether an emergency request (admin_unlock or admin_upgrade_tier) would go
+    // through its second step right now. Read-only; requests don't expire, so the only
+    // terminal state is `executed`.
+    pub fn check_emergency_ready(ctx: Context<CheckEmergencyReady>) -> Result<()> {
+        let emergency_request = &ctx.accounts.emergency_request;
+        let current_time = current_timestamp()?;
+        let ready_at = emergency_request.requested_at + EMERGENCY_DELAY;
+        
+        emit!(EmergencyReadiness {
+            emergency_request: emergency_request.key(),
+            requested_at: emergency_request.requested_at,
+            ready_at,
+            seconds_remaining: ready_at.saturating_sub(current_time).max(0) as u64,
+            executed: emergency_request.executed,
+            executable: !emergency_request.executed && current_time >= ready_at,
+        });
+        
+        Ok(())
+    }
+
+    // The vault's token can only change while the vault is empty
+    pub fn set_reward_token_mint(ctx: Context<UpdateProgramConfig>, reward_token_mint: Pubkey) -> Result<()> {
+        let staking_program = &mut ctx.accounts.staking_program;
+        require!(staking_program.vault_balance == 0, StakingError::VaultNotEmpty);
+        staking_program.reward_token_mint = reward_token_mint;
+        
+        emit!(AdminAction {
+            admin: ctx.accounts.authority.key(),
+            action: "setRewardTokenMint".to_string(),
+            data: reward_token_mint.to_string(),
+        });
+        
+        Ok(())
+    }
+
+    // Moves reward tokens from an admin into the reward vault, creating the vault on first use
+    pub fn fund_reward_vault(ctx: Context<FundRewardVault>, amount: u64) -> Result<()> {
+        require!(amount > 0, StakingError::InvalidAmount);
+        
+        let cpi_accounts = Transfer {
+            from: ctx.accounts.admin_token_account.to_account_info(),
+            to: ctx.accounts.reward_vault.to_account_info(),
+            authority: ctx.accounts.admin.to_account_info(),
+        };
+        token::transfer(CpiContext::new(ctx.accounts.token_program.to_account_info(), cpi_accounts), amount)?;
+        
+        let staking_program = &mut ctx.accounts.staking_program;
+        staking_program.vault_balance = staking_program
+            .vault_balance
+            .checked_add(amount)
+            .ok_or(StakingError::ArithmeticOverflow)?;
+        
+        emit!(VaultFunded {
+            admin: ctx.accounts.admin.key(),
+            amount,
+            vault_balance: staking_program.vault_balance,
+        });
+        
+        Ok(())
+    }
+
+    // Returns vault tokens to the authority, never dipping below what is owed to stakers
+    // (outstanding_rewards)
+    pub fn withdraw_unallocated(ctx: Context<WithdrawUnallocated>, amount: u64) -> Result<()> {
+        require!(amount > 0, StakingError::InvalidAmount);
+        let staking_program = &ctx.accounts.staking_program;
+        let unallocated = staking_program.vault_balance.saturating_sub(staking_program.outstanding_rewards);
+        require!(amount <= unallocated, StakingError::InsufficientVaultBalance);
+        
+        let seeds: &[&[u8]] = &[b"staking_program".as_ref(), &[staking_program.bump]];
+        let signer = &[seeds];
+        let cpi_accounts = Transfer {
+            from: ctx.accounts.reward_vault.to_account_info(),
+            to: ctx.accounts.authority_token_account.to_account_info(),
+            authority: ctx.accounts.staking_program.to_account_info(),
+        };
+        let cpi_ctx = CpiContext::new_with_signer(ctx.accounts.token_program.to_account_info(), cpi_accounts, signer);
+        token::transfer(cpi_ctx, amount)?;
+        
+        let staking_program = &mut ctx.accounts.staking_program;
+        staking_program.vault_balance -= amount;
+        
+        emit!(VaultWithdrawn {
+            authority: ctx.accounts.authority.key(),
+            amount,
+            vault_balance: staking_program.vault_balance,
+        });
+        
+        Ok(())
+    }
+
+    // Vault counterpart of claim_tickets: pays the position's settled, unpaid rewards in the
+    // reward token. Shares `tickets_minted` with claim_tickets, so each ticket pays once,
+    // and the daily emission cap, so the vault can't be drained faster than tickets mint.
+    pub fn claim_vault_rewards(ctx: Context<ClaimVaultRewards>) -> Result<()> {
+        let current_time = current_timestamp()?;
+        require!(
+            !ctx.accounts.staking_program.is_instruction_paused(PAUSE_CLAIM_REWARDS, current_time),
+            StakingError::ContractPaused
+        );
+        
+        // Only what fits under the daily emission cap is paid; the rest stays claimable
+        let amount = ctx
+            .accounts
+            .staking_program
+            .take_emission_allowance(ctx.accounts.staking_position.mintable_tickets(), current_time)?;
+        require!(amount <= ctx.accounts.staking_program.vault_balance, StakingError::InsufficientVaultBalance);
+        
+        if amount > 0 {
+            let seeds: &[&[u8]] = &[b"staking_program".as_ref(), &[ctx.accounts.staking_program.bump]];
+            let signer = &[seeds];
+            let cpi_accounts = Transfer {
+                from: ctx.accounts.reward_vault.to_account_info(),
+                to: ctx.accounts.user_reward_account.to_account_info(),
+                authority: ctx.accounts.staking_program.to_account_info(),
+            };
+            let cpi_ctx = CpiContext::new_with_signer(ctx.accounts.token_program.to_account_info(), cpi_accounts, signer);
+            token::transfer(cpi_ctx, amount)?;
+        }
+        
+        let staking_position = &mut ctx.accounts.staking_position;
+        staking_position.tickets_minted += amount;
+        let staking_program = &mut ctx.accounts.staking_program;
+        staking_program.vault_balance -= amount;
+        staking_program.record_paid(amount);
+        
+        emit!(VaultRewardsClaimed {
+            user: ctx.accounts.user.key(),
+            nft_mint: staking_position.nft_mint,
+            amount,
+        });
+        
+        Ok(())
+    }
+
+    // Withdraws a pending admin_unlock request before it executes. The PDA is closed
+    // (rent back to the requester), so a later admin_unlock starts the delay over.
+    pub fn cancel_emergency_unlock(ctx: Context<CancelEmergencyUnlock>) -> Result<()> {
+        let emergency_request = &mut ctx.accounts.emergency_request;
+        emergency_request.requested_at = 0;
+        ctx.accounts.active_emergency_requests.remove(&emergency_request.key());
+        
+        emit!(EmergencyAction {
+            admin: ctx.accounts.signer.key(),
+            action: "emergencyUnlockCancelled".to_string(),
+            reason: emergency_request.reason.clone(),
+        });
+        
+        Ok(())
+    }
+}
+
+// Account structures
+#[account]
+pub struct StakingProgram {
+    pub authority: Pubkey,
+    pub multi_sig_threshold: u8,
+    pub total_staked: u64,
+    pub total_collections: u64,
+    pub is_paused: bool,
+    pub paused_at: i64,
+    pub migration_ends_at: i64,
+    pub paused_instructions: u32,
+    pub stake_fee_lamports: u64,
+    pub treasury: Pubkey,
+    pub max_lock_duration: i64,
+    // Caller programs allowed to invoke CPI-gated instructions
+    pub cpi_whitelist: Vec<Pubkey>,
+    // Staking stays closed until this many collections are validated (0 = no gating)
+    pub validated_collections: u64,
+    pub min_validated_collections: u64,
+    // Flat fee charged to the treasury on each claim_rewards
+    pub claim_fee_lamports: u64,
+    // Hash of the terms users must accept to stake; all zeroes disables the check
+    pub current_terms_hash: [u8; 32],
+    // Key whose signature authorizes credit_rewards batches (default = disabled), and
+    // the last batch nonce it used
+    pub reward_oracle: Pubkey,
+    pub oracle_nonce: u64,
+    // Distinct admin approvals needed to unpause
+    pub unpause_threshold: u8,
+    // Ladder for collections using computed multipliers (see computed_multiplier)
+    pub multiplier_base: u64,
+    pub multiplier_growth: u64,
+    // Stakes are rejected before this time (0 = open immediately)
+    pub staking_starts_at: i64,
+    // SPL mint claim_tickets mints settled tickets from (default = tickets stay off-chain)
+    pub ticket_mint: Pubkey,
+    // Share of a position's settled, unminted tickets clawed back by unstake_early
+    pub early_unstake_penalty_bps: u16,
+    // Lamports charged to non-authority callers of add_collection (0 = free)
+    pub collection_onboarding_fee: u64,
+    // SPL token paid out of the reward vault by claim_vault_rewards (default = no vault)
+    pub reward_token_mint: Pubkey,
+    // Reward tokens held in the vault, as tracked by fund/withdraw/claim
+    pub vault_balance: u64,
+    // Settled rewards not yet paid out by claim_tickets or claim_vault_rewards. An upper
+    // bound: settled rewards of positions closed before payout stay counted.
+    pub outstanding_rewards: u64,
+    // Balance a wallet must hold to stake, as a proxy for account age (0 = off)
+    pub min_staker_lamports: u64,
+    // Items a single batch instruction may process (see MAX_BATCH_SIZE_CEILING)
+    pub max_batch_size: u16,
+    // When set, only validated (and not lapsed) collections accept new stakes
+    pub require_validation: bool,
+    // Cap on tickets paid by claim_tickets and claim_vault_rewards per rolling 24h window
+    // (0 = no cap), and the current window's start and usage
+    pub max_tickets_per_day: u64,
+    pub tickets_emitted_today: u64,
+    pub emission_window_start: i64,
+    pub bump: u8,
+}
+
+impl StakingProgram {
+    // A pause lapses on its own AUTO_UNPAUSE_DELAY after it started, even before
+    // try_auto_unpause clears the flag
+    pub fn auto_unpause_due(&self, current_time: i64) -> bool {
+        self.is_paused && self.paused_at > 0 && current_time >= self.paused_at + naffles_staking::AUTO_UNPAUSE_DELAY
+    }
+
+    pub fn is_paused_at(&self, current_time: i64) -> bool {
+        self.is_paused && !self.auto_unpause_due(current_time)
+    }
+
+    pub fn is_instruction_paused(&self, flag: u32, current_time: i64) -> bool {
+        self.is_paused_at(current_time) || self.paused_instructions & flag != 0
+    }
+
+    pub fn check_lock_duration(&self, unlock_at: i64, current_time: i64) -> Result<()> {
+        require!(unlock_at - current_time <= self.max_lock_duration, StakingError::LockTooLong);
+        Ok(())
+    }
+
+    // Counter updates fail cleanly instead of wrapping if accounting ever drifts
+    pub fn record_stake(&mut self) -> Result<()> {
+        self.total_staked = self.total_staked.checked_add(1).ok_or(StakingError::ArithmeticOverflow)?;
+        Ok(())
+    }
+
+    pub fn record_unstake(&mut self) -> Result<()> {
+        self.total_staked = self.total_staked.checked_sub(1).ok_or(StakingError::ArithmeticOverflow)?;
+        Ok(())
+    }
+
+    pub fn check_batch_size(&self, len: usize) -> Result<()> {
+        require!(len <= self.max_batch_size as usize, StakingError::BatchTooLarge);
+        Ok(())
+    }
+
+    // Portion of `amount` tickets that may be paid out now under `max_tickets_per_day`, counted
+    // against the current 24h window (a new window starts once the last one has run out).
+    // Fails when the window's allowance is used up and something was asked for.
+    pub fn take_emission_allowance(&mut self, amount: u64, current_time: i64) -> Result<u64> {
+        if self.max_tickets_per_day == 0 {
+            return Ok(amount);
+        }
+        if current_time >= self.emission_window_start + naffles_staking::SECONDS_PER_DAY as i64 {
+            self.emission_window_start = current_time;
+            self.tickets_emitted_today = 0;
+        }
+        let allowance = self.max_tickets_per_day.saturating_sub(self.tickets_emitted_today);
+        require!(amount == 0 || allowance > 0, StakingError::DailyEmissionCapReached);
+        let granted = amount.min(allowance);
+        self.tickets_emitted_today += granted;
+        Ok(granted)
+    }
+
+    pub fn record_settled(&mut self, amount: u64) -> Result<()> {
+        self.outstanding_rewards = self.outstanding_rewards.checked_add(amount).ok_or(StakingError::ArithmeticOverflow)?;
+        Ok(())
+    }
+
+    pub fn record_paid(&mut self, amount: u64) {
+        self.outstanding_rewards = self.outstanding_rewards.saturating_sub(amount);
+    }
+
+    // Whether settled tickets are paid on-chain (claim_tickets or claim_vault_rewards)
+    // rather than credited off-chain from RewardsClaimed
+    pub fn pays_out_on_chain(&self) -> bool {
+        self.ticket_mint != Pubkey::default() || self.reward_token_mint != Pubkey::default()
+    }
+
+    // Multiplier for a duration tier (0 = 6m, 1 = 12m, 2 = 3y) on the computed ladder,
+    // in basis points:
+    //   multiplier(tier) = multiplier_base * (multiplier_growth / 10_000)^tier
+    // so each longer tier scales the previous one by the growth factor.
+    pub fn computed_multiplier(&self, tier: u8) -> Result<u64> {
+        require!(tier <= 2, StakingError::InvalidDuration);
+        let mut multiplier = self.multiplier_base as u128;
+        for _ in 0..tier {
+            multiplier = multiplier
+                .checked_mul(self.multiplier_growth as u128)
+                .ok_or(StakingError::ArithmeticOverflow)?
+                / naffles_staking::BASIS_POINTS as u128;
+        }
+        u64::try_from(multiplier).map_err(|_| StakingError::ArithmeticOverflow.into())
+    }
+}
+
+#[account]
+pub struct AdminAccount {
+    pub admin: Pubkey,
+    pub is_active: bool,
+    pub added_at: i64,
+    pub bump: u8,
+}
+
+// A finished deactivation of a collection, from set_collection_active(false) to the
+// reactivation
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, Debug)]
+pub struct InactivePeriod {
+    pub start: i64,
+    pub end: i64,
+}
+
+#[account]
+pub struct CollectionAccount {
+    pub collection_mint: Pubkey,
+    pub six_month_tickets: u64,
+    pub twelve_month_tickets: u64,
+    pub three_year_tickets: u64,
+    pub six_month_multiplier: u64,
+    pub twelve_month_multiplier: u64,
+    pub three_year_multiplier: u64,
+    pub is_active: bool,
+    pub is_validated: bool,
+    pub total_staked: u64,
+    pub reward_pool: u64,
+    pub reward_pool_remaining: u64,
+    pub vesting_cliff_bps: u16,
+    pub honor_existing_on_deactivate: bool,
+    // Set when the collection is deactivated, 0 while active
+    pub deactivated_at: i64,
+    // Whether positions in this collection may change owner while staked
+    pub positions_transferable: bool,
+    // Floor on the multiplier used for settlement, whatever a position locked in
+    pub min_multiplier_bps: u64,
+    // One of the CURVE_* constants
+    pub distribution_curve: u8,
+    // Mint this collection was registered under before its last migration, if any.
+    // The PDA address stays derived from the original mint.
+    pub previous_collection_mint: Pubkey,
+    // First time the collection was validated, 0 if never
+    pub validated_at: i64,
+    // Extra multiplier for positions staked before `validated_at`
+    pub early_adopter_bonus_bps: u16,
+    // When set, rewards accrue from max(staked_at, validated_at) instead of staked_at
+    pub accrue_from_validation: bool,
+    // Penalty on a claim made right at stake time; decays linearly to 0 at unlock
+    pub early_claim_penalty_bps: u16,
+    // How long a validation lasts (0 = forever) and when the current one lapses
+    // (0 = never). A lapsed validation counts as unvalidated until renewed.
+    pub validation_validity_period: i64,
+    pub validation_expires_at: i64,
+    // Treasury-funded match on claims, as a share of each claim, while budget lasts
+    pub reward_match_bps: u16,
+    pub match_budget: u64,
+    // When set, the tier multipliers are derived from the program-wide ladder instead of
+    // being configured per collection
+    pub computed_multipliers: bool,
+    // Rewards can't be claimed until `total_staked` first reaches the threshold
+    // (0 = immediately). Activation is permanent and accrual is retroactive.
+    pub reward_activation_threshold: u64,
+    pub rewards_activated: bool,
+    // Minimum seconds between two claim_rewards on the same position (0 = no cooldown)
+    pub claim_cooldown: i64,
+    // Extra multiplier paid while the collection is not (or no longer) validated
+    pub pending_validation_boost_bps: u16,
+    // Most NFTs that can be staked in the collection at once (0 = unlimited)
+    pub max_stake: u64,
+    // Most open positions a single wallet may hold in the collection (0 = unlimited)
+    pub max_per_user: u64,
+    // Reward rounding: up (in the user's favor) when set, down otherwise
+    pub favor_user: bool,
+    // Scale multipliers down as the reward pool depletes (see apply_dynamic_multiplier)
+    pub dynamic_multiplier: bool,
+    // Bonus per full month held past unlock, and its cap (see loyalty_bonus_for)
+    pub loyalty_bonus_bps_per_month: u16,
+    pub max_loyalty_bps: u16,
+    // Set (for good) by freeze_collection_rewards; accrual stops at `rewards_frozen_at`
+    pub rewards_frozen: bool,
+    pub rewards_frozen_at: i64,
+    // Earlier deactivations, oldest first. Without the honor policy their time inside a
+    // position's term doesn't accrue, as for the current one (`deactivated_at`).
+    pub inactive_periods: Vec<InactivePeriod>,
+    // Periods pushed out of `inactive_periods`: the span from the first one's start to
+    // the last one's end, and the inactive seconds inside it
+    pub folded_inactive_span: InactivePeriod,
+    pub folded_inactive_seconds: i64,
+    pub bump: u8,
+}
+
+impl CollectionAccount {
+    pub const LEN: usize = 8 + 32 + 8 + 8 + 8 + 8 + 8 + 8 + 1 + 1 + 8 + 8 + 8 + 2 + 1 + 8 + 1 + 8 + 1 + 32 + 8 + 2 + 1 + 2 + 8 + 8 + 2 + 8 + 1 + 8 + 1 + 8 + 2 + 8 + 8 + 1 + 1 + 2 + 2 + 1 + 8
+        + 4 + 16 * naffles_staking::MAX_INACTIVE_PERIODS + 16 + 8 + 1;
+    
+    // A freshly added collection: active, unvalidated, default multipliers, no pool
+    pub fn new(collection_mint: Pubkey, tickets: [u64; 3], bump: u8) -> Self {
+        Self {
+            collection_mint,
+            six_month_tickets: tickets[0],
+            twelve_month_tickets: tickets[1],
+            three_year_tickets: tickets[2],
+            six_month_multiplier: 11000, // 1.1x in basis points
+            twelve_month_multiplier: 12500, // 1.25x
+            three_year_multiplier: 15000, // 1.5x
+            is_active: true,
+            is_validated: false,
+            total_staked: 0,
+            reward_pool: 0,
+            reward_pool_remaining: 0,
+            vesting_cliff_bps: 0,
+            honor_existing_on_deactivate: true,
+            deactivated_at: 0,
+            positions_transferable: true,
+            min_multiplier_bps: 0,
+            distribution_curve: naffles_staking::CURVE_LINEAR,
+            previous_collection_mint: Pubkey::default(),
+            validated_at: 0,
+            early_adopter_bonus_bps: 0,
+            accrue_from_validation: false,
+            early_claim_penalty_bps: 0,
+            validation_validity_period: 0,
+            validation_expires_at: 0,
+            reward_match_bps: 0,
+            match_budget: 0,
+            computed_multipliers: false,
+            reward_activation_threshold: 0,
+            rewards_activated: false,
+            claim_cooldown: 0,
+            pending_validation_boost_bps: 0,
+            max_stake: 0,
+            max_per_user: 0,
+            favor_user: false,
+            dynamic_multiplier: false,
+            loyalty_bonus_bps_per_month: 0,
+            max_loyalty_bps: 0,
+            rewards_frozen: false,
+            rewards_frozen_at: 0,
+            inactive_periods: Vec::new(),
+            folded_inactive_span: InactivePeriod { start: 0, end: 0 },
+            folded_inactive_seconds: 0,
+            bump,
+        }
+    }
+
+    // Seconds of [from, to] the collection spent in finished inactive periods
+    pub fn inactive_between(&self, from: i64, to: i64) -> i64 {
+        self.inactive_periods
+            .iter()
+            .map(|period| elapsed_since(period.start.max(from), period.end.min(to)))
+            .sum::<i64>()
+            + self.folded_inactive_between(from, to)
+    }
+
+    // The folded periods' share of [from, to]. Exact when the window covers the whole
+    // span or misses it; for a partial overlap, the least the folded seconds could
+    // fall inside it, so the position is never over-charged.
+    pub fn folded_inactive_between(&self, from: i64, to: i64) -> i64 {
+        let span = self.folded_inactive_span;
+        let overlap = elapsed_since(span.start.max(from), span.end.min(to));
+        if overlap == 0 {
+            return 0;
+        }
+        let outside = (span.end - span.start) - overlap;
+        (self.folded_inactive_seconds - outside).max(0)
+    }
+
+    // Moves a finished period out of `inactive_periods` into the folded total
+    pub fn fold_inactive_period(&mut self, period: InactivePeriod) -> Result<()> {
+        if self.folded_inactive_seconds == 0 {
+            self.folded_inactive_span.start = period.start;
+        }
+        self.folded_inactive_span.end = period.end;
+        self.folded_inactive_seconds = self
+            .folded_inactive_seconds
+            .checked_add(period.end - period.start)
+            .ok_or(StakingError::ArithmeticOverflow)?;
+        Ok(())
+    }
+
+    // `locked_multiplier` raised to the collection floor
+    pub fn effective_multiplier(&self, locked_multiplier: u64) -> u64 {
+        locked_multiplier.max(self.min_multiplier_bps)
+    }
+
+    // Validated and not lapsed at `current_time`
+    pub fn is_validated_at(&self, current_time: i64) -> bool {
+        self.is_validated && (self.validation_expires_at == 0 || current_time < self.validation_expires_at)
+    }
+
+    pub fn next_validation_expiry(&self, current_time: i64) -> i64 {
+        if self.validation_validity_period == 0 {
+            0
+        } else {
+            current_time + self.validation_validity_period
+        }
+    }
+
+    // Early adopters are positions opened strictly before the collection was first
+    // validated; they took the unvalidated-period risk and earn the bonus, paid only
+    // while the collection's validation is current. A lapse lowers accrual but can't
+    // claw back what was settled at the bonus rate (see StakingPosition::mark_settled).
+    pub fn early_adopter_bonus_for(&self, position: &StakingPosition, current_time: i64) -> u16 {
+        if self.validated_at > 0 && position.staked_at < self.validated_at && self.is_validated_at(current_time) {
+            self.early_adopter_bonus_bps
+        } else {
+            0
+        }
+    }
+
+    // Time a position starts accruing. Under `accrue_from_validation`, a collection that
+    // was never validated accrues nothing yet, so the start is `current_time`. The start
+    // never passes unlock, so a position validated late earns only the part of its term
+    // left after validation.
+    pub fn accrual_start(&self, position: &StakingPosition, current_time: i64) -> i64 {
+        if !self.accrue_from_validation {
+            position.staked_at
+        } else if self.validated_at == 0 {
+            current_time.min(position.unlock_at)
+        } else {
+            position.staked_at.max(self.validated_at).min(position.unlock_at)
+        }
+    }
+
+    // Risk premium for staking into a collection whose validation isn't current. Like
+    // every multiplier component it is evaluated at settlement, so it stops applying to
+    // all unclaimed accrual as soon as the collection is validated.
+    pub fn pending_validation_boost(&self, current_time: i64) -> u16 {
+        if self.is_validated_at(current_time) {
+            0
+        } else {
+            self.pending_validation_boost_bps
+        }
+    }
+
+    // Loyalty rate reached by holding past unlock without claiming the NFT:
+    // `loyalty_bonus_bps_per_month` for each full LOYALTY_MONTH since `unlock_at`, capped
+    // at `max_loyalty_bps`
+    pub fn loyalty_bonus_for(&self, position: &StakingPosition, current_time: i64) -> u16 {
+        let months = (elapsed_since(position.unlock_at, current_time) / naffles_staking::LOYALTY_MONTH) as u64;
+        self.loyalty_rate(months) as u16
+    }
+
+    fn loyalty_rate(&self, month: u64) -> u64 {
+        month
+            .saturating_mul(self.loyalty_bonus_bps_per_month as u64)
+            .min(self.max_loyalty_bps as u64)
+    }
+
+    // Tickets earned for the time held past unlock, and only that time. Each full
+    // LOYALTY_MONTH past `unlock_at` pays a month of the position's term accrual rate
+    // (`full_term_rewards` over the term) at that month's loyalty rate, so the n-th month
+    // earns n * loyalty_bonus_bps_per_month (capped at max_loyalty_bps) of a month's accrual.
+    pub fn loyalty_rewards(
+        &self,
+        position: &StakingPosition,
+        full_term_rewards: u128,
+        current_time: i64,
+    ) -> Result<u128> {
+        let term = position.unlock_at.saturating_sub(position.staked_at);
+        if self.loyalty_bonus_bps_per_month == 0 || term <= 0 {
+            return Ok(0);
+        }
+        let months = (elapsed_since(position.unlock_at, current_time) / naffles_staking::LOYALTY_MONTH) as u128;
+        let per_month = self.loyalty_bonus_bps_per_month as u128;
+        let cap = self.max_loyalty_bps as u128;
+        // Sum of the monthly rates: an arithmetic series up to the cap, flat after it
+        let months_to_cap = cap / per_month;
+        let rate_months = if months <= months_to_cap {
+            per_month * months * (months + 1) / 2
+        } else {
+            per_month * months_to_cap * (months_to_cap + 1) / 2 + (months - months_to_cap) * cap
+        };
+        let numerator = full_term_rewards
+            .checked_mul(naffles_staking::LOYALTY_MONTH as u128)
+            .and_then(|value| value.checked_mul(rate_months))
+            .ok_or(StakingError::ArithmeticOverflow)?;
+        Ok(div_round(numerator, term as u128 * naffles_staking::BASIS_POINTS as u128, self.favor_user))
+    }
+
+    // Floored multiplier plus any early-adopter bonus or pending-validation boost, in basis
+    // points. The loyalty bonus is accrued separately (see loyalty_rewards). The dynamic scaling is not included; it applies to each
+    // settlement's new accrual (see unsettled_rewards).
+    pub fn settlement_multiplier(&self, position: &StakingPosition, current_time: i64) -> Result<u64> {
+        let floored = self.effective_multiplier(position.locked_multiplier) as u128;
+        let bonus = self.early_adopter_bonus_for(position, current_time) as u128
+            + self.pending_validation_boost(current_time) as u128;
+        let multiplier = floored
+            .checked_mul(naffles_staking::BASIS_POINTS as u128 + bonus)
+            .ok_or(StakingError::ArithmeticOverflow)?
+            / naffles_staking::BASIS_POINTS as u128;
+        u64::try_from(multiplier).map_err(|_| StakingError::ArithmeticOverflow.into())
+    }
+
+    // Under `dynamic_multiplier`, the part of a multiplier above 1x shrinks linearly with
+    // the share of the reward pool left:
+    //   dynamic = max(10_000 + (multiplier - 10_000) * reward_pool_remaining / reward_pool, floor)
+    // where floor is min_multiplier_bps (capped at `multiplier`), so a full pool pays the
+    // whole multiplier and an empty one pays 1x or the floor. It is applied to each
+    // settlement's new accrual at the pool level of that settlement, never to tickets
+    // settled earlier.
+    pub fn apply_dynamic_multiplier(&self, multiplier: u64) -> u64 {
+        self.decayed_multiplier(multiplier).max(self.min_multiplier_bps.min(multiplier))
+    }
+
+    // Whether the floor, rather than the pool level, set apply_dynamic_multiplier's result
+    pub fn is_decay_floored(&self, multiplier: u64) -> bool {
+        self.decayed_multiplier(multiplier) < self.apply_dynamic_multiplier(multiplier)
+    }
+
+    fn decayed_multiplier(&self, multiplier: u64) -> u64 {
+        let bps = naffles_staking::BASIS_POINTS;
+        if !self.dynamic_multiplier || self.reward_pool == 0 || multiplier <= bps {
+            return multiplier;
+        }
+        let bonus = (multiplier - bps) as u128 * self.reward_pool_remaining as u128 / self.reward_pool as u128;
+        bps + bonus as u64
+    }
+
+    // `amount` accrued at `multiplier`, rescaled to the dynamic multiplier
+    pub fn scale_new_rewards(&self, amount: u64, multiplier: u64) -> u64 {
+        let dynamic = self.apply_dynamic_multiplier(multiplier);
+        if dynamic == multiplier {
+            return amount;
+        }
+        div_round(amount as u128 * dynamic as u128, multiplier as u128, self.favor_user) as u64
+    }
+
+    pub fn tickets_for(&self, duration: u8) -> Result<u64> {
+        match duration {
+            0 => Ok(self.six_month_tickets),
+            1 => Ok(self.twelve_month_tickets),
+            2 => Ok(self.three_year_tickets),
+            _ => Err(StakingError::InvalidDuration.into()),
+        }
+    }
+
+    pub fn is_stake_cap_reached(&self) -> bool {
+        self.max_stake > 0 && self.total_staked >= self.max_stake
+    }
+
+    pub fn record_stake(&mut self) -> Result<()> {
+        self.total_staked = self.total_staked.checked_add(1).ok_or(StakingError::ArithmeticOverflow)?;
+        self.check_rewards_activation();
+        Ok(())
+    }
+
+    pub fn record_unstake(&mut self) -> Result<()> {
+        self.total_staked = self.total_staked.checked_sub(1).ok_or(StakingError::ArithmeticOverflow)?;
+        Ok(())
+    }
+
+    // Latches `rewards_activated` the first time participation reaches the threshold
+    pub fn check_rewards_activation(&mut self) {
+        if self.reward_activation_threshold == 0
+            || self.rewards_activated
+            || self.total_staked < self.reward_activation_threshold
+        {
+            return;
+        }
+        self.rewards_activated = true;
+        emit!(CollectionRewardsActivated {
+            collection_mint: self.collection_mint,
+            total_staked: self.total_staked,
+            threshold: self.reward_activation_threshold,
+        });
+    }
+
+    // Earliest time `position` may claim rewards again
+    pub fn next_claim_at(&self, position: &StakingPosition) -> i64 {
+        if self.claim_cooldown == 0 || position.last_claim_at == 0 {
+            return 0;
+        }
+        position.last_claim_at + self.claim_cooldown
+    }
+
+    // Overwrites the tier multipliers with the program's computed ladder
+    pub fn apply_multiplier_curve(&mut self, staking_program: &StakingProgram) -> Result<()> {
+        self.six_month_multiplier = staking_program.computed_multiplier(0)?;
+        self.twelve_month_multiplier = staking_program.computed_multiplier(1)?;
+        self.three_year_multiplier = staking_program.computed_multiplier(2)?;
+        Ok(())
+    }
+
+    pub fn multiplier_for(&self, duration: u8) -> Result<u64> {
+        match duration {
+            0 => Ok(self.six_month_multiplier),
+            1 => Ok(self.twelve_month_multiplier),
+            2 => Ok(self.three_year_multiplier),
+            _ => Err(StakingError::InvalidDuration.into()),
+        }
+    }
+}
+
+#[account]
+pub struct StakingPosition {
+    pub owner: Pubkey,
+    pub nft_mint: Pubkey,
+    pub collection_mint: Pubkey,
+    pub staked_at: i64,
+    pub unlock_at: i64,
+    pub duration: u8,
+    pub is_active: bool,
+    pub total_rewards_earned: u64,
+    pub rewards_claimed: u64,
+    // Tier used for rewards; starts equal to `duration` and can only be raised by an admin
+    pub reward_tier: u8,
+    // Multiplier captured at stake time. This, not the collection's current config, is
+    // what settles rewards (raised to the collection's floor if one is set).
+    pub locked_multiplier: u64,
+    // Optional key allowed to claim the NFT on the owner's behalf
+    pub claim_delegate: Option<Pubkey>,
+    // Earned rewards currently locked in this position's reward stake
+    pub rewards_staked: u64,
+    // Layout version; see StakingPosition::CURRENT_VERSION and migrate_position
+    pub version: u8,
+    // Admin hold on reward accrual during a dispute. Time spent paused, past and current,
+    // doesn't count toward rewards; the unlock time is unaffected.
+    pub accrual_paused: bool,
+    pub accrual_paused_at: i64,
+    pub accrual_paused_total: i64,
+    // Terms hash the owner accepted when staking, kept for audit
+    pub accepted_terms_hash: [u8; 32],
+    // Settled tickets already minted as tokens by claim_tickets
+    pub tickets_minted: u64,
+    // Time of the last claim_rewards, 0 if never claimed
+    pub last_claim_at: i64,
+    // Set by sweep_stale_positions once the position no longer counts in total_staked
+    pub swept: bool,
+    // Rewards settled into total_rewards_earned by relock, summed over all relocks
+    pub carried_rewards: u64,
+    // Latest snapshot_id this position was counted in by snapshot_user_rewards, 0 if none
+    pub last_snapshot_id: u64,
+    pub bump: u8,
+}
+
+impl StakingPosition {
+    pub const LEN: usize = 8 + 32 + 32 + 32 + 8 + 8 + 1 + 1 + 8 + 8 + 1 + 8 + (1 + 32) + 8 + 1 + 1 + 8 + 8 + 32 + 8 + 8 + 1 + 8 + 8 + 1;
+    // Bumped with every change to the layout; see LAYOUT_LENS
+    pub const CURRENT_VERSION: u8 = 8;
+    
+    // Account size of each layout, indexed by version - 1. Every version appended fields
+    // just before `bump`:
+    //   1 rewards_staked and version, 2 the accrual pause fields, 3 accepted_terms_hash,
+    //   4 tickets_minted, 5 last_claim_at, 6 swept, 7 carried_rewards, 8 last_snapshot_id
+    pub const LAYOUT_LENS: [usize; Self::CURRENT_VERSION as usize] = [
+        Self::LAYOUT_V1_LEN,
+        Self::LAYOUT_V1_LEN + 1 + 8 + 8,
+        Self::LAYOUT_V1_LEN + 1 + 8 + 8 + 32,
+        Self::LAYOUT_V1_LEN + 1 + 8 + 8 + 32 + 8,
+        Self::LAYOUT_V1_LEN + 1 + 8 + 8 + 32 + 8 + 8,
+        Self::LAYOUT_V1_LEN + 1 + 8 + 8 + 32 + 8 + 8 + 1,
+        Self::LAYOUT_V1_LEN + 1 + 8 + 8 + 32 + 8 + 8 + 1 + 8,
+        Self::LEN,
+    ];
+    const LAYOUT_V1_LEN: usize = 8 + 32 + 32 + 32 + 8 + 8 + 1 + 1 + 8 + 8 + 1 + 8 + (1 + 32) + 8 + 1 + 1;
+    
+    // Decodes `data` (without the discriminator) in layout `layout`. Fields the layout
+    // doesn't have take the values a fresh stake would have had, as in
+    // StakingPositionV0::upgrade; `version` is left as stored.
+    pub fn read_layout(layout: u8, mut data: &[u8]) -> Result<StakingPosition> {
+        let data = &mut data;
+        let mut position = StakingPosition {
+            owner: Pubkey::deserialize(data)?,
+            nft_mint: Pubkey::deserialize(data)?,
+            collection_mint: Pubkey::deserialize(data)?,
+            staked_at: i64::deserialize(data)?,
+            unlock_at: i64::deserialize(data)?,
+            duration: u8::deserialize(data)?,
+            is_active: bool::deserialize(data)?,
+            total_rewards_earned: u64::deserialize(data)?,
+            rewards_claimed: u64::deserialize(data)?,
+            reward_tier: u8::deserialize(data)?,
+            locked_multiplier: u64::deserialize(data)?,
+            claim_delegate: Option::<Pubkey>::deserialize(data)?,
+            rewards_staked: u64::deserialize(data)?,
+            version: u8::deserialize(data)?,
+            accrual_paused: false,
+            accrual_paused_at: 0,
+            accrual_paused_total: 0,
+            accepted_terms_hash: [0u8; 32],
+            tickets_minted: 0,
+            last_claim_at: 0,
+            swept: false,
+            carried_rewards: 0,
+            last_snapshot_id: 0,
+            bump: 0,
+        };
+        if layout >= 2 {
+            position.accrual_paused = bool::deserialize(data)?;
+            position.accrual_paused_at = i64::deserialize(data)?;
+            position.accrual_paused_total = i64::deserialize(data)?;
+        }
+        if layout >= 3 {
+            position.accepted_terms_hash = <[u8; 32]>::deserialize(data)?;
+        }
+        // Settlements before claim_tickets existed were credited off-chain
+        position.tickets_minted = if layout >= 4 {
+            u64::deserialize(data)?
+        } else {
+            position.total_rewards_earned
+        };
+        if layout >= 5 {
+            position.last_claim_at = i64::deserialize(data)?;
+        }
+        if layout >= 6 {
+            position.swept = bool::deserialize(data)?;
+        }
+        if layout >= 7 {
+            position.carried_rewards = u64::deserialize(data)?;
+        }
+        if layout >= 8 {
+            position.last_snapshot_id = u64::deserialize(data)?;
+        }
+        position.bump = u8::deserialize(data)?;
+        Ok(position)
+    }
+
+    pub fn can_be_claimed_by(&self, signer: &Pubkey) -> bool {
+        self.owner == *signer || self.claim_delegate == Some(*signer)
+    }
+
+    // Hands the position to `new_owner` and clears any claim delegate
+    pub fn transfer_to(&mut self, new_owner: Pubkey) -> Result<()> {
+        require!(self.is_active, StakingError::PositionNotActive);
+        // A reward stake is tied to the position's owner at the time it was made
+        require!(self.rewards_staked == 0, StakingError::PositionNotTransferable);
+        self.owner = new_owner;
+        self.claim_delegate = None;
+        Ok(())
+    }
+
+    // Settled tickets neither locked in a reward stake nor already minted
+    pub fn mintable_tickets(&self) -> u64 {
+        self.total_rewards_earned
+            .saturating_sub(self.rewards_staked)
+            .saturating_sub(self.tickets_minted)
+    }
+
+    // Moves the settlement watermark up to `accrued`. Accrual can dip below the watermark
+    // (a bonus lapsing, a longer term); the watermark never does, so tickets already
+    // settled are never settled again.
+    pub fn mark_settled(&mut self, accrued: u64) {
+        self.rewards_claimed = self.rewards_claimed.max(accrued);
+    }
+
+    // Moves the position to the longer `new_duration`, keeping `staked_at`
+    pub fn extend(&mut self, new_duration: u8, tier_multiplier: u64) -> Result<()> {
+        require!(new_duration > self.duration, StakingError::InvalidDuration);
+        self.unlock_at = self.staked_at + staking_duration_seconds(new_duration)?;
+        self.duration = new_duration;
+        self.reward_tier = self.reward_tier.max(new_duration);
+        self.locked_multiplier = self.locked_multiplier.max(tier_multiplier);
+        Ok(())
+    }
+
+    // Fills in a freshly created position. The multiplier is locked here and governs
+    // the position's payout from then on.
+    pub fn open(
+        &mut self,
+        owner: Pubkey,
+        nft_mint: Pubkey,
+        collection: &CollectionAccount,
+        duration: u8,
+        staked_at: i64,
+        bump: u8,
+    ) -> Result<()> {
+        self.owner = owner;
+        self.nft_mint = nft_mint;
+        self.collection_mint = collection.collection_mint;
+        self.staked_at = staked_at;
+        self.unlock_at = staked_at + staking_duration_seconds(duration)?;
+        self.duration = duration;
+        self.reward_tier = duration;
+        self.locked_multiplier = collection.multiplier_for(duration)?;
+        self.claim_delegate = None;
+        self.is_active = true;
+        self.total_rewards_earned = 0;
+        self.rewards_claimed = 0;
+        self.rewards_staked = 0;
+        self.tickets_minted = 0;
+        self.last_claim_at = 0;
+        self.swept = false;
+        self.carried_rewards = 0;
+        self.last_snapshot_id = 0;
+        self.version = Self::CURRENT_VERSION;
+        self.accrual_paused = false;
+        self.accrual_paused_at = 0;
+        self.accrual_paused_total = 0;
+        self.accepted_terms_hash = [0u8; 32];
+        self.bump = bump;
+        Ok(())
+    }
+
+    // The position as re-created under `nft_mint` by a swap: same owner, term and reward
+    // state, at the new mint's address
+    pub fn swapped(&self, nft_mint: Pubkey, bump: u8) -> StakingPosition {
+        StakingPosition {
+            nft_mint,
+            version: Self::CURRENT_VERSION,
+            bump,
+            ..self.clone()
+        }
+    }
+
+    // Seconds of [accrual_start, min(current_time, unlock_at)] that count toward rewards.
+    // Nothing accrues past unlock, so only paused time inside the term is subtracted.
+    pub fn accrual_elapsed(&self, accrual_start: i64, current_time: i64) -> i64 {
+        let accrual_end = current_time.min(self.unlock_at);
+        let mut paused = self.accrual_paused_total;
+        if self.accrual_paused {
+            paused += self.paused_in_term(self.accrual_paused_at, current_time);
+        }
+        (elapsed_since(accrual_start, accrual_end) - paused).max(0)
+    }
+
+    // Ends an accrual pause, adding the part of it inside the term to the paused total.
+    // Returns the full length of the pause.
+    pub fn resume_accrual(&mut self, current_time: i64) -> i64 {
+        let paused_for = elapsed_since(self.accrual_paused_at, current_time);
+        self.accrual_paused_total += self.paused_in_term(self.accrual_paused_at, current_time);
+        self.accrual_paused = false;
+        self.accrual_paused_at = 0;
+        paused_for
+    }
+
+    // Length of the part of [from, to] that falls inside the term
+    pub fn paused_in_term(&self, from: i64, to: i64) -> i64 {
+        elapsed_since(from.max(self.staked_at), to.min(self.unlock_at))
+    }
+}
+
+#[account]
+pub struct EmergencyRequest {
+    pub requester: Pubkey,
+    pub requested_at: i64,
+    pub reason: String,
+    pub executed: bool,
+    pub bump: u8,
+}
+
+#[account]
+pub struct VerifiedNft {
+    pub nft_mint: Pubkey,
+    pub collection_mint: Pubkey,
+    pub verified_at: i64,
+    pub bump: u8,
+}
+
+// Pending (unexecuted) emergency requests, so monitors can poll a single account.
+// The registry holds at most MAX_ACTIVE_EMERGENCY_REQUESTS entries; once full, new
+// requests are refused until a pending one is executed.
+#[account]
+pub struct ActiveEmergencyRequests {
+    pub requests: Vec<Pubkey>,
+    pub bump: u8,
+}
+
+impl ActiveEmergencyRequests {
+    pub const LEN: usize = 8 + 4 + 32 * naffles_staking::MAX_ACTIVE_EMERGENCY_REQUESTS + 1;
+
+    pub fn add(&mut self, request: Pubkey) -> Result<()> {
+        if self.requests.contains(&request) {
+            return Ok(());
+        }
+        require!(
+            self.requests.len() < naffles_staking::MAX_ACTIVE_EMERGENCY_REQUESTS,
+            StakingError::EmergencyRegistryFull
+        );
+        self.requests.push(request);
+        Ok(())
+    }
+
+    pub fn remove(&mut self, request: &Pubkey) {
+        self.requests.retain(|key| key != request);
+    }
+}
+
+// Earned rewards locked for compounding; one open reward stake per staking position
+#[account]
+pub struct RewardStakePosition {
+    pub owner: Pubkey,
+    pub staking_position: Pubkey,
+    pub amount: u64,
+    pub staked_at: i64,
+    pub unlock_at: i64,
+    pub duration: u8,
+    pub locked_multiplier: u64,
+    pub bump: u8,
+}
+
+impl RewardStakePosition {
+    pub const LEN: usize = 8 + 32 + 32 + 8 + 8 + 8 + 1 + 8 + 1;
+}
+
+#[account]
+pub struct RewardSnapshot {
+    pub snapshot_id: u64,
+    pub user: Pubkey,
+    pub total_rewards_earned: u64,
+    pub positions_counted: u32,
+    pub taken_at: i64,
+    pub bump: u8,
+}
+
+// Layout of positions written before versioning (version 0). Kept only so
+// migrate_position can read them.
+#[derive(AnchorSerialize, AnchorDeserialize)]
+pub struct StakingPositionV0 {
+    pub owner: Pubkey,
+    pub nft_mint: Pubkey,
+    pub collection_mint: Pubkey,
+    pub staked_at: i64,
+    pub unlock_at: i64,
+    pub duration: u8,
+    pub is_active: bool,
+    pub total_rewards_earned: u64,
+    pub bump: u8,
+}
+
+impl StakingPositionV0 {
+    pub const LEN: usize = 8 + 32 + 32 + 32 + 8 + 8 + 1 + 1 + 8 + 1;
+    
+    // Fields added after version 0 take the values a fresh stake would have had
+    pub fn upgrade(self, collection: &CollectionAccount) -> Result<StakingPosition> {
+        Ok(StakingPosition {
+            owner: self.owner,
+            nft_mint: self.nft_mint,
+            collection_mint: self.collection_mint,
+            staked_at: self.staked_at,
+            unlock_at: self.unlock_at,
+            duration: self.duration,
+            is_active: self.is_active,
+            total_rewards_earned: self.total_rewards_earned,
+            rewards_claimed: self.total_rewards_earned,
+            reward_tier: self.duration,
+            locked_multiplier: collection.multiplier_for(self.duration)?,
+            claim_delegate: None,
+            rewards_staked: 0,
+            version: StakingPosition::CURRENT_VERSION,
+            accrual_paused: false,
+            accrual_paused_at: 0,
+            accrual_paused_total: 0,
+            accepted_terms_hash: [0u8; 32],
+            // Earlier settlements were credited off-chain
+            tickets_minted: self.total_rewards_earned,
+            last_claim_at: 0,
+            swept: false,
+            carried_rewards: 0,
+            last_snapshot_id: 0,
+            bump: self.bump,
+        })
+    }
+}
+
+// Exists only while staking is blocked for `nft_mint`
+#[account]
+pub struct BlockedMint {
+    pub nft_mint: Pubkey,
+    pub blocked_by: Pubkey,
+    pub blocked_at: i64,
+    pub bump: u8,
+}
+
+// External address on `chain_id` that `staker`'s rewards are attributed to. Read by
+// off-chain bridges; the program itself never pays out to it.
+#[account]
+pub struct RewardAddress {
+    pub staker: Pubkey,
+    pub chain_id: u64,
+    pub address: Vec<u8>,
+    pub updated_at: i64,
+    pub bump: u8,
+}
+
+impl RewardAddress {
+    pub const LEN: usize = 8 + 32 + 8 + 4 + naffles_staking::MAX_EXTERNAL_ADDRESS_LEN + 8 + 1;
+}
+
+// Admins who approved lifting the pause that started at `paused_at`
+#[account]
+pub struct UnpauseApproval {
+    pub paused_at: i64,
+    pub approvers: Vec<Pubkey>,
+    pub bump: u8,
+}
+
+impl UnpauseApproval {
+    pub const LEN: usize = 8 + 8 + 4 + 32 * naffles_staking::MAX_UNPAUSE_APPROVERS + 1;
+
+    pub fn approve(&mut self, admin: Pubkey) -> Result<()> {
+        require!(!self.approvers.contains(&admin), StakingError::AlreadyApproved);
+        require!(
+            self.approvers.len() < naffles_staking::MAX_UNPAUSE_APPROVERS,
+            StakingError::InvalidThreshold
+        );
+        self.approvers.push(admin);
+        Ok(())
+    }
+}
+
+// Return payload of get_position; field order is the wire format
+#[derive(AnchorSerialize, AnchorDeserialize, Clone)]
+pub struct PositionView {
+    pub position: Pubkey,
+    pub owner: Pubkey,
+    pub nft_mint: Pubkey,
+    pub collection_mint: Pubkey,
+    pub staked_at: i64,
+    pub unlock_at: i64,
+    pub duration: u8,
+    pub reward_tier: u8,
+    pub is_active: bool,
+    pub total_rewards_earned: u64,
+    pub locked_multiplier: u64,
+    pub version: u8,
+}
+
+// Return data of get_pending_rewards. `accrued` is the settled-plus-unsettled total so far,
+// `pending` the part not yet settled by claim_rewards, `full_term` the total at unlock.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone)]
+pub struct PendingRewardsView {
+    pub accrued: u64,
+    pub pending: u64,
+    pub full_term: u64,
+    pub is_full_term: bool,
+}
+
+// Per-position entry of the BatchPositionStatus event; `accrued_rewards` is the unclaimed part
+#[derive(AnchorSerialize, AnchorDeserialize, Clone)]
+pub struct PositionStatus {
+    pub nft_mint: Pubkey,
+    pub is_unlockable: bool,
+    pub seconds_remaining: u64,
+    pub accrued_rewards: u64,
+}
+
+// Admin actions that need `multi_sig_threshold` confirmations when the threshold is above one.
+// Tickets are ordered 6 months, 12 months, 3 years.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, PartialEq, Eq)]
+pub enum MultiSigAction {
+    AddCollection { collection_mint: Pubkey, tickets: [u64; 3] },
+    UpdateCollectionRewards { collection_mint: Pubkey, tickets: [u64; 3] },
+    AdminUnlock { staking_position: Pubkey },
+    // Positions are bound by positions_hash over the keys passed in remaining_accounts
+    GrantBonusRewards { amount: u64, positions_hash: [u8; 32], reason: String },
+}
+
+impl MultiSigAction {
+    // Largest variant: GrantBonusRewards with a reason of MAX_GRANT_REASON_LEN bytes
+    pub const MAX_LEN: usize = 1 + 8 + 32 + 4 + naffles_staking::MAX_GRANT_REASON_LEN;
+    
+    // sha256(nonce_le || borsh(action)); also the proposal PDA seed
+    pub fn hash(&self, nonce: u64) -> Result<[u8; 32]> {
+        let mut data = nonce.to_le_bytes().to_vec();
+        data.extend(self.try_to_vec()?);
+        Ok(hash(&data).to_bytes())
+    }
+}
+
+#[account]
+pub struct ProposalAccount {
+    pub action_hash: [u8; 32],
+    pub nonce: u64,
+    pub action: MultiSigAction,
+    pub proposer: Pubkey,
+    pub confirmations: Vec<Pubkey>,
+    pub executed: bool,
+    pub created_at: i64,
+    pub bump: u8,
+}
+
+impl ProposalAccount {
+    pub const LEN: usize = 8 + 32 + 8 + MultiSigAction::MAX_LEN + 32
+        + 4 + 32 * naffles_staking::MAX_PROPOSAL_CONFIRMATIONS + 1 + 8 + 1;
+
+    pub fn confirm(&mut self, admin: Pubkey) -> Result<()> {
+        require!(!self.confirmations.contains(&admin), StakingError::AlreadyApproved);
+        require!(
+            self.confirmations.len() < naffles_staking::MAX_PROPOSAL_CONFIRMATIONS,
+            StakingError::InvalidThreshold
+        );
+        self.confirmations.push(admin);
+        Ok(())
+    }
+}
+
+// Open positions a wallet holds in one collection, for CollectionAccount::max_per_user.
+// PDA seeds: [b"user_stake_count", collection_mint, user]. Every path that takes a
+// position out of the staked counters (claim, early unstake, admin unlocks, sweep)
+// releases it here too, under the position's owner.
+#[account]
+pub struct UserStakeCount {
+    pub collection_mint: Pubkey,
+    pub user: Pubkey,
+    pub count: u64,
+    pub bump: u8,
+}
+
+impl UserStakeCount {
+    pub const LEN: usize = 8 + 32 + 32 + 8 + 1;
+    
+    // Counts a new position, filling in the PDA the first time it is used
+    pub fn record_stake(&mut self, collection: &CollectionAccount, user: Pubkey, bump: u8) -> Result<()> {
+        require!(
+            collection.max_per_user == 0 || self.count < collection.max_per_user,
+            StakingError::UserStakeLimitReached
+        );
+        self.collection_mint = collection.collection_mint;
+        self.user = user;
+        self.bump = bump;
+        self.count = self.count.checked_add(1).ok_or(StakingError::ArithmeticOverflow)?;
+        Ok(())
+    }
+
+    // Positions opened before per-user limits existed were never counted, and their
+    // owner's counter starts at zero when first created on the way out
+    pub fn record_unstake(&mut self) -> Result<()> {
+        self.count = self.count.saturating_sub(1);
+        Ok(())
+    }
+
+    // Takes `count` transferred positions in, under the same limit as a new stake
+    pub fn record_transfer_in(&mut self, collection: &CollectionAccount, user: Pubkey, bump: u8, count: u64) -> Result<()> {
+        let total = self.count.checked_add(count).ok_or(StakingError::ArithmeticOverflow)?;
+        require!(
+            collection.max_per_user == 0 || total <= collection.max_per_user,
+            StakingError::UserStakeLimitReached
+        );
+        self.collection_mint = collection.collection_mint;
+        self.user = user;
+        self.bump = bump;
+        self.count = total;
+        Ok(())
+    }
+
+    pub fn record_transfer_out(&mut self, count: u64) {
+        self.count = self.count.saturating_sub(count);
+    }
+}
+
+// Per-wallet staking totals across all collections, read by off-chain leaderboards in
+// place of scanning every position. PDA seeds: [b"user_stats", user].
+// Kept under a position's current owner by every path that opens, closes, sweeps,
+// transfers or settles tickets to it; created by the first of those to need it, so
+// positions opened before it existed are not counted.
+#[account]
+pub struct UserStats {
+    pub user: Pubkey,
+    // Positions ever opened by the wallet
+    pub lifetime_staked: u64,
+    // Positions the wallet owns that still hold their NFT and count as staked (not swept)
+    pub currently_staked: u64,
+    // Tickets settled to positions while the wallet owned them
+    pub lifetime_rewards: u64,
+    pub bump: u8,
+}
+
+impl UserStats {
+    pub const LEN: usize = 8 + 32 + 8 + 8 + 8 + 1;
+    
+    // Counts a new position, filling in the PDA the first time it is used
+    pub fn record_stake(&mut self, user: Pubkey, bump: u8) -> Result<()> {
+        self.user = user;
+        self.bump = bump;
+        self.lifetime_staked = self.lifetime_staked.checked_add(1).ok_or(StakingError::ArithmeticOverflow)?;
+        self.currently_staked = self.currently_staked.checked_add(1).ok_or(StakingError::ArithmeticOverflow)?;
+        Ok(())
+    }
+
+    // Fills in the PDA on an account created outside stake_nft
+    pub fn fill(&mut self, user: Pubkey, bump: u8) {
+        self.user = user;
+        self.bump = bump;
+    }
+
+    // Positions opened before the wallet's UserStats existed were never counted
+    pub fn record_unstake(&mut self) -> Result<()> {
+        self.currently_staked = self.currently_staked.saturating_sub(1);
+        Ok(())
+    }
+
+    pub fn record_rewards(&mut self, amount: u64) -> Result<()> {
+        self.lifetime_rewards = self.lifetime_rewards.checked_add(amount).ok_or(StakingError::ArithmeticOverflow)?;
+        Ok(())
+    }
+
+    // Positions joining or leaving `currently_staked` without being opened or closed: a
+    // transfer, or a swept position relocked. `lifetime_staked` and `lifetime_rewards`
+    // stay with the wallet that opened the position and was settled its rewards.
+    pub fn record_moved_in(&mut self, user: Pubkey, bump: u8, count: u64) -> Result<()> {
+        self.fill(user, bump);
+        self.currently_staked = self.currently_staked.checked_add(count).ok_or(StakingError::ArithmeticOverflow)?;
+        Ok(())
+    }
+
+    pub fn record_moved_out(&mut self, count: u64) {
+        self.currently_staked = self.currently_staked.saturating_sub(count);
+    }
+}
+
+// Permanent record of one claim_rewards, written once and never modified. Opt-in per
+// claim because the claimer pays its rent (about 0.0018 SOL for LEN bytes).
+// PDA seeds: [b"reward_receipt", staking_position, staked_at (le), previous watermark (le)],
+// where the watermark is the position's `rewards_claimed` before the claim. claim_rewards
+// never lowers the watermark, and relock, which resets it, also moves `staked_at` (as does
+// reopening a position at the same address), so each claim that advances the watermark
+// gets its own receipt. A claim that settles nothing can't take a receipt.
+#[account]
+pub struct RewardReceipt {
+    pub staking_position: Pubkey,
+    pub owner: Pubkey,
+    pub nft_mint: Pubkey,
+    pub amount: u64,
+    // Treasury match credited on top of `amount`
+    pub matched: u64,
+    pub settlement_multiplier: u64,
+    pub claimed_at: i64,
+    pub bump: u8,
+}
+
+impl RewardReceipt {
+    pub const LEN: usize = 8 + 32 + 32 + 32 + 8 + 8 + 8 + 8 + 1;
+}
+
+// Helpers
+pub fn staking_duration_seconds(duration: u8) -> Result<i64> {
+    match duration {
+        0 => Ok(naffles_staking::SIX_MONTHS),
+        1 => Ok(naffles_staking::TWELVE_MONTHS),
+        2 => Ok(naffles_staking::THREE_YEARS),
+        _ => Err(StakingError::InvalidDuration.into()),
+    }
+}
+
+// Cluster time, rejecting values that can't be a real clock (zero or negative)
+pub fn current_timestamp() -> Result<i64> {
+    let now = Clock::get()?.unix_timestamp;
+    require!(now > 0, StakingError::InvalidClock);
+    Ok(now)
+}
+
+// Seconds since `start`, or zero if the clock reads at or before it
+pub fn elapsed_since(start: i64, current_time: i64) -> i64 {
+    if current_time <= start {
+        0
+    } else {
+        current_time - start
+    }
+}
+
+// Tickets accrued by a position at `current_time`: the reward tier's ticket amount scaled
+// by the position's locked multiplier (basis points), vested over the term along the
+// collection's distribution curve. With f = elapsed / term:
+//   linear:      full * f
+//   cliff:       0 until f = 1, then full
+//   back-loaded: full * f^2
+// plus any loyalty rewards for time held past unlock.
+pub fn calculate_rewards(
+    position: &StakingPosition,
+    collection: &CollectionAccount,
+    current_time: i64,
+) -> Result<u64> {
+    // Without the honor policy, a deactivated collection stops accruing at deactivation
+    let current_time = if collection.deactivated_at > 0 && !collection.honor_existing_on_deactivate {
+        current_time.min(collection.deactivated_at)
+    } else {
+        current_time
+    };
+    // A frozen collection accrues nothing past the freeze
+    let current_time = if collection.rewards_frozen {
+        current_time.min(collection.rewards_frozen_at)
+    } else {
+        current_time
+    };
+    
+    let favor_user = collection.favor_user;
+    let tickets = collection.tickets_for(position.reward_tier)? as u128;
+    let multiplier = collection.settlement_multiplier(position, current_time)? as u128;
+    let full_term_rewards = div_round(
+        tickets.checked_mul(multiplier).ok_or(StakingError::ArithmeticOverflow)?,
+        naffles_staking::BASIS_POINTS as u128,
+        favor_user,
+    );
+    
+    let term = position.unlock_at.saturating_sub(position.staked_at);
+    if term <= 0 {
+        return u64::try_from(full_term_rewards).map_err(|_| StakingError::ArithmeticOverflow.into());
+    }
+    if !vesting_cliff_passed(position, collection, current_time) {
+        return Ok(0);
+    }
+    let accrual_start = collection.accrual_start(position, current_time);
+    let mut elapsed = position.accrual_elapsed(accrual_start, current_time);
+    // Earlier deactivations don't accrue either
+    if !collection.honor_existing_on_deactivate {
+        let inactive = collection.inactive_between(accrual_start, current_time.min(position.unlock_at));
+        elapsed = (elapsed - inactive).max(0);
+    }
+    let elapsed = elapsed.min(term) as u128;
+    let term = term as u128;
+    
+    let accrued = match collection.distribution_curve {
+        naffles_staking::CURVE_LINEAR => div_round(
+            full_term_rewards.checked_mul(elapsed).ok_or(StakingError::ArithmeticOverflow)?,
+            term,
+            favor_user,
+        ),
+        naffles_staking::CURVE_CLIFF => {
+            if elapsed >= term {
+                full_term_rewards
+            } else {
+                0
+            }
+        }
+        naffles_staking::CURVE_BACK_LOADED => div_round(
+            full_term_rewards.checked_mul(elapsed * elapsed).ok_or(StakingError::ArithmeticOverflow)?,
+            term * term,
+            favor_user,
+        ),
+        _ => return err!(StakingError::InvalidDistributionCurve),
+    };
+    let accrued = accrued
+        .checked_add(collection.loyalty_rewards(position, full_term_rewards, current_time)?)
+        .ok_or(StakingError::ArithmeticOverflow)?;
+    
+    u64::try_from(accrued).map_err(|_| StakingError::ArithmeticOverflow.into())
+}
+
+// What a settlement at `current_time` would add before any claim penalty, pool cap or
+// match: the accrual above the position's watermark, rescaled to the dynamic multiplier
+// in force now. Returns (accrued, unsettled); `accrued` is what the watermark moves to.
+pub fn unsettled_rewards(
+    position: &StakingPosition,
+    collection: &CollectionAccount,
+    current_time: i64,
+) -> Result<(u64, u64)> {
+    let accrued = calculate_rewards(position, collection, current_time)?;
+    let new_accrual = accrued.saturating_sub(position.rewards_claimed);
+    let multiplier = collection.settlement_multiplier(position, current_time)?;
+    Ok((accrued, collection.scale_new_rewards(new_accrual, multiplier)))
+}
+
+// get_pending_rewards' view. `pending` is what a claim_rewards settlement at `current_time`
+// would add (unsettled_rewards), before claim_rewards' gates (activation, cooldown, vesting
+// cliff), early-claim penalty, pool cap and match, so a claim can pay less or be refused.
+// Accrual stops at the full-term value once the position unlocks.
+pub fn pending_rewards_view(
+    position: &StakingPosition,
+    collection: &CollectionAccount,
+    current_time: i64,
+) -> Result<PendingRewardsView> {
+    if !position.is_active {
+        return Ok(PendingRewardsView {
+            accrued: position.rewards_claimed,
+            pending: 0,
+            full_term: position.rewards_claimed,
+            is_full_term: true,
+        });
+    }
+    
+    let full_term_time = current_time.max(position.unlock_at);
+    let full_term = calculate_rewards(position, collection, full_term_time)?;
+    let (accrued, pending) = unsettled_rewards(position, collection, current_time)?;
+    Ok(PendingRewardsView {
+        accrued: accrued.min(full_term),
+        pending,
+        full_term,
+        is_full_term: current_time >= position.unlock_at,
+    })
+}
+
+// numerator / denominator, rounded up when the collection favors the user and down
+// (in the protocol's favor) otherwise. `denominator` must be non-zero.
+pub fn div_round(numerator: u128, denominator: u128, favor_user: bool) -> u128 {
+    let quotient = numerator / denominator;
+    if favor_user && !numerator.is_multiple_of(denominator) {
+        quotient + 1
+    } else {
+        quotient
+    }
+}
+
+// Raffle entry weight of a position, shared with the raffle program through
+// compute_entry_weight:
+//   base   = tickets(reward_tier) * settlement_multiplier / 10_000
+//   weight = base * (10_000 + progress_bps) / 10_000
+// where progress_bps is the share of the term already served (accrual pauses excluded),
+// so weight grows from base at stake time to 2x base at unlock. Inactive positions weigh 0.
+pub fn entry_weight(
+    position: &StakingPosition,
+    collection: &CollectionAccount,
+    current_time: i64,
+) -> Result<u64> {
+    if !position.is_active {
+        return Ok(0);
+    }
+    let bps = naffles_staking::BASIS_POINTS as u128;
+    let tickets = collection.tickets_for(position.reward_tier)? as u128;
+    let multiplier = collection.apply_dynamic_multiplier(collection.settlement_multiplier(position, current_time)?) as u128;
+    let base = tickets.checked_mul(multiplier).ok_or(StakingError::ArithmeticOverflow)? / bps;
+    
+    let term = position.unlock_at.saturating_sub(position.staked_at);
+    let progress_bps = if term <= 0 {
+        bps
+    } else {
+        let elapsed = position.accrual_elapsed(position.staked_at, current_time).min(term) as u128;
+        elapsed * bps / term as u128
+    };
+    
+    let weight = base.checked_mul(bps + progress_bps).ok_or(StakingError::ArithmeticOverflow)? / bps;
+    u64::try_from(weight).map_err(|_| StakingError::ArithmeticOverflow.into())
+}
+
+pub fn vesting_cliff_passed(
+    position: &StakingPosition,
+    collection: &CollectionAccount,
+    current_time: i64,
+) -> bool {
+    let term = position.unlock_at.saturating_sub(position.staked_at).max(0) as i128;
+    let cliff = term * collection.vesting_cliff_bps as i128 / naffles_staking::BASIS_POINTS as i128;
+    let accrual_start = collection.accrual_start(position, current_time);
+    let elapsed = position.accrual_elapsed(accrual_start, current_time) as i128;
+    elapsed >= cliff
+}
+
+// Confirms `metadata_info` is the Metaplex metadata PDA for `nft_mint` and that it
+// carries a verified collection equal to `collection_mint`.
+pub fn verify_collection_membership(
+    metadata_info: &AccountInfo,
+    nft_mint: &Pubkey,
+    collection_mint: &Pubkey,
+) -> Result<()> {
+    require!(*metadata_info.owner == mpl_token_metadata::ID, StakingError::InvalidMetadata);
+    let (expected_metadata, _) = Pubkey::find_program_address(
+        &[b"metadata", mpl_token_metadata::ID.as_ref(), nft_mint.as_ref()],
+        &mpl_token_metadata::ID,
+    );
+    require!(metadata_info.key() == expected_metadata, StakingError::InvalidMetadata);
+    
+    let metadata = Metadata::from_account_info(metadata_info)?;
+    require!(metadata.mint == *nft_mint, StakingError::InvalidMetadata);
+    
+    match metadata.collection {
+        Some(collection) if collection.verified && collection.key == *collection_mint => Ok(()),
+        _ => Err(StakingError::NftNotInCollection.into()),
+    }
+}
+
+// Deserializes a StakingPosition passed through `remaining_accounts`
+pub fn read_position(account_info: &AccountInfo) -> Result<StakingPosition> {
+    require!(*account_info.owner == crate::ID, StakingError::InvalidPosition);
+    let data = account_info.try_borrow_data()?;
+    StakingPosition::try_deserialize(&mut &data[..])
+}
+
+pub fn write_position(account_info: &AccountInfo, position: &StakingPosition) -> Result<()> {
+    let mut data = account_info.try_borrow_mut_data()?;
+    position.try_serialize(&mut &mut data[..])
+}
+
+// Releases `position` from its owner's UserStakeCount passed as a raw account (sweep and
+// the multi-sig unlock, where it can't be a typed context account). An owner without a
+// counter yet has nothing counted to release.
+pub fn release_stake_count(account_info: &AccountInfo, position: &StakingPosition) -> Result<()> {
+    let (expected, _) = Pubkey::find_program_address(
+        &[b"user_stake_count", position.collection_mint.as_ref(), position.owner.as_ref()],
+        &crate::ID,
+    );
+    require!(*account_info.key == expected, StakingError::InvalidUserCounter);
+    if account_info.data_is_empty() {
+        return Ok(());
+    }
+    
+    let mut user_stake_count = Account::<UserStakeCount>::try_from(account_info)?;
+    user_stake_count.record_unstake()?;
+    user_stake_count.exit(&crate::ID)
+}
+
+// Applies `update` to `owner`'s UserStats passed as a raw account (remaining_accounts,
+// or the multi-sig context), as release_stake_count does for UserStakeCount. A wallet
+// without UserStats yet is skipped.
+pub fn update_user_stats(
+    account_info: &AccountInfo,
+    owner: &Pubkey,
+    update: impl FnOnce(&mut UserStats) -> Result<()>,
+) -> Result<()> {
+    let (expected, _) = Pubkey::find_program_address(&[b"user_stats", owner.as_ref()], &crate::ID);
+    require!(*account_info.key == expected, StakingError::InvalidUserCounter);
+    if account_info.data_is_empty() {
+        return Ok(());
+    }
+    
+    let mut user_stats = Account::<UserStats>::try_from(account_info)?;
+    update(&mut user_stats)?;
+    user_stats.exit(&crate::ID)
+}
+
+// Preconditions shared by every path that opens a position from a user stake
+// Each failed check emits StakeRejected before returning its error, so drop-off reasons
+// show up in the logs of the failed transaction.
+pub fn check_stake_allowed(
+    user: Pubkey,
+    staking_program: &StakingProgram,
+    collection_account: &CollectionAccount,
+    verified_nft: Option<&VerifiedNft>,
+    duration: u8,
+    current_time: i64,
+    terms_hash: &[u8; 32],
+) -> Result<()> {
+    let collection_mint = collection_account.collection_mint;
+    
+    if staking_program.is_instruction_paused(naffles_staking::PAUSE_STAKE, current_time) {
+        return reject_stake(user, collection_mint, naffles_staking::REJECT_PAUSED, StakingError::ContractPaused);
+    }
+    if current_time < staking_program.staking_starts_at {
+        return reject_stake(user, collection_mint, naffles_staking::REJECT_NOT_STARTED, StakingError::StakingNotStarted);
+    }
+    // An all-zero current hash means no terms gating
+    if staking_program.current_terms_hash != [0u8; 32] && *terms_hash != staking_program.current_terms_hash {
+        return reject_stake(user, collection_mint, naffles_staking::REJECT_TERMS_NOT_ACCEPTED, StakingError::TermsNotAccepted);
+    }
+    // A lapsed validation counts until expire_collection_validation clears it, except on
+    // the collection being staked into
+    let lapsed = u64::from(collection_account.is_validated && !collection_account.is_validated_at(current_time));
+    if staking_program.validated_collections.saturating_sub(lapsed) < staking_program.min_validated_collections {
+        return reject_stake(user, collection_mint, naffles_staking::REJECT_PROGRAM_NOT_READY, StakingError::ProgramNotReady);
+    }
+    if duration > 2 {
+        return reject_stake(user, collection_mint, naffles_staking::REJECT_INVALID_DURATION, StakingError::InvalidDuration);
+    }
+    if !collection_account.is_active {
+        return reject_stake(user, collection_mint, naffles_staking::REJECT_COLLECTION_INACTIVE, StakingError::CollectionNotActive);
+    }
+    if staking_program.require_validation && !collection_account.is_validated_at(current_time) {
+        return reject_stake(user, collection_mint, naffles_staking::REJECT_COLLECTION_NOT_VALIDATED, StakingError::CollectionNotValidated);
+    }
+    if collection_account.is_stake_cap_reached() {
+        return reject_stake(user, collection_mint, naffles_staking::REJECT_STAKE_CAP_REACHED, StakingError::CollectionStakeCapReached);
+    }
+
+    // A cached membership proof, when supplied, must point at this collection
+    if let Some(verified_nft) = verified_nft {
+        if verified_nft.collection_mint != collection_mint {
+            return reject_stake(user, collection_mint, naffles_staking::REJECT_NOT_IN_COLLECTION, StakingError::NftNotInCollection);
+        }
+    }
+
+    let unlock_at = current_time + staking_duration_seconds(duration)?;
+    if staking_program.check_lock_duration(unlock_at, current_time).is_err() {
+        return reject_stake(user, collection_mint, naffles_staking::REJECT_LOCK_TOO_LONG, StakingError::LockTooLong);
+    }
+
+    Ok(())
+}
+
+// A stake must prove collection membership: either the cached VerifiedNft (its collection
+// is checked in check_stake_allowed) or the NFT's Metaplex metadata
+pub fn check_nft_membership(
+    user: Pubkey,
+    nft_mint: &Pubkey,
+    collection_mint: &Pubkey,
+    verified_nft: Option<&VerifiedNft>,
+    nft_metadata: Option<&AccountInfo>,
+) -> Result<()> {
+    if verified_nft.is_some() {
+        return Ok(());
+    }
+    match nft_metadata {
+        Some(metadata_info) => verify_collection_membership(metadata_info, nft_mint, collection_mint).or_else(|_| {
+            reject_stake(user, *collection_mint, naffles_staking::REJECT_NOT_IN_COLLECTION, StakingError::NftNotInCollection)
+        }),
+        None => reject_stake(user, *collection_mint, naffles_staking::REJECT_NOT_IN_COLLECTION, StakingError::NftNotInCollection),
+    }
+}
+
+fn reject_stake(user: Pubkey, collection_mint: Pubkey, reason: u8, error: StakingError) -> Result<()> {
+    emit!(StakeRejected {
+        user,
+        collection_mint,
+        reason,
+    });
+    Err(error.into())
+}
+
+// Checks shared by swap_staked_nft and swap_staked_pnft. The reward stake and emergency
+// request PDAs are derived from the position's address, so a position with either one
+// can't move to a new address.
+#[allow(clippy::too_many_arguments)]
+pub fn check_swap_allowed(
+    user: Pubkey,
+    staking_program: &StakingProgram,
+    collection_account: &CollectionAccount,
+    position: &StakingPosition,
+    new_nft_mint: &Pubkey,
+    verified_nft: Option<&VerifiedNft>,
+    new_nft_metadata: Option<&AccountInfo>,
+    blocked_mint_info: &AccountInfo,
+    emergency_request_info: &AccountInfo,
+) -> Result<()> {
+    let current_time = current_timestamp()?;
+    let collection_mint = collection_account.collection_mint;
+    
+    require!(
+        !staking_program.is_instruction_paused(naffles_staking::PAUSE_STAKE | naffles_staking::PAUSE_CLAIM_NFT, current_time),
+        StakingError::ContractPaused
+    );
+    require!(position.is_active, StakingError::PositionNotActive);
+    require!(*new_nft_mint != position.nft_mint, StakingError::InvalidRecipient);
+    require!(position.rewards_staked == 0, StakingError::RewardsStillStaked);
+    if *emergency_request_info.owner == crate::ID && !emergency_request_info.data_is_empty() {
+        let request = EmergencyRequest::try_deserialize(&mut &emergency_request_info.data.borrow()[..])?;
+        require!(request.executed, StakingError::EmergencyRequestPending);
+    }
+    
+    if let Some(verified_nft) = verified_nft {
+        require!(verified_nft.collection_mint == collection_mint, StakingError::NftNotInCollection);
+    }
+    check_nft_membership(user, new_nft_mint, &collection_mint, verified_nft, new_nft_metadata)?;
+    check_mint_not_blocked(user, collection_mint, blocked_mint_info)
+}
+
+pub fn collect_fee<'info>(
+    payer: AccountInfo<'info>,
+    treasury: AccountInfo<'info>,
+    system_program_info: AccountInfo<'info>,
+    lamports: u64,
+) -> Result<()> {
+    if lamports == 0 {
+        return Ok(());
+    }
+    let fee_accounts = system_program::Transfer { from: payer, to: treasury };
+    system_program::transfer(CpiContext::new(system_program_info, fee_accounts), lamports)
+}
+
+pub struct PnftTransferAccounts<'info> {
+    pub token: AccountInfo<'info>,
+    pub token_owner: AccountInfo<'info>,
+    pub destination: AccountInfo<'info>,
+    pub destination_owner: AccountInfo<'info>,
+    pub mint: AccountInfo<'info>,
+    pub metadata: AccountInfo<'info>,
+    pub edition: AccountInfo<'info>,
+    pub owner_token_record: AccountInfo<'info>,
+    pub destination_token_record: AccountInfo<'info>,
+    pub authority: AccountInfo<'info>,
+    pub payer: AccountInfo<'info>,
+    pub system_program: AccountInfo<'info>,
+    pub sysvar_instructions: AccountInfo<'info>,
+    pub spl_token_program: AccountInfo<'info>,
+    pub spl_ata_program: AccountInfo<'info>,
+    pub authorization_rules_program: AccountInfo<'info>,
+    pub authorization_rules: AccountInfo<'info>,
+    pub token_metadata_program: AccountInfo<'info>,
+}
+
+// Moves one programmable NFT through Token Metadata's Transfer instruction. Collections
+// without a rule set pass the Token Metadata program id for the rules accounts.
+pub fn transfer_pnft(accounts: PnftTransferAccounts, signer_seeds: &[&[&[u8]]]) -> Result<()> {
+    let instruction = TransferBuilder::new()
+        .token(accounts.token.key())
+        .token_owner(accounts.token_owner.key())
+        .destination(accounts.destination.key())
+        .destination_owner(accounts.destination_owner.key())
+        .mint(accounts.mint.key())
+        .metadata(accounts.metadata.key())
+        .edition(accounts.edition.key())
+        .owner_token_record(accounts.owner_token_record.key())
+        .destination_token_record(accounts.destination_token_record.key())
+        .authority(accounts.authority.key())
+        .payer(accounts.payer.key())
+        .system_program(accounts.system_program.key())
+        .sysvar_instructions(accounts.sysvar_instructions.key())
+        .spl_token_program(accounts.spl_token_program.key())
+        .spl_ata_program(accounts.spl_ata_program.key())
+        .authorization_rules_program(accounts.authorization_rules_program.key())
+        .authorization_rules(accounts.authorization_rules.key())
+        .build(TransferArgs::V1 {
+            amount: 1,
+            authorization_data: None,
+        })
+        .map_err(|_| StakingError::PnftTransferFailed)?
+        .instruction();
+    
+    invoke_signed(
+        &instruction,
+        &[
+            accounts.token,
+            accounts.token_owner,
+            accounts.destination,
+            accounts.destination_owner,
+            accounts.mint,
+            accounts.metadata,
+            accounts.edition,
+            accounts.owner_token_record,
+            accounts.destination_token_record,
+            accounts.authority,
+            accounts.payer,
+            accounts.system_program,
+            accounts.sysvar_instructions,
+            accounts.spl_token_program,
+            accounts.spl_ata_program,
+            accounts.authorization_rules_program,
+            accounts.authorization_rules,
+            accounts.token_metadata_program,
+        ],
+        signer_seeds,
+    )?;
+    
+    Ok(())
+}
+
+// The instructions sysvar only exposes top-level instructions. If the one currently
+// executing belongs to another program, we were reached through CPI and that program
+// must be whitelisted; direct calls are always allowed.
+pub fn check_cpi_caller(staking_program: &StakingProgram, sysvar_instructions: &AccountInfo) -> Result<()> {
+    let current = sysvar::instructions::get_instruction_relative(0, sysvar_instructions)?;
+    if current.program_id == crate::ID {
+        return Ok(());
+    }
+    require!(
+        staking_program.cpi_whitelist.contains(&current.program_id),
+        StakingError::Unauthorized
+    );
+    Ok(())
+}
+
+// First reason `claimer` can't take the NFT out of `position` right now, if any
+pub fn claim_blocker(
+    staking_program: &StakingProgram,
+    position: &StakingPosition,
+    claimer: &Pubkey,
+    current_time: i64,
+) -> Option<(u8, StakingError)> {
+    if staking_program.is_instruction_paused(naffles_staking::PAUSE_CLAIM_NFT, current_time) {
+        return Some((naffles_staking::CLAIM_BLOCKED_PAUSED, StakingError::ContractPaused));
+    }
+    if !position.is_active {
+        return Some((naffles_staking::CLAIM_BLOCKED_INACTIVE, StakingError::PositionNotActive));
+    }
+    if !position.can_be_claimed_by(claimer) {
+        return Some((naffles_staking::CLAIM_BLOCKED_NOT_OWNER, StakingError::NotPositionOwner));
+    }
+    if current_time < position.unlock_at {
+        return Some((naffles_staking::CLAIM_BLOCKED_LOCKED, StakingError::StakingPeriodNotCompleted));
+    }
+    // Claiming closes the position, which an open reward stake still points at
+    if position.rewards_staked > 0 {
+        return Some((naffles_staking::CLAIM_BLOCKED_REWARDS_STAKED, StakingError::RewardsStillStaked));
+    }
+    // Closing would also destroy settled tickets still waiting to be minted or paid
+    if staking_program.pays_out_on_chain() && position.mintable_tickets() > 0 {
+        return Some((naffles_staking::CLAIM_BLOCKED_TICKETS_UNPAID, StakingError::TicketsNotPaidOut));
+    }
+    None
+}
+
+pub fn check_claim_allowed(
+    staking_program: &StakingProgram,
+    position: &StakingPosition,
+    claimer: &Pubkey,
+    current_time: i64,
+) -> Result<()> {
+    match claim_blocker(staking_program, position, claimer, current_time) {
+        Some((_, error)) => Err(error.into()),
+        None => Ok(()),
+    }
+}
+
+// Rewrites positions recorded under the collection's previous mint to its current one.
+// Positions already on the current mint are skipped.
+pub fn retarget_positions(collection: &CollectionAccount, position_infos: &[AccountInfo]) -> Result<u32> {
+    let mut updated: u32 = 0;
+    for account_info in position_infos.iter() {
+        require!(account_info.is_writable, StakingError::InvalidPosition);
+        let mut position = read_position(account_info)?;
+        if position.collection_mint == collection.collection_mint {
+            continue;
+        }
+        require!(position.collection_mint == collection.previous_collection_mint, StakingError::CollectionNotFound);
+        
+        position.collection_mint = collection.collection_mint;
+        write_position(account_info, &position)?;
+        updated += 1;
+    }
+    Ok(updated)
+}
+
+// Checks that the instruction preceding the current one is an Ed25519 program
+// instruction carrying exactly one signature, by `signer`, over `message`, with all of
+// its data inline.
+pub fn verify_oracle_signature(sysvar_instructions: &AccountInfo, signer: &Pubkey, message: &[u8]) -> Result<()> {
+    let current_index = sysvar::instructions::load_current_index_checked(sysvar_instructions)?;
+    require!(current_index > 0, StakingError::InvalidOracleSignature);
+    let ed25519_ix = sysvar::instructions::load_instruction_at_checked(current_index as usize - 1, sysvar_instructions)?;
+    require!(ed25519_ix.program_id == ed25519_program::ID, StakingError::InvalidOracleSignature);
+    
+    // Layout: [num_signatures, padding] followed by one 14-byte offsets record
+    let data = &ed25519_ix.data;
+    require!(data.len() >= 16 && data[0] == 1, StakingError::InvalidOracleSignature);
+    let read_u16 = |at: usize| u16::from_le_bytes([data[at], data[at + 1]]);
+    let public_key_offset = read_u16(6) as usize;
+    let message_offset = read_u16(10) as usize;
+    let message_size = read_u16(12) as usize;
+    require!(
+        read_u16(4) == u16::MAX && read_u16(8) == u16::MAX && read_u16(14) == u16::MAX,
+        StakingError::InvalidOracleSignature
+    );
+    
+    let public_key = data.get(public_key_offset..public_key_offset + 32).ok_or(StakingError::InvalidOracleSignature)?;
+    let signed_message = data.get(message_offset..message_offset + message_size).ok_or(StakingError::InvalidOracleSignature)?;
+    require!(public_key == signer.as_ref() && signed_message == message, StakingError::InvalidOracleSignature);
+    Ok(())
+}
+
+// Anti-sybil gate. Solana keeps no account creation time, so holding at least
+// `min_staker_lamports` (checked before any fee is taken) stands in for account age.
+// It only raises the cost of farming with fresh wallets: funds can be moved into a new
+// wallet right before staking, and out again afterwards.
+pub fn check_account_eligible(user: &AccountInfo, staking_program: &StakingProgram, collection_mint: Pubkey) -> Result<()> {
+    if user.lamports() < staking_program.min_staker_lamports {
+        return reject_stake(
+            user.key(),
+            collection_mint,
+            naffles_staking::REJECT_ACCOUNT_NOT_ELIGIBLE,
+            StakingError::AccountNotEligible,
+        );
+    }
+    Ok(())
+}
+
+pub fn check_mint_not_blocked(user: Pubkey, collection_mint: Pubkey, blocked_mint_info: &AccountInfo) -> Result<()> {
+    if *blocked_mint_info.owner == crate::ID && !blocked_mint_info.data_is_empty() {
+        return reject_stake(user, collection_mint, naffles_staking::REJECT_MINT_BLOCKED, StakingError::MintBlocked);
+    }
+    Ok(())
+}
+
+// Share (basis points) of a claim forfeited when claiming at `current_time`:
+//   penalty = early_claim_penalty_bps * (term - elapsed) / term
+// i.e. the full configured penalty at stake time, falling linearly to zero at unlock.
+// Wall-clock time since staking is used, not accrual time. Nothing is claimable before
+// the vesting cliff, so the highest penalty actually paid is the one at the cliff.
+pub fn early_claim_penalty_bps(position: &StakingPosition, collection: &CollectionAccount, current_time: i64) -> u64 {
+    let term = position.unlock_at.saturating_sub(position.staked_at);
+    if term <= 0 || collection.early_claim_penalty_bps == 0 {
+        return 0;
+    }
+    let remaining = term - elapsed_since(position.staked_at, current_time).min(term);
+    (collection.early_claim_penalty_bps as i128 * remaining as i128 / term as i128) as u64
+}
+
+// Direct single-signer admin paths are closed once a multi-sig threshold is set; the same
+// effects then go through propose_action / confirm_action / execute_action.
+fn require_single_signer(staking_program: &StakingProgram) -> Result<()> {
+    require!(
+        staking_program.multi_sig_threshold <= 1,
+        StakingError::InsufficientMultiSigConfirmations
+    );
+    Ok(())
+}
+
+// Creates and initializes the collection PDA for `collection_mint` at `collection_info`,
+// funded by `payer`.
+fn create_collection_account<'info>(
+    payer: AccountInfo<'info>,
+    collection_info: &AccountInfo<'info>,
+    system_program: AccountInfo<'info>,
+    collection_mint: Pubkey,
+    tickets: [u64; 3],
+) -> Result<()> {
+    let (expected, bump) = Pubkey::find_program_address(&[b"collection", collection_mint.as_ref()], &crate::ID);
+    require!(collection_info.key() == expected, StakingError::CollectionNotFound);
+    require!(*collection_info.owner != crate::ID, StakingError::CollectionAlreadyExists);
+    
+    let seeds: &[&[u8]] = &[b"collection".as_ref(), collection_mint.as_ref(), &[bump]];
+    create_pda_account(payer, collection_info, system_program, CollectionAccount::LEN, seeds)?;
+    
+    let collection = CollectionAccount::new(collection_mint, tickets, bump);
+    collection.try_serialize(&mut &mut collection_info.try_borrow_mut_data()?[..])?;
+    Ok(())
+}
+
+fn emit_multipliers_derived(collection_account: &CollectionAccount) {
+    emit!(MultipliersDerived {
+        collection_mint: collection_account.collection_mint,
+        six_month_multiplier: collection_account.six_month_multiplier,
+        twelve_month_multiplier: collection_account.twelve_month_multiplier,
+        three_year_multiplier: collection_account.three_year_multiplier,
+    });
+}
+
+// Each confirmer account must be a distinct, still-active AdminAccount whose admin
+// confirmed the proposal; an admin deactivated after confirming no longer counts.
+fn check_confirmers(proposal: &ProposalAccount, confirmers: &[AccountInfo]) -> Result<()> {
+    let mut seen: Vec<Pubkey> = Vec::with_capacity(confirmers.len());
+    for account_info in confirmers {
+        let admin_account = Account::<AdminAccount>::try_from(account_info)?;
+        let (expected, _) = Pubkey::find_program_address(&[b"admin", admin_account.admin.as_ref()], &crate::ID);
+        require!(account_info.key() == expected, StakingError::Unauthorized);
+        require!(admin_account.is_active, StakingError::Unauthorized);
+        require!(
+            proposal.confirmations.contains(&admin_account.admin) && !seen.contains(&admin_account.admin),
+            StakingError::InsufficientMultiSigConfirmations
+        );
+        seen.push(admin_account.admin);
+    }
+    Ok(())
+}
+
+// sha256 over the concatenated keys, binding a GrantBonusRewards proposal to its positions
+fn positions_hash(positions: &[AccountInfo]) -> [u8; 32] {
+    let keys: Vec<u8> = positions.iter().flat_map(|info| info.key.to_bytes()).collect();
+    hash(&keys).to_bytes()
+}
+
+// Adds a bonus to the settled rewards of each position in `positions`, given as groups
+// of the position and its owner's UserStats PDA. Indexers credit the tickets from
+// BonusRewardsGranted; with a ticket mint they become mintable too.
+fn grant_bonus(
+    staking_program: &mut StakingProgram,
+    positions: &[AccountInfo],
+    amount: u64,
+    reason: &str,
+) -> Result<()> {
+    validate_reason(reason, naffles_staking::MAX_GRANT_REASON_LEN)?;
+    require!(
+        amount > 0 && amount <= naffles_staking::MAX_BONUS_PER_POSITION,
+        StakingError::BonusTooLarge
+    );
+    require!(positions.len().is_multiple_of(naffles_staking::REWARD_GROUP_LEN), StakingError::InvalidAmount);
+    staking_program.check_batch_size(positions.len() / naffles_staking::REWARD_GROUP_LEN)?;
+    
+    let mut seen: Vec<Pubkey> = Vec::with_capacity(positions.len() / naffles_staking::REWARD_GROUP_LEN);
+    for group in positions.chunks(naffles_staking::REWARD_GROUP_LEN) {
+        let (account_info, user_stats) = (&group[0], &group[1]);
+        require!(!seen.contains(account_info.key), StakingError::InvalidPosition);
+        require!(account_info.is_writable, StakingError::InvalidPosition);
+        seen.push(*account_info.key);
+        
+        let mut position = read_position(account_info)?;
+        position.total_rewards_earned = position
+            .total_rewards_earned
+            .checked_add(amount)
+            .ok_or(StakingError::ArithmeticOverflow)?;
+        write_position(account_info, &position)?;
+        staking_program.record_settled(amount)?;
+        update_user_stats(user_stats, &position.owner, |user_stats| user_stats.record_rewards(amount))?;
+        
+        emit!(BonusRewardsGranted {
+            owner: position.owner,
+            nft_mint: position.nft_mint,
+            amount,
+            total_rewards_earned: position.total_rewards_earned,
+            reason: reason.to_string(),
+        });
+    }
+    Ok(())
+}
+
+// Creates the zero-filled position PDA for (nft_mint, user) at `position_info`, funded
+// by `payer`, and returns its bump
+fn create_position_account<'info>(
+    payer: AccountInfo<'info>,
+    position_info: &AccountInfo<'info>,
+    system_program: AccountInfo<'info>,
+    nft_mint: &Pubkey,
+    user: &Pubkey,
+) -> Result<u8> {
+    let (expected, bump) =
+        Pubkey::find_program_address(&[b"staking_position", nft_mint.as_ref(), user.as_ref()], &crate::ID);
+    require!(position_info.key() == expected, StakingError::InvalidPosition);
+    require!(*position_info.owner != crate::ID, StakingError::InvalidPosition);
+    
+    let seeds: &[&[u8]] = &[b"staking_position".as_ref(), nft_mint.as_ref(), user.as_ref(), &[bump]];
+    create_pda_account(payer, position_info, system_program, StakingPosition::LEN, seeds)?;
+    Ok(bump)
+}
+
+// Creates the program-owned PDA at `account_info` with `space` bytes, funded by `payer`
+// and signed for with `seeds`. Anyone can send lamports to an address before it exists,
+// which would make create_account fail, so a pre-funded address is instead topped up to
+// rent exemption, then allocated and assigned.
+fn create_pda_account<'info>(
+    payer: AccountInfo<'info>,
+    account_info: &AccountInfo<'info>,
+    system_program: AccountInfo<'info>,
+    space: usize,
+    seeds: &[&[u8]],
+) -> Result<()> {
+    let rent_lamports = Rent::get()?.minimum_balance(space);
+    let signer = &[seeds];
+    if account_info.lamports() == 0 {
+        let create_accounts = system_program::CreateAccount {
+            from: payer,
+            to: account_info.clone(),
+        };
+        let create_ctx = CpiContext::new_with_signer(system_program, create_accounts, signer);
+        return system_program::create_account(create_ctx, rent_lamports, space as u64, &crate::ID);
+    }
+    
+    let top_up = rent_lamports.saturating_sub(account_info.lamports());
+    if top_up > 0 {
+        let transfer_accounts = system_program::Transfer {
+            from: payer,
+            to: account_info.clone(),
+        };
+        system_program::transfer(CpiContext::new(system_program.clone(), transfer_accounts), top_up)?;
+    }
+    let allocate_accounts = system_program::Allocate {
+        account_to_allocate: account_info.clone(),
+    };
+    system_program::allocate(
+        CpiContext::new_with_signer(system_program.clone(), allocate_accounts, signer),
+        space as u64,
+    )?;
+    let assign_accounts = system_program::Assign {
+        account_to_assign: account_info.clone(),
+    };
+    system_program::assign(CpiContext::new_with_signer(system_program, assign_accounts, signer), &crate::ID)
+}
+
+// Reasons end up in events, AdminAction data and EmergencyRequest accounts, so they must
+// be non-empty, at most `max_len` bytes and free of control characters
+pub fn validate_reason(reason: &str, max_len: usize) -> Result<()> {
+    require!(!reason.is_empty(), StakingError::ReasonRequired);
+    require!(
+        reason.len() <= max_len && !reason.chars().any(char::is_control),
+        StakingError::InvalidReason
+    );
+    Ok(())
+}
+
+// Creates and writes the RewardReceipt PDA for a claim at `receipt_info`, funded by
+// `payer`. Fails if the receipt exists, i.e. the claim settled nothing new.
+fn create_reward_receipt<'info>(
+    payer: AccountInfo<'info>,
+    receipt_info: &AccountInfo<'info>,
+    system_program: AccountInfo<'info>,
+    mut receipt: RewardReceipt,
+    staked_at: i64,
+    previous_watermark: u64,
+) -> Result<()> {
+    let staked_at_bytes = staked_at.to_le_bytes();
+    let watermark_bytes = previous_watermark.to_le_bytes();
+    let (expected, bump) = Pubkey::find_program_address(
+        &[b"reward_receipt", receipt.staking_position.as_ref(), &staked_at_bytes, &watermark_bytes],
+        &crate::ID,
+    );
+    require!(receipt_info.key() == expected, StakingError::InvalidPosition);
+    require!(*receipt_info.owner != crate::ID, StakingError::RewardReceiptExists);
+    
+    let seeds: &[&[u8]] = &[
+        b"reward_receipt".as_ref(),
+        receipt.staking_position.as_ref(),
+        &staked_at_bytes,
+        &watermark_bytes,
+        &[bump],
+    ];
+    create_pda_account(payer, receipt_info, system_program, RewardReceipt::LEN, seeds)?;
+    
+    receipt.bump = bump;
+    receipt.try_serialize(&mut &mut receipt_info.try_borrow_mut_data()?[..])?;
+    
+    emit!(RewardReceiptCreated {
+        receipt: expected,
+        staking_position: receipt.staking_position,
+        owner: receipt.owner,
+        amount: receipt.amount,
+        claimed_at: receipt.claimed_at,
+    });
+    Ok(())
+}
+
+// Context structures
+#[derive(Accounts)]
+pub struct Initialize<'info> {
+    #[account(
+        init,
+        payer = authority,
+        space = 8 + 32 + 1 + 8 + 8 + 1 + 8 + 8 + 4 + 8 + 32 + 8 + 4 + 32 * naffles_staking::MAX_CPI_CALLERS + 8 + 8 + 8 + 32 + 32 + 8 + 1 + 8 + 8 + 8 + 32 + 2 + 8 + 32 + 8 + 8 + 8 + 2 + 1 + 8 + 8 + 8 + 1,
+        seeds = [b"staking_program"],
+        bump
+    )]
+    pub staking_program: Account<'info, StakingProgram>,
+    
+    #[account(mut)]
+    pub authority: Signer<'info>,
+    
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct AddAdmin<'info> {
+    // Admins can onboard collections, so only the authority may appoint them
+    #[account(
+        mut,
+        constraint = staking_program.authority == authority.key() @ StakingError::Unauthorized
+    )]
+    pub staking_program: Account<'info, StakingProgram>,
+    
+    #[account(
+        init,
+        payer = authority,
+        space = 8 + 32 + 1 + 8 + 1,
+        seeds = [b"admin", admin.key().as_ref()],
+        bump
+    )]
+    pub admin_account: Account<'info, AdminAccount>,
+    
+    /// CHECK: This is the admin being added
+    pub admin: AccountInfo<'info>,
+    
+    #[account(mut)]
+    pub authority: Signer<'info>,
+    
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct AddCollection<'info> {
+    #[account(mut)]
+    pub staking_program: Account<'info, StakingProgram>,
+    
+    #[account(
+        init,
+        payer = authority,
+        space = CollectionAccount::LEN,
+        seeds = [b"collection", collection_mint.key().as_ref()],
+        bump
+    )]
+    pub collection_account: Account<'info, CollectionAccount>,
+    
+    /// CHECK: This is the collection mint being added
+    pub collection_mint: AccountInfo<'info>,
+    
+    #[account(mut, address = staking_program.treasury @ StakingError::InvalidRecipient)]
+    pub treasury: SystemAccount<'info>,
+    
+    // The caller's admin record; required unless the caller is the program authority
+    #[account(
+        seeds = [b"admin", authority.key().as_ref()],
+        bump = admin_account.bump,
+        constraint = admin_account.is_active @ StakingError::Unauthorized
+    )]
+    pub admin_account: Option<Account<'info, AdminAccount>>,
+    
+    #[account(mut)]
+    pub authority: Signer<'info>,
+    
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct StakeNft<'info> {
+    #[account(mut)]
+    pub staking_program: Account<'info, StakingProgram>,
+    
+    #[account(mut)]
+    pub collection_account: Account<'info, CollectionAccount>,
+    
+    #[account(
+        init,
+        payer = user,
+        space = StakingPosition::LEN,
+        seeds = [b"staking_position", nft_mint.key().as_ref(), user.key().as_ref()],
+        bump
+    )]
+    pub staking_position: Account<'info, StakingPosition>,
+    
+    #[account(
+        init_if_needed,
+        payer = user,
+        space = UserStakeCount::LEN,
+        seeds = [b"user_stake_count", collection_account.collection_mint.as_ref(), user.key().as_ref()],
+        bump
+    )]
+    pub user_stake_count: Account<'info, UserStakeCount>,
+    
+    #[account(
+        init_if_needed,
+        payer = user,
+        space = UserStats::LEN,
+        seeds = [b"user_stats", user.key().as_ref()],
+        bump
+    )]
+    pub user_stats: Account<'info, UserStats>,
+    
+    #[account(
+        constraint = nft_mint.decimals == 0 && nft_mint.supply == 1 @ StakingError::NotAnNft
+    )]
+    pub nft_mint: Account<'info, Mint>,
+    
+    #[account(
+        mut,
+        constraint = user_token_account.mint == nft_mint.key() @ StakingError::InvalidRecipient,
+        constraint = user_token_account.amount >= 1 @ StakingError::NotAnNft
+    )]
+    pub user_token_account: Account<'info, TokenAccount>,
+    
+    // Must be custodied by the staking program PDA, or the NFT could never be claimed back
+    #[account(
+        mut,
+        constraint = program_token_account.owner == staking_program.key() @ StakingError::InvalidRecipient,
+        constraint = program_token_account.mint == nft_mint.key() @ StakingError::InvalidRecipient
+    )]
+    pub program_token_account: Account<'info, TokenAccount>,
+    
+    #[account(
+        seeds = [b"verified_nft", nft_mint.key().as_ref()],
+        bump = verified_nft.bump
+    )]
+    pub verified_nft: Option<Account<'info, VerifiedNft>>,
+    
+    /// CHECK: Metaplex metadata for nft_mint, required when verified_nft is absent.
+    /// Owner and PDA address are checked in verify_collection_membership.
+    pub nft_metadata: Option<UncheckedAccount<'info>>,
+    
+    /// CHECK: BlockedMint PDA for the mint; only its existence matters
+    #[account(seeds = [b"blocked_mint", nft_mint.key().as_ref()], bump)]
+    pub blocked_mint: UncheckedAccount<'info>,
+    
+    #[account(mut, address = staking_program.treasury @ StakingError::InvalidRecipient)]
+    pub treasury: SystemAccount<'info>,
+    
+    #[account(mut)]
+    pub user: Signer<'info>,
+    
+    pub token_program: Program<'info, Token>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct ClaimNft<'info> {
+    #[account(mut)]
+    pub staking_program: Account<'info, StakingProgram>,
+    
+    #[account(
+        mut,
+        constraint = collection_account.collection_mint == staking_position.collection_mint @ StakingError::CollectionNotFound
+    )]
+    pub collection_account: Account<'info, CollectionAccount>,
+    
+    // Closed on claim so the owner gets the rent back and the NFT can be staked again.
+    // Rewards should be settled with claim_rewards first; anything unsettled is forfeited.
+    // Settled tickets paid on-chain must be minted or withdrawn first (see claim_blocker).
+    #[account(mut, close = owner)]
+    pub staking_position: Account<'info, StakingPosition>,
+    
+    /// CHECK: Position owner, refunded the position rent
+    #[account(mut, address = staking_position.owner @ StakingError::InvalidRecipient)]
+    pub owner: AccountInfo<'info>,
+    
+    // Owner's stake counter; created here for owners whose positions predate per-user
+    // limits (see UserStakeCount::record_unstake)
+    #[account(
+        init_if_needed,
+        payer = user,
+        space = UserStakeCount::LEN,
+        seeds = [b"user_stake_count", staking_position.collection_mint.as_ref(), staking_position.owner.as_ref()],
+        bump
+    )]
+    pub user_stake_count: Account<'info, UserStakeCount>,
+    
+    // Owner's totals, created here like the stake counter
+    #[account(
+        init_if_needed,
+        payer = user,
+        space = UserStats::LEN,
+        seeds = [b"user_stats", staking_position.owner.as_ref()],
+        bump
+    )]
+    pub user_stats: Account<'info, UserStats>,
+    
+    // Always the position owner's account, whether the owner or the claim delegate signs
+    #[account(
+        mut,
+        constraint = user_token_account.owner == staking_position.owner @ StakingError::InvalidRecipient,
+        constraint = user_token_account.mint == staking_position.nft_mint @ StakingError::InvalidRecipient
+    )]
+    pub user_token_account: Account<'info, TokenAccount>,
+    
+    #[account(
+        mut,
+        constraint = program_token_account.owner == staking_program.key() @ StakingError::InvalidRecipient,
+        constraint = program_token_account.mint == staking_position.nft_mint @ StakingError::InvalidRecipient
+    )]
+    pub program_token_account: Account<'info, TokenAccount>,
+    
+    #[account(mut)]
+    pub user: Signer<'info>,
+    
+    pub token_program: Program<'info, Token>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct ClaimRewards<'info> {
+    #[account(mut)]
+    pub staking_program: Account<'info, StakingProgram>,
+    
+    #[account(
+        mut,
+        constraint = collection_account.collection_mint == staking_position.collection_mint @ StakingError::CollectionNotFound
+    )]
+    pub collection_account: Account<'info, CollectionAccount>,
+    
+    #[account(
+        mut,
+        constraint = staking_position.owner == user.key() @ StakingError::NotPositionOwner
+    )]
+    pub staking_position: Account<'info, StakingPosition>,
+    
+    #[account(mut)]
+    pub user: Signer<'info>,
+    
+    #[account(mut, address = staking_program.treasury @ StakingError::InvalidRecipient)]
+    pub treasury: SystemAccount<'info>,
+    
+    /// CHECK: Instructions sysvar, used to identify CPI callers
+    #[account(address = sysvar::instructions::ID)]
+    pub sysvar_instructions: AccountInfo<'info>,
+    
+    /// CHECK: RewardReceipt PDA for this claim, passed only to opt into a receipt; the
+    /// address is checked in create_reward_receipt
+    #[account(mut)]
+    pub reward_receipt: Option<UncheckedAccount<'info>>,
+    
+    // As in ClaimNft
+    #[account(
+        init_if_needed,
+        payer = user,
+        space = UserStats::LEN,
+        seeds = [b"user_stats", user.key().as_ref()],
+        bump
+    )]
+    pub user_stats: Account<'info, UserStats>,
+    
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct AdminUnlock<'info> {
+    #[account(mut)]
+    pub staking_program: Account<'info, StakingProgram>,
+    
+    #[account(
+        mut,
+        constraint = collection_account.collection_mint == staking_position.collection_mint @ StakingError::CollectionNotFound
+    )]
+    pub collection_account: Account<'info, CollectionAccount>,
+    
+    #[account(mut)]
+    pub staking_position: Account<'info, StakingPosition>,
+    
+    // Owner's stake counter, as in ClaimNft
+    #[account(
+        init_if_needed,
+        payer = admin,
+        space = UserStakeCount::LEN,
+        seeds = [b"user_stake_count", staking_position.collection_mint.as_ref(), staking_position.owner.as_ref()],
+        bump
+    )]
+    pub user_stake_count: Account<'info, UserStakeCount>,
+    
+    #[account(
+        init_if_needed,
+        payer = admin,
+        space = UserStats::LEN,
+        seeds = [b"user_stats", staking_position.owner.as_ref()],
+        bump
+    )]
+    pub user_stats: Account<'info, UserStats>,
+    
+    #[account(
+        init_if_needed,
+        payer = admin,
+        space = 8 + 32 + 8 + 4 + naffles_staking::MAX_REASON_LEN + 1 + 1,
+        seeds = [b"emergency_request", staking_position.key().as_ref()],
+        bump
+    )]
+    pub emergency_request: Account<'info, EmergencyRequest>,
+    
+    #[account(
+        init_if_needed,
+        payer = admin,
+        space = ActiveEmergencyRequests::LEN,
+        seeds = [b"active_emergency_requests"],
+        bump
+    )]
+    pub active_emergency_requests: Account<'info, ActiveEmergencyRequests>,
+    
+    #[account(
+        constraint = admin_account.admin == admin.key() @ StakingError::Unauthorized,
+        constraint = admin_account.is_active @ StakingError::Unauthorized
+    )]
+    pub admin_account: Account<'info, AdminAccount>,
+    
+    #[account(
+        mut,
+        constraint = program_token_account.owner == staking_program.key() @ StakingError::InvalidRecipient,
+        constraint = program_token_account.mint == staking_position.nft_mint @ StakingError::InvalidRecipient
+    )]
+    pub program_token_account: Account<'info, TokenAccount>,
+    
+    // The NFT only ever goes back to the position owner
+    #[account(
+        mut,
+        constraint = owner_token_account.owner == staking_position.owner @ StakingError::InvalidRecipient,
+        constraint = owner_token_account.mint == staking_position.nft_mint @ StakingError::InvalidRecipient
+    )]
+    pub owner_token_account: Account<'info, TokenAccount>,
+    
+    #[account(mut)]
+    pub admin: Signer<'info>,
+    
+    pub token_program: Program<'info, Token>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct PauseContract<'info> {
+    #[account(mut)]
+    pub staking_program: Account<'info, StakingProgram>,
+    
+    #[account(
+        constraint = admin_account.admin == admin.key() @ StakingError::Unauthorized,
+        constraint = admin_account.is_active @ StakingError::Unauthorized
+    )]
+    pub admin_account: Account<'info, AdminAccount>,
+    
+    pub admin: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct UnpauseContract<'info> {
+    #[account(mut)]
+    pub staking_program: Account<'info, StakingProgram>,
+    
+    #[account(
+        init_if_needed,
+        payer = admin,
+        space = UnpauseApproval::LEN,
+        seeds = [b"unpause_approval", staking_program.paused_at.to_le_bytes().as_ref()],
+        bump
+    )]
+    pub unpause_approval: Account<'info, UnpauseApproval>,
+    
+    #[account(
+        constraint = admin_account.admin == admin.key() @ StakingError::Unauthorized,
+        constraint = admin_account.is_active @ StakingError::Unauthorized
+    )]
+    pub admin_account: Account<'info, AdminAccount>,
+    
+    #[account(mut)]
+    pub admin: Signer<'info>,
+    
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct UpdateCollectionRewards<'info> {
+    pub staking_program: Account<'info, StakingProgram>,
+    
+    #[account(mut)]
+    pub collection_account: Account<'info, CollectionAccount>,
+    
+    // Ticket counts drive every settlement, so only active admins may change them
+    #[account(
+        constraint = admin_account.admin == authority.key() @ StakingError::Unauthorized,
+        constraint = admin_account.is_active @ StakingError::Unauthorized
+    )]
+    pub admin_account: Account<'info, AdminAccount>,
+    
+    pub authority: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct ValidateCollection<'info> {
+    #[account(mut)]
+    pub staking_program: Account<'info, StakingProgram>,
+    
+    #[account(mut)]
+    pub collection_account: Account<'info, CollectionAccount>,
+    
+    // Validation counts toward min_validated_collections, so only active admins may set it
+    #[account(
+        constraint = admin_account.admin == authority.key() @ StakingError::Unauthorized,
+        constraint = admin_account.is_active @ StakingError::Unauthorized
+    )]
+    pub admin_account: Account<'info, AdminAccount>,
+    
+    pub authority: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct ExpireCollectionValidation<'info> {
+    #[account(mut)]
+    pub staking_program: Account<'info, StakingProgram>,
+    
+    #[account(mut)]
+    pub collection_account: Account<'info, CollectionAccount>,
+    
+    pub caller: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct TimeUntilUnlock<'info> {
+    pub staking_position: Account<'info, StakingPosition>,
+}
+
+#[derive(Accounts)]
+pub struct UpdateProgramConfig<'info> {
+    #[account(
+        mut,
+        constraint = staking_program.authority == authority.key() @ StakingError::Unauthorized
+    )]
+    pub staking_program: Account<'info, StakingProgram>,
+    
+    pub authority: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct GetStakeCost<'info> {
+    pub staking_program: Account<'info, StakingProgram>,
+    
+    pub collection_account: Account<'info, CollectionAccount>,
+    
+    /// CHECK: The user being quoted; only used to derive the counter PDAs
+    pub user: UncheckedAccount<'info>,
+    
+    /// CHECK: UserStakeCount PDA for the user; only its existence matters
+    #[account(seeds = [b"user_stake_count", collection_account.collection_mint.as_ref(), user.key().as_ref()], bump)]
+    pub user_stake_count: UncheckedAccount<'info>,
+    
+    /// CHECK: UserStats PDA for the user; only its existence matters
+    #[account(seeds = [b"user_stats", user.key().as_ref()], bump)]
+    pub user_stats: UncheckedAccount<'info>,
+}
+
+#[derive(Accounts)]
+pub struct UpdateCollectionConfig<'info> {
+    pub staking_program: Account<'info, StakingProgram>,
+    
+    #[account(mut)]
+    pub collection_account: Account<'info, CollectionAccount>,
+    
+    #[account(
+        constraint = admin_account.admin == admin.key() @ StakingError::Unauthorized,
+        constraint = admin_account.is_active @ StakingError::Unauthorized
+    )]
+    pub admin_account: Account<'info, AdminAccount>,
+    
+    pub admin: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct SetPausedInstructions<'info> {
+    #[account(mut)]
+    pub staking_program: Account<'info, StakingProgram>,
+    
+    #[account(
+        constraint = admin_account.admin == admin.key() @ StakingError::Unauthorized,
+        constraint = admin_account.is_active @ StakingError::Unauthorized
+    )]
+    pub admin_account: Account<'info, AdminAccount>,
+    
+    pub admin: Signer<'info>,
+}
+
+#[derive(Accounts)]
+#[instruction(owner: Pubkey, nft_mint: Pubkey)]
+pub struct AdminCreatePosition<'info> {
+    #[account(
+        mut,
+        constraint = staking_program.authority == authority.key() @ StakingError::Unauthorized
+    )]
+    pub staking_program: Account<'info, StakingProgram>,
+    
+    #[account(mut)]
+    pub collection_account: Account<'info, CollectionAccount>,
+    
+    #[account(
+        init,
+        payer = authority,
+        space = StakingPosition::LEN,
+        seeds = [b"staking_position", nft_mint.as_ref(), owner.as_ref()],
+        bump
+    )]
+    pub staking_position: Account<'info, StakingPosition>,
+    
+    #[account(
+        init_if_needed,
+        payer = authority,
+        space = UserStakeCount::LEN,
+        seeds = [b"user_stake_count", collection_account.collection_mint.as_ref(), owner.as_ref()],
+        bump
+    )]
+    pub user_stake_count: Account<'info, UserStakeCount>,
+    
+    #[account(
+        init_if_needed,
+        payer = authority,
+        space = UserStats::LEN,
+        seeds = [b"user_stats", owner.as_ref()],
+        bump
+    )]
+    pub user_stats: Account<'info, UserStats>,
+    
+    // The migrated NFT has to already be custodied by the program
+    #[account(
+        constraint = program_token_account.mint == nft_mint @ StakingError::InvalidRecipient,
+        constraint = program_token_account.owner == staking_program.key() @ StakingError::InvalidRecipient,
+        constraint = program_token_account.amount == 1 @ StakingError::InvalidRecipient
+    )]
+    pub program_token_account: Account<'info, TokenAccount>,
+    
+    #[account(mut)]
+    pub authority: Signer<'info>,
+    
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct VerifyNftMembership<'info> {
+    pub collection_account: Account<'info, CollectionAccount>,
+    
+    #[account(
+        init_if_needed,
+        payer = payer,
+        space = 8 + 32 + 32 + 8 + 1,
+        seeds = [b"verified_nft", nft_mint.key().as_ref()],
+        bump
+    )]
+    pub verified_nft: Account<'info, VerifiedNft>,
+    
+    /// CHECK: This is the NFT mint being verified
+    pub nft_mint: AccountInfo<'info>,
+    
+    /// CHECK: Owner and PDA are validated in verify_collection_membership
+    pub nft_metadata: AccountInfo<'info>,
+    
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(new_tier: u8)]
+pub struct AdminUpgradeTier<'info> {
+    #[account(
+        constraint = collection_account.collection_mint == staking_position.collection_mint @ StakingError::CollectionNotFound
+    )]
+    pub collection_account: Account<'info, CollectionAccount>,
+    
+    #[account(mut)]
+    pub staking_position: Account<'info, StakingPosition>,
+    
+    #[account(
+        init_if_needed,
+        payer = admin,
+        space = 8 + 32 + 8 + 4 + naffles_staking::MAX_REASON_LEN + 1 + 1,
+        seeds = [b"tier_upgrade_request", staking_position.key().as_ref(), &[new_tier]],
+        bump
+    )]
+    pub emergency_request: Account<'info, EmergencyRequest>,
+    
+    #[account(
+        init_if_needed,
+        payer = admin,
+        space = ActiveEmergencyRequests::LEN,
+        seeds = [b"active_emergency_requests"],
+        bump
+    )]
+    pub active_emergency_requests: Account<'info, ActiveEmergencyRequests>,
+    
+    #[account(
+        constraint = admin_account.admin == admin.key() @ StakingError::Unauthorized,
+        constraint = admin_account.is_active @ StakingError::Unauthorized
+    )]
+    pub admin_account: Account<'info, AdminAccount>,
+    
+    #[account(mut)]
+    pub admin: Signer<'info>,
+    
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct ListEmergencyRequests<'info> {
+    #[account(seeds = [b"active_emergency_requests"], bump = active_emergency_requests.bump)]
+    pub active_emergency_requests: Account<'info, ActiveEmergencyRequests>,
+}
+
+#[derive(Accounts)]
+pub struct SetClaimDelegate<'info> {
+    #[account(
+        mut,
+        constraint = staking_position.owner == owner.key() @ StakingError::NotPositionOwner
+    )]
+    pub staking_position: Account<'info, StakingPosition>,
+    
+    pub owner: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct TotalLiability<'info> {
+    pub staking_program: Account<'info, StakingProgram>,
+    
+    pub collection_account: Account<'info, CollectionAccount>,
+}
+
+#[derive(Accounts)]
+pub struct StakePnft<'info> {
+    #[account(mut)]
+    pub staking_program: Account<'info, StakingProgram>,
+    
+    #[account(mut)]
+    pub collection_account: Account<'info, CollectionAccount>,
+    
+    #[account(
+        init,
+        payer = user,
+        space = StakingPosition::LEN,
+        seeds = [b"staking_position", nft_mint.key().as_ref(), user.key().as_ref()],
+        bump
+    )]
+    pub staking_position: Account<'info, StakingPosition>,
+    
+    #[account(
+        init_if_needed,
+        payer = user,
+        space = UserStakeCount::LEN,
+        seeds = [b"user_stake_count", collection_account.collection_mint.as_ref(), user.key().as_ref()],
+        bump
+    )]
+    pub user_stake_count: Account<'info, UserStakeCount>,
+    
+    #[account(
+        init_if_needed,
+        payer = user,
+        space = UserStats::LEN,
+        seeds = [b"user_stats", user.key().as_ref()],
+        bump
+    )]
+    pub user_stats: Account<'info, UserStats>,
+    
+    /// CHECK: This is the pNFT mint being staked, validated by Token Metadata
+    pub nft_mint: AccountInfo<'info>,
+    
+    #[account(mut)]
+    pub user_token_account: Account<'info, TokenAccount>,
+    
+    /// CHECK: Program vault ATA; created by Token Metadata if it does not exist yet
+    #[account(
+        mut,
+        address = get_associated_token_address(&staking_program.key(), &nft_mint.key()) @ StakingError::InvalidRecipient
+    )]
+    pub program_token_account: AccountInfo<'info>,
+    
+    /// CHECK: Validated by Token Metadata
+    #[account(mut)]
+    pub nft_metadata: AccountInfo<'info>,
+    
+    /// CHECK: Validated by Token Metadata
+    pub nft_edition: AccountInfo<'info>,
+    
+    /// CHECK: Validated by Token Metadata
+    #[account(mut)]
+    pub owner_token_record: AccountInfo<'info>,
+    
+    /// CHECK: Validated by Token Metadata
+    #[account(mut)]
+    pub destination_token_record: AccountInfo<'info>,
+    
+    /// CHECK: Validated by Token Metadata
+    pub authorization_rules_program: AccountInfo<'info>,
+    
+    /// CHECK: Validated by Token Metadata
+    pub authorization_rules: AccountInfo<'info>,
+    
+    #[account(
+        seeds = [b"verified_nft", nft_mint.key().as_ref()],
+        bump = verified_nft.bump
+    )]
+    pub verified_nft: Option<Account<'info, VerifiedNft>>,
+    
+    /// CHECK: BlockedMint PDA for the mint; only its existence matters
+    #[account(seeds = [b"blocked_mint", nft_mint.key().as_ref()], bump)]
+    pub blocked_mint: UncheckedAccount<'info>,
+    
+    #[account(mut, address = staking_program.treasury @ StakingError::InvalidRecipient)]
+    pub treasury: SystemAccount<'info>,
+    
+    #[account(mut)]
+    pub user: Signer<'info>,
+    
+    /// CHECK: Instructions sysvar
+    #[account(address = sysvar::instructions::ID)]
+    pub sysvar_instructions: AccountInfo<'info>,
+    
+    /// CHECK: Token Metadata program
+    #[account(address = mpl_token_metadata::ID)]
+    pub token_metadata_program: AccountInfo<'info>,
+    
+    pub token_program: Program<'info, Token>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct ClaimPnft<'info> {
+    #[account(mut)]
+    pub staking_program: Account<'info, StakingProgram>,
+    
+    #[account(
+        mut,
+        constraint = collection_account.collection_mint == staking_position.collection_mint @ StakingError::CollectionNotFound
+    )]
+    pub collection_account: Account<'info, CollectionAccount>,
+    
+    // Closed on claim, as in ClaimNft
+    #[account(mut, close = owner)]
+    pub staking_position: Account<'info, StakingPosition>,
+    
+    /// CHECK: Must be the staked mint
+    #[account(address = staking_position.nft_mint @ StakingError::InvalidRecipient)]
+    pub nft_mint: AccountInfo<'info>,
+    
+    /// CHECK: Program vault ATA holding the pNFT
+    #[account(
+        mut,
+        address = get_associated_token_address(&staking_program.key(), &nft_mint.key()) @ StakingError::InvalidRecipient
+    )]
+    pub program_token_account: AccountInfo<'info>,
+    
+    /// CHECK: Owner's ATA for the mint; created by Token Metadata if needed
+    #[account(
+        mut,
+        address = get_associated_token_address(&staking_position.owner, &nft_mint.key()) @ StakingError::InvalidRecipient
+    )]
+    pub owner_token_account: AccountInfo<'info>,
+    
+    /// CHECK: The position owner, who receives the pNFT and the position rent even when a delegate signs
+    #[account(mut, address = staking_position.owner @ StakingError::NotPositionOwner)]
+    pub owner: AccountInfo<'info>,
+    
+    // As in ClaimNft
+    #[account(
+        init_if_needed,
+        payer = user,
+        space = UserStakeCount::LEN,
+        seeds = [b"user_stake_count", staking_position.collection_mint.as_ref(), staking_position.owner.as_ref()],
+        bump
+    )]
+    pub user_stake_count: Account<'info, UserStakeCount>,
+    
+    // As in ClaimNft
+    #[account(
+        init_if_needed,
+        payer = user,
+        space = UserStats::LEN,
+        seeds = [b"user_stats", staking_position.owner.as_ref()],
+        bump
+    )]
+    pub user_stats: Account<'info, UserStats>,
+    
+    /// CHECK: Validated by Token Metadata
+    #[account(mut)]
+    pub nft_metadata: AccountInfo<'info>,
+    
+    /// CHECK: Validated by Token Metadata
+    pub nft_edition: AccountInfo<'info>,
+    
+    /// CHECK: Validated by Token Metadata
+    #[account(mut)]
+    pub owner_token_record: AccountInfo<'info>,
+    
+    /// CHECK: Validated by Token Metadata
+    #[account(mut)]
+    pub destination_token_record: AccountInfo<'info>,
+    
+    /// CHECK: Validated by Token Metadata
+    pub authorization_rules_program: AccountInfo<'info>,
+    
+    /// CHECK: Validated by Token Metadata
+    pub authorization_rules: AccountInfo<'info>,
+    
+    #[account(mut)]
+    pub user: Signer<'info>,
+    
+    /// CHECK: Instructions sysvar
+    #[account(address = sysvar::instructions::ID)]
+    pub sysvar_instructions: AccountInfo<'info>,
+    
+    /// CHECK: Token Metadata program
+    #[account(address = mpl_token_metadata::ID)]
+    pub token_metadata_program: AccountInfo<'info>,
+    
+    pub token_program: Program<'info, Token>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct StakeRewards<'info> {
+    pub staking_program: Account<'info, StakingProgram>,
+    
+    #[account(
+        constraint = collection_account.collection_mint == staking_position.collection_mint @ StakingError::CollectionNotFound
+    )]
+    pub collection_account: Account<'info, CollectionAccount>,
+    
+    #[account(
+        mut,
+        constraint = staking_position.owner == user.key() @ StakingError::NotPositionOwner
+    )]
+    pub staking_position: Account<'info, StakingPosition>,
+    
+    #[account(
+        init,
+        payer = user,
+        space = RewardStakePosition::LEN,
+        seeds = [b"reward_stake", staking_position.key().as_ref()],
+        bump
+    )]
+    pub reward_stake: Account<'info, RewardStakePosition>,
+    
+    #[account(mut)]
+    pub user: Signer<'info>,
+    
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct ClaimRewardStake<'info> {
+    #[account(mut)]
+    pub staking_program: Account<'info, StakingProgram>,
+    
+    #[account(
+        mut,
+        constraint = staking_position.owner == user.key() @ StakingError::NotPositionOwner
+    )]
+    pub staking_position: Account<'info, StakingPosition>,
+    
+    #[account(
+        mut,
+        close = user,
+        seeds = [b"reward_stake", staking_position.key().as_ref()],
+        bump = reward_stake.bump
+    )]
+    pub reward_stake: Account<'info, RewardStakePosition>,
+    
+    // As in ClaimNft
+    #[account(
+        init_if_needed,
+        payer = user,
+        space = UserStats::LEN,
+        seeds = [b"user_stats", user.key().as_ref()],
+        bump
+    )]
+    pub user_stats: Account<'info, UserStats>,
+    
+    #[account(mut)]
+    pub user: Signer<'info>,
+    
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(snapshot_id: u64, user: Pubkey)]
+pub struct SnapshotUserRewards<'info> {
+    #[account(
+        constraint = staking_program.authority == authority.key() @ StakingError::Unauthorized
+    )]
+    pub staking_program: Account<'info, StakingProgram>,
+    
+    // No instruction ever writes this account again, so a snapshot is immutable
+    #[account(
+        init,
+        payer = authority,
+        space = 8 + 8 + 32 + 8 + 4 + 8 + 1,
+        seeds = [b"reward_snapshot", snapshot_id.to_le_bytes().as_ref(), user.as_ref()],
+        bump
+    )]
+    pub reward_snapshot: Account<'info, RewardSnapshot>,
+    
+    #[account(mut)]
+    pub authority: Signer<'info>,
+    
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(new_owner: Pubkey)]
+pub struct BatchTransferPositions<'info> {
+    pub staking_program: Account<'info, StakingProgram>,
+    
+    pub collection_account: Account<'info, CollectionAccount>,
+    
+    // As in TransferPosition
+    #[account(
+        init_if_needed,
+        payer = owner,
+        space = UserStakeCount::LEN,
+        seeds = [b"user_stake_count", collection_account.collection_mint.as_ref(), owner.key().as_ref()],
+        bump
+    )]
+    pub owner_stake_count: Account<'info, UserStakeCount>,
+    
+    #[account(
+        init_if_needed,
+        payer = owner,
+        space = UserStakeCount::LEN,
+        seeds = [b"user_stake_count", collection_account.collection_mint.as_ref(), new_owner.as_ref()],
+        bump
+    )]
+    pub new_owner_stake_count: Account<'info, UserStakeCount>,
+    
+    #[account(
+        init_if_needed,
+        payer = owner,
+        space = UserStats::LEN,
+        seeds = [b"user_stats", owner.key().as_ref()],
+        bump
+    )]
+    pub owner_stats: Account<'info, UserStats>,
+    
+    #[account(
+        init_if_needed,
+        payer = owner,
+        space = UserStats::LEN,
+        seeds = [b"user_stats", new_owner.as_ref()],
+        bump
+    )]
+    pub new_owner_stats: Account<'info, UserStats>,
+    
+    #[account(mut)]
+    pub owner: Signer<'info>,
+    
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct MigratePosition<'info> {
+    /// CHECK: May still be in an old layout; validated and decoded in the handler
+    #[account(mut)]
+    pub staking_position: UncheckedAccount<'info>,
+    
+    pub collection_account: Account<'info, CollectionAccount>,
+    
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct BatchAddCollections<'info> {
+    #[account(
+        mut,
+        constraint = staking_program.authority == authority.key() @ StakingError::Unauthorized
+    )]
+    pub staking_program: Account<'info, StakingProgram>,
+    
+    #[account(mut)]
+    pub authority: Signer<'info>,
+    
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct CanClaim<'info> {
+    pub staking_program: Account<'info, StakingProgram>,
+    pub staking_position: Account<'info, StakingPosition>,
+}
+
+#[derive(Accounts)]
+pub struct SetPositionAccrualPaused<'info> {
+    #[account(mut)]
+    pub staking_position: Account<'info, StakingPosition>,
+    
+    #[account(
+        constraint = admin_account.admin == admin.key() @ StakingError::Unauthorized,
+        constraint = admin_account.is_active @ StakingError::Unauthorized
+    )]
+    pub admin_account: Account<'info, AdminAccount>,
+    
+    pub admin: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct AuditVault<'info> {
+    pub staking_program: Account<'info, StakingProgram>,
+    
+    #[account(
+        constraint = program_token_account.owner == staking_program.key() @ StakingError::InvalidRecipient
+    )]
+    pub program_token_account: Account<'info, TokenAccount>,
+}
+
+#[derive(Accounts)]
+pub struct MigrateCollectionMint<'info> {
+    #[account(
+        constraint = staking_program.authority == authority.key() @ StakingError::Unauthorized
+    )]
+    pub staking_program: Account<'info, StakingProgram>,
+    
+    #[account(mut)]
+    pub collection_account: Account<'info, CollectionAccount>,
+    
+    pub authority: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct SyncPositionCollectionMint<'info> {
+    pub staking_program: Account<'info, StakingProgram>,
+    
+    pub collection_account: Account<'info, CollectionAccount>,
+}
+
+#[derive(Accounts)]
+pub struct CreditRewards<'info> {
+    #[account(mut)]
+    pub staking_program: Account<'info, StakingProgram>,
+    
+    /// CHECK: Instructions sysvar, used to find the oracle's Ed25519 signature
+    #[account(address = sysvar::instructions::ID)]
+    pub sysvar_instructions: AccountInfo<'info>,
+}
+
+#[derive(Accounts)]
+pub struct BlockMint<'info> {
+    #[account(
+        init,
+        payer = admin,
+        space = 8 + 32 + 32 + 8 + 1,
+        seeds = [b"blocked_mint", nft_mint.key().as_ref()],
+        bump
+    )]
+    pub blocked_mint: Account<'info, BlockedMint>,
+    
+    /// CHECK: The NFT mint being blocked
+    pub nft_mint: AccountInfo<'info>,
+    
+    #[account(
+        constraint = admin_account.admin == admin.key() @ StakingError::Unauthorized,
+        constraint = admin_account.is_active @ StakingError::Unauthorized
+    )]
+    pub admin_account: Account<'info, AdminAccount>,
+    
+    #[account(mut)]
+    pub admin: Signer<'info>,
+    
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct UnblockMint<'info> {
+    #[account(
+        mut,
+        close = admin,
+        seeds = [b"blocked_mint", blocked_mint.nft_mint.as_ref()],
+        bump = blocked_mint.bump
+    )]
+    pub blocked_mint: Account<'info, BlockedMint>,
+    
+    #[account(
+        constraint = admin_account.admin == admin.key() @ StakingError::Unauthorized,
+        constraint = admin_account.is_active @ StakingError::Unauthorized
+    )]
+    pub admin_account: Account<'info, AdminAccount>,
+    
+    #[account(mut)]
+    pub admin: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct DerivePosition {}
+
+#[derive(Accounts)]
+pub struct GetPosition<'info> {
+    pub staking_position: Account<'info, StakingPosition>,
+}
+
+#[derive(Accounts)]
+pub struct SwapStakedNft<'info> {
+    pub staking_program: Account<'info, StakingProgram>,
+    
+    #[account(
+        constraint = collection_account.collection_mint == staking_position.collection_mint @ StakingError::CollectionNotFound
+    )]
+    pub collection_account: Account<'info, CollectionAccount>,
+    
+    // Closed once its state is copied into new_staking_position
+    #[account(
+        mut,
+        close = user,
+        constraint = staking_position.owner == user.key() @ StakingError::NotPositionOwner
+    )]
+    pub staking_position: Account<'info, StakingPosition>,
+    
+    #[account(
+        init,
+        payer = user,
+        space = StakingPosition::LEN,
+        seeds = [b"staking_position", new_nft_mint.key().as_ref(), user.key().as_ref()],
+        bump
+    )]
+    pub new_staking_position: Account<'info, StakingPosition>,
+    
+    #[account(
+        mut,
+        constraint = program_old_token_account.mint == staking_position.nft_mint @ StakingError::InvalidRecipient,
+        constraint = program_old_token_account.owner == staking_program.key() @ StakingError::InvalidRecipient
+    )]
+    pub program_old_token_account: Account<'info, TokenAccount>,
+    
+    #[account(
+        mut,
+        constraint = user_old_token_account.mint == staking_position.nft_mint @ StakingError::InvalidRecipient,
+        constraint = user_old_token_account.owner == user.key() @ StakingError::InvalidRecipient
+    )]
+    pub user_old_token_account: Account<'info, TokenAccount>,
+    
+    #[account(constraint = new_nft_mint.decimals == 0 && new_nft_mint.supply == 1 @ StakingError::NotAnNft)]
+    pub new_nft_mint: Account<'info, Mint>,
+    
+    #[account(
+        seeds = [b"verified_nft", new_nft_mint.key().as_ref()],
+        bump = verified_nft.bump
+    )]
+    pub verified_nft: Option<Account<'info, VerifiedNft>>,
+    
+    /// CHECK: Metaplex metadata for new_nft_mint, required when verified_nft is absent.
+    /// Owner and PDA address are checked in verify_collection_membership.
+    pub new_nft_metadata: Option<UncheckedAccount<'info>>,
+    
+    #[account(
+        mut,
+        constraint = user_new_token_account.mint == new_nft_mint.key() @ StakingError::InvalidRecipient,
+        constraint = user_new_token_account.owner == user.key() @ StakingError::InvalidRecipient
+    )]
+    pub user_new_token_account: Account<'info, TokenAccount>,
+    
+    #[account(
+        mut,
+        constraint = program_new_token_account.mint == new_nft_mint.key() @ StakingError::InvalidRecipient,
+        constraint = program_new_token_account.owner == staking_program.key() @ StakingError::InvalidRecipient
+    )]
+    pub program_new_token_account: Account<'info, TokenAccount>,
+    
+    /// CHECK: BlockedMint PDA for the new mint; only its existence matters
+    #[account(seeds = [b"blocked_mint", new_nft_mint.key().as_ref()], bump)]
+    pub blocked_mint: UncheckedAccount<'info>,
+    
+    /// CHECK: EmergencyRequest PDA for the old position; checked in check_swap_allowed
+    #[account(seeds = [b"emergency_request", staking_position.key().as_ref()], bump)]
+    pub emergency_request: UncheckedAccount<'info>,
+    
+    #[account(mut)]
+    pub user: Signer<'info>,
+    
+    pub token_program: Program<'info, Token>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct SwapStakedPnft<'info> {
+    pub staking_program: Account<'info, StakingProgram>,
+    
+    #[account(
+        constraint = collection_account.collection_mint == staking_position.collection_mint @ StakingError::CollectionNotFound
+    )]
+    pub collection_account: Account<'info, CollectionAccount>,
+    
+    // As in SwapStakedNft
+    #[account(
+        mut,
+        close = user,
+        constraint = staking_position.owner == user.key() @ StakingError::NotPositionOwner
+    )]
+    pub staking_position: Account<'info, StakingPosition>,
+    
+    #[account(
+        init,
+        payer = user,
+        space = StakingPosition::LEN,
+        seeds = [b"staking_position", new_nft_mint.key().as_ref(), user.key().as_ref()],
+        bump
+    )]
+    pub new_staking_position: Account<'info, StakingPosition>,
+    
+    /// CHECK: Must be the staked mint
+    #[account(address = staking_position.nft_mint @ StakingError::InvalidRecipient)]
+    pub old_nft_mint: AccountInfo<'info>,
+    
+    /// CHECK: Program vault ATA holding the staked pNFT
+    #[account(
+        mut,
+        address = get_associated_token_address(&staking_program.key(), &old_nft_mint.key()) @ StakingError::InvalidRecipient
+    )]
+    pub program_old_token_account: AccountInfo<'info>,
+    
+    /// CHECK: User's ATA for the staked mint; created by Token Metadata if needed
+    #[account(
+        mut,
+        address = get_associated_token_address(&user.key(), &old_nft_mint.key()) @ StakingError::InvalidRecipient
+    )]
+    pub user_old_token_account: AccountInfo<'info>,
+    
+    /// CHECK: Validated by Token Metadata
+    #[account(mut)]
+    pub old_nft_metadata: AccountInfo<'info>,
+    
+    /// CHECK: Validated by Token Metadata
+    pub old_nft_edition: AccountInfo<'info>,
+    
+    /// CHECK: Validated by Token Metadata
+    #[account(mut)]
+    pub old_owner_token_record: AccountInfo<'info>,
+    
+    /// CHECK: Validated by Token Metadata
+    #[account(mut)]
+    pub old_destination_token_record: AccountInfo<'info>,
+    
+    /// CHECK: The replacement pNFT mint, validated by Token Metadata
+    pub new_nft_mint: AccountInfo<'info>,
+    
+    #[account(mut)]
+    pub user_new_token_account: Account<'info, TokenAccount>,
+    
+    /// CHECK: Program vault ATA; created by Token Metadata if it does not exist yet
+    #[account(
+        mut,
+        address = get_associated_token_address(&staking_program.key(), &new_nft_mint.key()) @ StakingError::InvalidRecipient
+    )]
+    pub program_new_token_account: AccountInfo<'info>,
+    
+    /// CHECK: Validated by Token Metadata
+    #[account(mut)]
+    pub new_nft_metadata: AccountInfo<'info>,
+    
+    /// CHECK: Validated by Token Metadata
+    pub new_nft_edition: AccountInfo<'info>,
+    
+    /// CHECK: Validated by Token Metadata
+    #[account(mut)]
+    pub new_owner_token_record: AccountInfo<'info>,
+    
+    /// CHECK: Validated by Token Metadata
+    #[account(mut)]
+    pub new_destination_token_record: AccountInfo<'info>,
+    
+    /// CHECK: Validated by Token Metadata
+    pub authorization_rules_program: AccountInfo<'info>,
+    
+    /// CHECK: Validated by Token Metadata
+    pub authorization_rules: AccountInfo<'info>,
+    
+    #[account(
+        seeds = [b"verified_nft", new_nft_mint.key().as_ref()],
+        bump = verified_nft.bump
+    )]
+    pub verified_nft: Option<Account<'info, VerifiedNft>>,
+    
+    /// CHECK: BlockedMint PDA for the new mint; only its existence matters
+    #[account(seeds = [b"blocked_mint", new_nft_mint.key().as_ref()], bump)]
+    pub blocked_mint: UncheckedAccount<'info>,
+    
+    /// CHECK: EmergencyRequest PDA for the old position; checked in check_swap_allowed
+    #[account(seeds = [b"emergency_request", staking_position.key().as_ref()], bump)]
+    pub emergency_request: UncheckedAccount<'info>,
+    
+    #[account(mut)]
+    pub user: Signer<'info>,
+    
+    /// CHECK: Instructions sysvar
+    #[account(address = sysvar::instructions::ID)]
+    pub sysvar_instructions: AccountInfo<'info>,
+    
+    /// CHECK: Token Metadata program
+    #[account(address = mpl_token_metadata::ID)]
+    pub token_metadata_program: AccountInfo<'info>,
+    
+    pub token_program: Program<'info, Token>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(nonce: u64, action_hash: [u8; 32])]
+pub struct ProposeAction<'info> {
+    pub staking_program: Account<'info, StakingProgram>,
+    
+    #[account(
+        init,
+        payer = admin,
+        space = ProposalAccount::LEN,
+        seeds = [b"proposal", action_hash.as_ref()],
+        bump
+    )]
+    pub proposal: Account<'info, ProposalAccount>,
+    
+    #[account(
+        init_if_needed,
+        payer = admin,
+        space = ActiveEmergencyRequests::LEN,
+        seeds = [b"active_emergency_requests"],
+        bump
+    )]
+    pub active_emergency_requests: Account<'info, ActiveEmergencyRequests>,
+    
+    #[account(
+        constraint = admin_account.admin == admin.key() @ StakingError::Unauthorized,
+        constraint = admin_account.is_active @ StakingError::Unauthorized
+    )]
+    pub admin_account: Account<'info, AdminAccount>,
+    
+    #[account(mut)]
+    pub admin: Signer<'info>,
+    
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct ConfirmAction<'info> {
+    pub staking_program: Account<'info, StakingProgram>,
+    
+    #[account(
+        mut,
+        seeds = [b"proposal", proposal.action_hash.as_ref()],
+        bump = proposal.bump
+    )]
+    pub proposal: Account<'info, ProposalAccount>,
+    
+    #[account(
+        constraint = admin_account.admin == admin.key() @ StakingError::Unauthorized,
+        constraint = admin_account.is_active @ StakingError::Unauthorized
+    )]
+    pub admin_account: Account<'info, AdminAccount>,
+    
+    pub admin: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct ExecuteAction<'info> {
+    #[account(mut)]
+    pub staking_program: Account<'info, StakingProgram>,
+    
+    #[account(
+        mut,
+        seeds = [b"proposal", proposal.action_hash.as_ref()],
+        bump = proposal.bump,
+        close = proposer
+    )]
+    pub proposal: Account<'info, ProposalAccount>,
+    
+    /// CHECK: Proposer, refunded the proposal rent
+    #[account(mut, address = proposal.proposer @ StakingError::InvalidRecipient)]
+    pub proposer: AccountInfo<'info>,
+    
+    #[account(
+        mut,
+        seeds = [b"active_emergency_requests"],
+        bump = active_emergency_requests.bump
+    )]
+    pub active_emergency_requests: Account<'info, ActiveEmergencyRequests>,
+    
+    #[account(
+        constraint = admin_account.admin == admin.key() @ StakingError::Unauthorized,
+        constraint = admin_account.is_active @ StakingError::Unauthorized
+    )]
+    pub admin_account: Account<'info, AdminAccount>,
+    
+    #[account(mut)]
+    pub admin: Signer<'info>,
+    
+    /// CHECK: collection PDA for the action; created for AddCollection, deserialized otherwise
+    #[account(mut)]
+    pub collection_account: Option<UncheckedAccount<'info>>,
+    
+    #[account(mut)]
+    pub staking_position: Option<Account<'info, StakingPosition>>,
+    
+    #[account(mut)]
+    pub program_token_account: Option<Account<'info, TokenAccount>>,
+    
+    #[account(mut)]
+    pub owner_token_account: Option<Account<'info, TokenAccount>>,
+    
+    /// CHECK: the position owner's UserStakeCount PDA, checked in release_stake_count
+    #[account(mut)]
+    pub user_stake_count: Option<UncheckedAccount<'info>>,
+    
+    /// CHECK: the position owner's UserStats PDA, checked in update_user_stats
+    #[account(mut)]
+    pub user_stats: Option<UncheckedAccount<'info>>,
+    
+    /// CHECK: position owner for AdminUnlock, refunded the position rent; checked in execute_action
+    #[account(mut)]
+    pub position_owner: Option<UncheckedAccount<'info>>,
+    
+    pub token_program: Option<Program<'info, Token>>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct GetBatchPositionStatus<'info> {
+    pub staking_program: Account<'info, StakingProgram>,
+    
+    pub collection_account: Account<'info, CollectionAccount>,
+}
+
+#[derive(Accounts)]
+pub struct SyncComputedMultipliers<'info> {
+    pub staking_program: Account<'info, StakingProgram>,
+    
+    #[account(mut)]
+    pub collection_account: Account<'info, CollectionAccount>,
+}
+
+#[derive(Accounts)]
+pub struct TryAutoUnpause<'info> {
+    #[account(mut)]
+    pub staking_program: Account<'info, StakingProgram>,
+    
+    pub caller: Signer<'info>,
+}
+
+#[derive(Accounts)]
+#[instruction(chain_id: u64)]
+pub struct SetRewardAddress<'info> {
+    #[account(
+        init_if_needed,
+        payer = staker,
+        space = RewardAddress::LEN,
+        seeds = [b"reward_address", staker.key().as_ref(), chain_id.to_le_bytes().as_ref()],
+        bump
+    )]
+    pub reward_address: Account<'info, RewardAddress>,
+    
+    #[account(mut)]
+    pub staker: Signer<'info>,
+    
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct ClaimTickets<'info> {
+    #[account(
+        mut,
+        constraint = staking_program.ticket_mint != Pubkey::default() @ StakingError::TicketMintNotSet
+    )]
+    pub staking_program: Account<'info, StakingProgram>,
+    
+    #[account(
+        mut,
+        constraint = staking_position.owner == user.key() @ StakingError::NotPositionOwner
+    )]
+    pub staking_position: Account<'info, StakingPosition>,
+    
+    #[account(mut, address = staking_program.ticket_mint)]
+    pub ticket_mint: Account<'info, Mint>,
+    
+    #[account(
+        mut,
+        associated_token::mint = ticket_mint,
+        associated_token::authority = user
+    )]
+    pub user_ticket_account: Account<'info, TokenAccount>,
+    
+    /// CHECK: PDA signing as the ticket mint's authority; holds no data
+    #[account(seeds = [b"ticket_mint_authority"], bump)]
+    pub ticket_mint_authority: UncheckedAccount<'info>,
+    
+    pub user: Signer<'info>,
+    
+    pub token_program: Program<'info, Token>,
+}
+
+#[derive(Accounts)]
+pub struct ComputeEntryWeight<'info> {
+    #[account(
+        constraint = collection_account.collection_mint == staking_position.collection_mint @ StakingError::CollectionNotFound
+    )]
+    pub collection_account: Account<'info, CollectionAccount>,
+    
+    pub staking_position: Account<'info, StakingPosition>,
+}
+
+#[derive(Accounts)]
+pub struct SetAdminStatus<'info> {
+    #[account(
+        constraint = staking_program.authority == authority.key() @ StakingError::Unauthorized
+    )]
+    pub staking_program: Account<'info, StakingProgram>,
+    
+    #[account(
+        mut,
+        seeds = [b"admin", admin_account.admin.as_ref()],
+        bump = admin_account.bump
+    )]
+    pub admin_account: Account<'info, AdminAccount>,
+    
+    pub authority: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct UnstakeEarly<'info> {
+    #[account(mut)]
+    pub staking_program: Account<'info, StakingProgram>,
+    
+    #[account(
+        mut,
+        constraint = collection_account.collection_mint == staking_position.collection_mint @ StakingError::CollectionNotFound
+    )]
+    pub collection_account: Account<'info, CollectionAccount>,
+    
+    #[account(
+        mut,
+        close = user,
+        constraint = staking_position.owner == user.key() @ StakingError::NotPositionOwner
+    )]
+    pub staking_position: Account<'info, StakingPosition>,
+    
+    // As in ClaimNft
+    #[account(
+        init_if_needed,
+        payer = user,
+        space = UserStakeCount::LEN,
+        seeds = [b"user_stake_count", staking_position.collection_mint.as_ref(), user.key().as_ref()],
+        bump
+    )]
+    pub user_stake_count: Account<'info, UserStakeCount>,
+    
+    // As in ClaimNft
+    #[account(
+        init_if_needed,
+        payer = user,
+        space = UserStats::LEN,
+        seeds = [b"user_stats", user.key().as_ref()],
+        bump
+    )]
+    pub user_stats: Account<'info, UserStats>,
+    
+    #[account(
+        mut,
+        constraint = user_token_account.owner == user.key() @ StakingError::InvalidRecipient,
+        constraint = user_token_account.mint == staking_position.nft_mint @ StakingError::InvalidRecipient
+    )]
+    pub user_token_account: Account<'info, TokenAccount>,
+    
+    #[account(
+        mut,
+        constraint = program_token_account.owner == staking_program.key() @ StakingError::InvalidRecipient,
+        constraint = program_token_account.mint == staking_position.nft_mint @ StakingError::InvalidRecipient
+    )]
+    pub program_token_account: Account<'info, TokenAccount>,
+    
+    // Payout accounts for the settled tickets left after the penalty: the ticket_mint
+    // three when a ticket mint is set, otherwise the vault two when a reward token is set
+    #[account(mut, address = staking_program.ticket_mint)]
+    pub ticket_mint: Option<Account<'info, Mint>>,
+    
+    #[account(
+        mut,
+        constraint = user_ticket_account.owner == user.key() @ StakingError::InvalidRecipient,
+        constraint = user_ticket_account.mint == staking_program.ticket_mint @ StakingError::InvalidRecipient
+    )]
+    pub user_ticket_account: Option<Account<'info, TokenAccount>>,
+    
+    /// CHECK: PDA signing as the ticket mint's authority; holds no data
+    #[account(seeds = [b"ticket_mint_authority"], bump)]
+    pub ticket_mint_authority: Option<UncheckedAccount<'info>>,
+    
+    #[account(mut, seeds = [b"reward_vault"], bump)]
+    pub reward_vault: Option<Account<'info, TokenAccount>>,
+    
+    #[account(
+        mut,
+        constraint = user_reward_account.owner == user.key() @ StakingError::InvalidRecipient,
+        constraint = user_reward_account.mint == staking_program.reward_token_mint @ StakingError::InvalidRecipient
+    )]
+    pub user_reward_account: Option<Account<'info, TokenAccount>>,
+    
+    #[account(mut)]
+    pub user: Signer<'info>,
+    
+    pub token_program: Program<'info, Token>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct GrantBonusRewards<'info> {
+    #[account(
+        mut,
+        constraint = staking_program.authority == authority.key() @ StakingError::Unauthorized
+    )]
+    pub staking_program: Account<'info, StakingProgram>,
+    
+    pub authority: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct ExtendDuration<'info> {
+    #[account(mut)]
+    pub staking_program: Account<'info, StakingProgram>,
+    
+    #[account(
+        mut,
+        constraint = collection_account.collection_mint == staking_position.collection_mint @ StakingError::CollectionNotFound
+    )]
+    pub collection_account: Account<'info, CollectionAccount>,
+    
+    #[account(
+        mut,
+        constraint = staking_position.owner == user.key() @ StakingError::NotPositionOwner
+    )]
+    pub staking_position: Account<'info, StakingPosition>,
+    
+    // As in ClaimNft
+    #[account(
+        init_if_needed,
+        payer = user,
+        space = UserStats::LEN,
+        seeds = [b"user_stats", user.key().as_ref()],
+        bump
+    )]
+    pub user_stats: Account<'info, UserStats>,
+    
+    #[account(mut)]
+    pub user: Signer<'info>,
+    
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct SweepStalePositions<'info> {
+    #[account(mut)]
+    pub staking_program: Account<'info, StakingProgram>,
+    
+    #[account(mut)]
+    pub collection_account: Account<'info, CollectionAccount>,
+}
+
+#[derive(Accounts)]
+#[instruction(new_owner: Pubkey)]
+pub struct TransferPosition<'info> {
+    #[account(
+        constraint = collection_account.collection_mint == staking_position.collection_mint @ StakingError::CollectionNotFound
+    )]
+    pub collection_account: Account<'info, CollectionAccount>,
+    
+    #[account(
+        mut,
+        constraint = staking_position.owner == owner.key() @ StakingError::NotPositionOwner
+    )]
+    pub staking_position: Account<'info, StakingPosition>,
+    
+    // Counters the position moves between; created for wallets that have none yet
+    #[account(
+        init_if_needed,
+        payer = owner,
+        space = UserStakeCount::LEN,
+        seeds = [b"user_stake_count", collection_account.collection_mint.as_ref(), owner.key().as_ref()],
+        bump
+    )]
+    pub owner_stake_count: Account<'info, UserStakeCount>,
+    
+    #[account(
+        init_if_needed,
+        payer = owner,
+        space = UserStakeCount::LEN,
+        seeds = [b"user_stake_count", collection_account.collection_mint.as_ref(), new_owner.as_ref()],
+        bump
+    )]
+    pub new_owner_stake_count: Account<'info, UserStakeCount>,
+    
+    #[account(
+        init_if_needed,
+        payer = owner,
+        space = UserStats::LEN,
+        seeds = [b"user_stats", owner.key().as_ref()],
+        bump
+    )]
+    pub owner_stats: Account<'info, UserStats>,
+    
+    #[account(
+        init_if_needed,
+        payer = owner,
+        space = UserStats::LEN,
+        seeds = [b"user_stats", new_owner.as_ref()],
+        bump
+    )]
+    pub new_owner_stats: Account<'info, UserStats>,
+    
+    #[account(mut)]
+    pub owner: Signer<'info>,
+    
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct GetMultiplierBreakdown<'info> {
+    #[account(
+        constraint = collection_account.collection_mint == staking_position.collection_mint @ StakingError::CollectionNotFound
+    )]
+    pub collection_account: Account<'info, CollectionAccount>,
+    
+    pub staking_position: Account<'info, StakingPosition>,
+}
+
+#[derive(Accounts)]
+pub struct StakeNftBatch<'info> {
+    #[account(mut)]
+    pub staking_program: Account<'info, StakingProgram>,
+    
+    #[account(mut)]
+    pub collection_account: Account<'info, CollectionAccount>,
+    
+    #[account(
+        init_if_needed,
+        payer = user,
+        space = UserStakeCount::LEN,
+        seeds = [b"user_stake_count", collection_account.collection_mint.as_ref(), user.key().as_ref()],
+        bump
+    )]
+    pub user_stake_count: Account<'info, UserStakeCount>,
+    
+    #[account(
+        init_if_needed,
+        payer = user,
+        space = UserStats::LEN,
+        seeds = [b"user_stats", user.key().as_ref()],
+        bump
+    )]
+    pub user_stats: Account<'info, UserStats>,
+    
+    #[account(mut, address = staking_program.treasury @ StakingError::InvalidRecipient)]
+    pub treasury: SystemAccount<'info>,
+    
+    #[account(mut)]
+    pub user: Signer<'info>,
+    
+    pub token_program: Program<'info, Token>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct GetPendingRewards<'info> {
+    #[account(
+        constraint = collection_account.collection_mint == staking_position.collection_mint @ StakingError::CollectionNotFound
+    )]
+    pub collection_account: Account<'info, CollectionAccount>,
+    
+    pub staking_position: Account<'info, StakingPosition>,
+}
+
+#[derive(Accounts)]
+pub struct CheckEmergencyReady<'info> {
+    pub emergency_request: Account<'info, EmergencyRequest>,
+}
+
+#[derive(Accounts)]
+pub struct FundRewardVault<'info> {
+    #[account(
+        mut,
+        constraint = staking_program.reward_token_mint != Pubkey::default() @ StakingError::RewardVaultNotSet
+    )]
+    pub staking_program: Account<'info, StakingProgram>,
+    
+    #[account(address = staking_program.reward_token_mint)]
+    pub reward_mint: Account<'info, Mint>,
+    
+    // Token account owned by the staking program PDA, so only the program can move funds out
+    #[account(
+        init_if_needed,
+        payer = admin,
+        token::mint = reward_mint,
+        token::authority = staking_program,
+        seeds = [b"reward_vault"],
+        bump
+    )]
+    pub reward_vault: Account<'info, TokenAccount>,
+    
+    #[account(
+        mut,
+        constraint = admin_token_account.mint == reward_mint.key() @ StakingError::InvalidRecipient
+    )]
+    pub admin_token_account: Account<'info, TokenAccount>,
+    
+    #[account(
+        constraint = admin_account.admin == admin.key() @ StakingError::Unauthorized,
+        constraint = admin_account.is_active @ StakingError::Unauthorized
+    )]
+    pub admin_account: Account<'info, AdminAccount>,
+    
+    #[account(mut)]
+    pub admin: Signer<'info>,
+    
+    pub token_program: Program<'info, Token>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct WithdrawUnallocated<'info> {
+    #[account(
+        mut,
+        constraint = staking_program.authority == authority.key() @ StakingError::Unauthorized
+    )]
+    pub staking_program: Account<'info, StakingProgram>,
+    
+    #[account(mut, seeds = [b"reward_vault"], bump)]
+    pub reward_vault: Account<'info, TokenAccount>,
+    
+    #[account(
+        mut,
+        constraint = authority_token_account.mint == staking_program.reward_token_mint @ StakingError::InvalidRecipient
+    )]
+    pub authority_token_account: Account<'info, TokenAccount>,
+    
+    pub authority: Signer<'info>,
+    
+    pub token_program: Program<'info, Token>,
+}
+
+#[derive(Accounts)]
+pub struct ClaimVaultRewards<'info> {
+    #[account(
+        mut,
+        constraint = staking_program.reward_token_mint != Pubkey::default() @ StakingError::RewardVaultNotSet
+    )]
+    pub staking_program: Account<'info, StakingProgram>,
+    
+    #[account(
+        mut,
+        constraint = staking_position.owner == user.key() @ StakingError::NotPositionOwner
+    )]
+    pub staking_position: Account<'info, StakingPosition>,
+    
+    #[account(mut, seeds = [b"reward_vault"], bump)]
+    pub reward_vault: Account<'info, TokenAccount>,
+    
+    #[account(
+        mut,
+        constraint = user_reward_account.owner == user.key() @ StakingError::InvalidRecipient,
+        constraint = user_reward_account.mint == staking_program.reward_token_mint @ StakingError::InvalidRecipient
+    )]
+    pub user_reward_account: Account<'info, TokenAccount>,
+    
+    pub user: Signer<'info>,
+    
+    pub token_program: Program<'info, Token>,
+}
+
+#[derive(Accounts)]
+pub struct CancelEmergencyUnlock<'info> {
+    #[account(
+        constraint = signer.key() == emergency_request.requester || signer.key() == staking_program.authority @ StakingError::Unauthorized
+    )]
+    pub staking_program: Account<'info, StakingProgram>,
+    
+    pub staking_position: Account<'info, StakingPosition>,
+    
+    #[account(
+        mut,
+        close = requester,
+        seeds = [b"emergency_request", staking_position.key().as_ref()],
+        bump = emergency_request.bump,
+        constraint = !emergency_request.executed @ StakingError::EmergencyRequestAlreadyExecuted
+    )]
+    pub emergency_request: Account<'info, EmergencyRequest>,
+    
+    #[account(mut, seeds = [b"active_emergency_requests"], bump = active_emergency_requests.bump)]
+    pub active_emergency_requests: Account<'info, ActiveEmergencyRequests>,
+    
+    /// CHECK: Original requester, refunded the request rent
+    #[account(mut, address = emergency_request.requester @ StakingError::InvalidRecipient)]
+    pub requester: AccountInfo<'info>,
+    
+    pub signer: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct SetCollectionActive<'info> {
+    #[account(
+        constraint = staking_program.authority == authority.key() @ StakingError::Unauthorized
+    )]
+    pub staking_program: Account<'info, StakingProgram>,
+    
+    #[account(mut)]
+    pub collection_account: Account<'info, CollectionAccount>,
+    
+    pub authority: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct Relock<'info> {
+    #[account(mut)]
+    pub staking_program: Account<'info, StakingProgram>,
+    
+    #[account(
+        mut,
+        constraint = collection_account.collection_mint == staking_position.collection_mint @ StakingError::CollectionNotFound
+    )]
+    pub collection_account: Account<'info, CollectionAccount>,
+    
+    #[account(
+        mut,
+        constraint = staking_position.owner == user.key() @ StakingError::NotPositionOwner
+    )]
+    pub staking_position: Account<'info, StakingPosition>,
+    
+    // Counts a swept position again
+    #[account(
+        init_if_needed,
+        payer = user,
+        space = UserStakeCount::LEN,
+        seeds = [b"user_stake_count", staking_position.collection_mint.as_ref(), user.key().as_ref()],
+        bump
+    )]
+    pub user_stake_count: Account<'info, UserStakeCount>,
+    
+    // As in ClaimNft
+    #[account(
+        init_if_needed,
+        payer = user,
+        space = UserStats::LEN,
+        seeds = [b"user_stats", user.key().as_ref()],
+        bump
+    )]
+    pub user_stats: Account<'info, UserStats>,
+    
+    // Receives the claim fee, as in ClaimRewards
+    #[account(mut, address = staking_program.treasury @ StakingError::InvalidRecipient)]
+    pub treasury: SystemAccount<'info>,
+    
+    #[account(mut)]
+    pub user: Signer<'info>,
+    
+    pub system_program: Program<'info, System>,
+}
+
+// Events
+#[event]
+pub struct NftStaked {
+    pub user: Pubkey,
+    pub nft_mint: Pubkey,
+    pub collection_mint: Pubkey,
+    pub duration: u8,
+    pub unlock_at: i64,
+    // Multiplier shown to the user at stake time; equal to the position's locked multiplier
+    pub display_multiplier: u64,
+    // Whether that multiplier came from the program's computed ladder
+    pub multiplier_computed: bool,
+}
+
+#[event]
+pub struct NftClaimed {
+    pub user: Pubkey,
+    pub nft_mint: Pubkey,
+    pub collection_mint: Pubkey,
+    // Snapshot of the position before it is closed, so indexers can derive the realized
+    // rate (total_rewards_earned over actual_duration_held) without reading it first
+    pub total_rewards_earned: u64,
+    pub staked_at: i64,
+    pub unlock_at: i64,
+    pub actual_duration_held: i64,
+}
+
+#[event]
+pub struct EmergencyUnlock {
+    pub admin: Pubkey,
+    pub user: Pubkey,
+    pub nft_mint: Pubkey,
+    pub reason: String,
+}
+
+#[event]
+pub struct CollectionAdded {
+    pub collection_mint: Pubkey,
+    pub six_month_tickets: u64,
+    pub twelve_month_tickets: u64,
+    pub three_year_tickets: u64,
+    // Lamports paid to the treasury for onboarding; 0 for the authority
+    pub onboarding_fee: u64,
+}
+
+#[event]
+pub struct CollectionUpdated {
+    pub collection_mint: Pubkey,
+    pub six_month_tickets: u64,
+    pub twelve_month_tickets: u64,
+    pub three_year_tickets: u64,
+}
+
+#[event]
+pub struct SecurityViolation {
+    pub violation_type: String,
+    pub violator: Pubkey,
+    pub details: String,
+}
+
+#[event]
+pub struct AdminAction {
+    pub admin: Pubkey,
+    pub action: String,
+    pub data: String,
+}
+
+#[event]
+pub struct EmergencyAction {
+    pub admin: Pubkey,
+    pub action: String,
+    pub reason: String,
+}
+
+#[event]
+pub struct UnlockTimeRemaining {
+    pub nft_mint: Pubkey,
+    pub unlock_at: i64,
+    pub days_remaining: u64,
+    pub hours_remaining: u64,
+    pub is_unlocked: bool,
+}
+
+#[event]
+pub struct RewardsClaimed {
+    pub user: Pubkey,
+    pub nft_mint: Pubkey,
+    pub collection_mint: Pubkey,
+    pub amount: u64,
+    pub total_rewards_earned: u64,
+    // Multiplier this claim's accrual settled at: the position's locked multiplier (not the
+    // collection's current config) after the floor, bonuses and dynamic scaling
+    pub settlement_multiplier: u64,
+    pub cliff_passed: bool,
+    pub fee_lamports: u64,
+    pub distribution_curve: u8,
+    // Bonus included in settlement_multiplier; 0 when the position isn't eligible
+    pub early_adopter_bonus_bps: u16,
+    // Pending-validation boost included in settlement_multiplier; 0 once validated
+    pub pending_validation_boost_bps: u16,
+    // Loyalty rate of the latest full month past unlock (see loyalty_rewards); 0 before
+    pub loyalty_bonus_bps: u16,
+    pub accrual_start: i64,
+    // Tickets forfeited to the early-claim penalty
+    pub penalty: u64,
+    // Treasury match credited on top of `amount` (already included in total_rewards_earned)
+    pub matched: u64,
+    // Earliest time of the next claim on this position (0 = no cooldown)
+    pub next_claim_at: i64,
+    // Whether accrued rewards were rounded up (true) or down
+    pub favor_user: bool,
+}
+
+#[event]
+pub struct RewardPoolDepleted {
+    pub collection_mint: Pubkey,
+    pub reward_pool: u64,
+}
+
+#[event]
+pub struct StakeCost {
+    pub position_rent: u64,
+    // Rent of the UserStakeCount and UserStats accounts the stake would create
+    pub counter_rent: u64,
+    pub stake_fee_lamports: u64,
+    pub total_lamports: u64,
+}
+
+#[event]
+pub struct PausedInstructionsUpdated {
+    pub admin: Pubkey,
+    pub paused_instructions: u32,
+}
+
+#[event]
+pub struct PositionMigrated {
+    pub admin: Pubkey,
+    pub owner: Pubkey,
+    pub nft_mint: Pubkey,
+    pub collection_mint: Pubkey,
+    pub duration: u8,
+    pub staked_at: i64,
+    pub unlock_at: i64,
+}
+
+#[event]
+pub struct NftVerified {
+    pub nft_mint: Pubkey,
+    pub collection_mint: Pubkey,
+    pub verifier: Pubkey,
+}
+
+#[event]
+pub struct TierUpgraded {
+    pub admin: Pubkey,
+    pub nft_mint: Pubkey,
+    pub old_tier: u8,
+    pub new_tier: u8,
+    pub reason: String,
+}
+
+#[event]
+pub struct ActiveEmergencyRequestsListed {
+    pub requests: Vec<Pubkey>,
+}
+
+#[event]
+pub struct ClaimDelegateUpdated {
+    pub owner: Pubkey,
+    pub nft_mint: Pubkey,
+    pub delegate: Option<Pubkey>,
+}
+
+#[event]
+pub struct ProgramLiability {
+    pub collection_mint: Pubkey,
+    pub positions_counted: u32,
+    pub liability: u64,
+}
+
+// Reason codes: see the REJECT_* constants
+#[event]
+pub struct StakeRejected {
+    pub user: Pubkey,
+    pub collection_mint: Pubkey,
+    pub reason: u8,
+}
+
+#[event]
+pub struct RewardsStaked {
+    pub user: Pubkey,
+    pub nft_mint: Pubkey,
+    pub amount: u64,
+    pub duration: u8,
+    pub unlock_at: i64,
+    pub multiplier: u64,
+}
+
+#[event]
+pub struct RewardStakeClaimed {
+    pub user: Pubkey,
+    pub nft_mint: Pubkey,
+    pub principal: u64,
+    pub payout: u64,
+}
+
+#[event]
+pub struct RewardSnapshotTaken {
+    pub snapshot_id: u64,
+    pub user: Pubkey,
+    pub total_rewards_earned: u64,
+    pub positions_counted: u32,
+    pub taken_at: i64,
+}
+
+#[event]
+pub struct PositionTransferred {
+    pub nft_mint: Pubkey,
+    pub from: Pubkey,
+    pub to: Pubkey,
+}
+
+#[event]
+pub struct PositionsBatchTransferred {
+    pub collection_mint: Pubkey,
+    pub from: Pubkey,
+    pub to: Pubkey,
+    pub count: u32,
+}
+
+#[event]
+pub struct PositionVersionUpgraded {
+    pub nft_mint: Pubkey,
+    pub from_version: u8,
+    pub to_version: u8,
+}
+
+#[event]
+pub struct ClaimEligibility {
+    pub nft_mint: Pubkey,
+    pub claimer: Pubkey,
+    pub can_claim: bool,
+    pub reason: u8,
+    pub unlock_at: i64,
+}
+
+#[event]
+pub struct PositionAccrualPaused {
+    pub admin: Pubkey,
+    pub nft_mint: Pubkey,
+    pub paused_at: i64,
+}
+
+#[event]
+pub struct PositionAccrualResumed {
+    pub admin: Pubkey,
+    pub nft_mint: Pubkey,
+    pub paused_for: i64,
+    pub accrual_paused_total: i64,
+}
+
+#[event]
+pub struct MultiplierFloored {
+    pub nft_mint: Pubkey,
+    pub locked_multiplier: u64,
+    pub floored_multiplier: u64,
+}
+
+#[event]
+pub struct VaultAudit {
+    pub nft_mint: Pubkey,
+    pub vault: Pubkey,
+    pub vault_amount: u64,
+    pub expected: u64,
+    pub surplus: u64,
+    pub deficit: u64,
+}
+
+#[event]
+pub struct CollectionMintMigrated {
+    pub old_mint: Pubkey,
+    pub new_mint: Pubkey,
+    pub positions_updated: u32,
+}
+
+#[event]
+pub struct RewardsCredited {
+    pub nft_mint: Pubkey,
+    pub owner: Pubkey,
+    pub amount: u64,
+    pub total_rewards_earned: u64,
+    pub nonce: u64,
+}
+
+#[event]
+pub struct MintBlocked {
+    pub admin: Pubkey,
+    pub nft_mint: Pubkey,
+}
+
+#[event]
+pub struct MintUnblocked {
+    pub admin: Pubkey,
+    pub nft_mint: Pubkey,
+}
+
+#[event]
+pub struct PositionPda {
+    pub nft_mint: Pubkey,
+    pub user: Pubkey,
+    pub position: Pubkey,
+    pub bump: u8,
+}
+
+#[event]
+pub struct UnpauseApprovalProgress {
+    pub admin: Pubkey,
+    pub paused_at: i64,
+    pub approvals: u8,
+    pub threshold: u8,
+}
+
+#[event]
+pub struct CollectionValidated {
+    pub collection_mint: Pubkey,
+    pub validated: bool,
+    // 0 when the validation doesn't expire
+    pub validation_expires_at: i64,
+}
+
+#[event]
+pub struct StakedNftSwapped {
+    pub owner: Pubkey,
+    pub collection_mint: Pubkey,
+    pub old_nft_mint: Pubkey,
+    pub new_nft_mint: Pubkey,
+}
+
+#[event]
+pub struct ActionProposed {
+    pub proposal: Pubkey,
+    pub proposer: Pubkey,
+    pub action_hash: [u8; 32],
+    pub threshold: u8,
+}
+
+#[event]
+pub struct ActionConfirmed {
+    pub proposal: Pubkey,
+    pub admin: Pubkey,
+    pub confirmations: u8,
+    pub threshold: u8,
+}
+
+#[event]
+pub struct ActionExecuted {
+    pub proposal: Pubkey,
+    pub executor: Pubkey,
+    pub action_hash: [u8; 32],
+}
+
+#[event]
+pub struct BatchPositionStatus {
+    pub collection_mint: Pubkey,
+    pub statuses: Vec<PositionStatus>,
+}
+
+#[event]
+pub struct MultipliersDerived {
+    pub collection_mint: Pubkey,
+    pub six_month_multiplier: u64,
+    pub twelve_month_multiplier: u64,
+    pub three_year_multiplier: u64,
+}
+
+#[event]
+pub struct RewardAddressSet {
+    pub staker: Pubkey,
+    pub chain_id: u64,
+    pub address: Vec<u8>,
+}
+
+// Accrual brought up to date by claim_rewards. `accrued` is the position's accrual at
+// `accrued_at` (the new settlement watermark) and `settled` what this claim added to
+// total_rewards_earned, match included.
+#[event]
+pub struct RewardsAccrued {
+    pub nft_mint: Pubkey,
+    pub accrued: u64,
+    pub settled: u64,
+    pub total_rewards_earned: u64,
+    pub accrued_at: i64,
+}
+
+#[event]
+pub struct TicketsClaimed {
+    pub user: Pubkey,
+    pub nft_mint: Pubkey,
+    pub amount: u64,
+}
+
+#[event]
+pub struct CollectionRewardsActivated {
+    pub collection_mint: Pubkey,
+    pub total_staked: u64,
+    pub threshold: u64,
+}
+
+#[event]
+pub struct EarlyUnstake {
+    pub user: Pubkey,
+    pub nft_mint: Pubkey,
+    // Accrued but unsettled tickets given up
+    pub forfeited_rewards: u64,
+    // Settled tickets clawed back by the early-unstake penalty
+    pub penalty: u64,
+    // Settled tickets minted or paid from the vault on the way out (0 when they are
+    // credited off-chain)
+    pub paid_out: u64,
+}
+
+#[event]
+pub struct BonusRewardsGranted {
+    pub owner: Pubkey,
+    pub nft_mint: Pubkey,
+    pub amount: u64,
+    pub total_rewards_earned: u64,
+    pub reason: String,
+}
+
+#[event]
+pub struct DurationExtended {
+    pub nft_mint: Pubkey,
+    pub old_duration: u8,
+    pub new_duration: u8,
+    pub unlock_at: i64,
+    pub settled_rewards: u64,
+}
+
+#[event]
+pub struct StalePositionsSwept {
+    pub collection_mint: Pubkey,
+    pub positions_swept: u32,
+    // Collection's total_staked after the sweep
+    pub total_staked: u64,
+}
+
+#[event]
+pub struct MultiplierBreakdown {
+    pub nft_mint: Pubkey,
+    // Multiplier captured at stake time (raised by tier upgrades or extensions)
+    pub locked_multiplier: u64,
+    // Collection floor and the locked multiplier after applying it
+    pub min_multiplier_bps: u64,
+    pub floored_multiplier: u64,
+    // Bonuses applied on top of the floored multiplier; 0 when not applicable
+    pub early_adopter_bonus_bps: u16,
+    pub pending_validation_boost_bps: u16,
+    // Loyalty rate for time held past unlock; accrued on its own, not in the multiplier
+    pub loyalty_bonus_bps: u16,
+    // Final multiplier rewards settle at
+    pub settlement_multiplier: u64,
+}
+
+#[event]
+pub struct EmergencyReadiness {
+    pub emergency_request: Pubkey,
+    pub requested_at: i64,
+    pub ready_at: i64,
+    pub seconds_remaining: u64,
+    pub executed: bool,
+    pub executable: bool,
+}
+
+#[event]
+pub struct RewardReceiptCreated {
+    pub receipt: Pubkey,
+    pub staking_position: Pubkey,
+    pub owner: Pubkey,
+    pub amount: u64,
+    pub claimed_at: i64,
+}
+
+#[event]
+pub struct VaultFunded {
+    pub admin: Pubkey,
+    pub amount: u64,
+    pub vault_balance: u64,
+}
+
+#[event]
+pub struct VaultWithdrawn {
+    pub authority: Pubkey,
+    pub amount: u64,
+    pub vault_balance: u64,
+}
+
+#[event]
+pub struct VaultRewardsClaimed {
+    pub user: Pubkey,
+    pub nft_mint: Pubkey,
+    pub amount: u64,
+}
+
+#[event]
+pub struct DynamicMultiplierApplied {
+    pub collection_mint: Pubkey,
+    pub nft_mint: Pubkey,
+    pub dynamic_multiplier: u64,
+    pub reward_pool_remaining: u64,
+    pub reward_pool: u64,
+}
+
+#[event]
+pub struct PositionRelocked {
+    pub nft_mint: Pubkey,
+    pub new_duration: u8,
+    pub unlock_at: i64,
+    // Rewards from the finished period settled by the relock
+    pub carried_rewards: u64,
+}
+
+// Error codes
+#[error_code]
+pub enum StakingError {
+    #[msg("Contract is paused")]
+    ContractPaused,
+    
+    #[msg("Invalid staking duration")]
+    InvalidDuration,
+    
+    #[msg("Collection not active")]
+    CollectionNotActive,
+    
+    #[msg("Position not active")]
+    PositionNotActive,
+    
+    #[msg("Not position owner")]
+    NotPositionOwner,
+    
+    #[msg("Staking period not completed")]
+    StakingPeriodNotCompleted,
+    
+    #[msg("Reason required for emergency action")]
+    ReasonRequired,
+    
+    #[msg("Emergency delay not met")]
+    EmergencyDelayNotMet,
+    
+    #[msg("Emergency request already executed")]
+    EmergencyRequestAlreadyExecuted,
+    
+    #[msg("Unauthorized access")]
+    Unauthorized,
+    
+    #[msg("Invalid recipient address")]
+    InvalidRecipient,
+    
+    #[msg("Collection already exists")]
+    CollectionAlreadyExists,
+    
+    #[msg("Collection not found")]
+    CollectionNotFound,
+    
+    #[msg("Insufficient multi-sig confirmations")]
+    InsufficientMultiSigConfirmations,
+    
+    #[msg("Migration window is closed")]
+    MigrationWindowClosed,
+    
+    #[msg("Invalid migration window")]
+    InvalidMigrationWindow,
+    
+    #[msg("Invalid timestamp")]
+    InvalidTimestamp,
+    
+    #[msg("Rewards below the requested minimum")]
+    SlippageExceeded,
+    
+    #[msg("Arithmetic overflow")]
+    ArithmeticOverflow,
+    
+    #[msg("Invalid amount")]
+    InvalidAmount,
+    
+    #[msg("NFT is not a verified member of the collection")]
+    NftNotInCollection,
+    
+    #[msg("Invalid NFT metadata account")]
+    InvalidMetadata,
+    
+    #[msg("Reward tier can only be upgraded")]
+    TierDowngradeNotAllowed,
+    
+    #[msg("Basis points value out of range")]
+    InvalidBasisPoints,
+    
+    #[msg("Too many pending emergency requests")]
+    EmergencyRegistryFull,
+    
+    #[msg("Batch too large")]
+    BatchTooLarge,
+    
+    #[msg("Invalid staking position account")]
+    InvalidPosition,
+    
+    #[msg("Programmable NFT transfer could not be built")]
+    PnftTransferFailed,
+    
+    #[msg("Lock duration exceeds the configured maximum")]
+    LockTooLong,
+    
+    #[msg("Not enough unstaked rewards")]
+    InsufficientRewards,
+    
+    #[msg("CPI caller whitelist is full")]
+    CpiWhitelistFull,
+    
+    #[msg("Invalid cluster clock")]
+    InvalidClock,
+    
+    #[msg("Positions in this collection cannot be transferred")]
+    PositionNotTransferable,
+    
+    #[msg("Position is already on the current layout")]
+    PositionAlreadyMigrated,
+    
+    #[msg("Not enough validated collections for staking to open")]
+    ProgramNotReady,
+    
+    #[msg("Not enough lamports to pay the claim fee")]
+    InsufficientFeeBalance,
+    
+    #[msg("Unknown reward distribution curve")]
+    InvalidDistributionCurve,
+    
+    #[msg("Invalid collection mint")]
+    InvalidCollectionMint,
+    
+    #[msg("Terms hash does not match the current terms")]
+    TermsNotAccepted,
+    
+    #[msg("Missing or invalid reward oracle signature")]
+    InvalidOracleSignature,
+    
+    #[msg("Oracle nonce already used")]
+    StaleNonce,
+    
+    #[msg("Staking is blocked for this NFT")]
+    MintBlocked,
+    
+    #[msg("Contract is not paused")]
+    ContractNotPaused,
+    
+    #[msg("Invalid approval threshold")]
+    InvalidThreshold,
+    
+    #[msg("Admin already approved")]
+    AlreadyApproved,
+    
+    #[msg("Collection is not validated")]
+    CollectionNotValidated,
+    
+    #[msg("Action hash does not match the proposed action")]
+    InvalidProposal,
+    
+    #[msg("Proposal already executed")]
+    ProposalAlreadyExecuted,
+    
+    #[msg("Account required by the proposed action is missing")]
+    MissingActionAccount,
+    
+    #[msg("Collection does not use computed multipliers")]
+    MultipliersNotComputed,
+    
+    #[msg("Pause has not reached the auto-unpause delay")]
+    AutoUnpauseNotDue,
+    
+    #[msg("Staking has not started yet")]
+    StakingNotStarted,
+    
+    #[msg("Chain is not supported for reward attribution")]
+    UnsupportedChain,
+    
+    #[msg("External address is empty or too long")]
+    InvalidExternalAddress,
+    
+    #[msg("No ticket mint is configured")]
+    TicketMintNotSet,
+    
+    #[msg("Collection has not reached its reward activation threshold")]
+    CollectionNotActivated,
+    
+    #[msg("Position has rewards locked in a reward stake")]
+    RewardsStillStaked,
+    
+    #[msg("Claim cooldown has not elapsed")]
+    ClaimCooldownActive,
+    
+    #[msg("The program authority cannot be removed as an admin")]
+    CannotRemoveAuthority,
+    
+    #[msg("Position is already unlocked; use claim_nft")]
+    AlreadyUnlocked,
+    
+    #[msg("Bonus amount is zero or above the per-position cap")]
+    BonusTooLarge,
+    
+    #[msg("Reason is too long or contains control characters")]
+    InvalidReason,
+    
+    #[msg("Collection has reached its maximum number of staked NFTs")]
+    CollectionStakeCapReached,
+    
+    #[msg("Wallet has reached the per-user staking limit for this collection")]
+    UserStakeLimitReached,
+    
+    #[msg("Mint is not an NFT (decimals must be 0 and supply 1) or the account doesn't hold it")]
+    NotAnNft,
+    
+    #[msg("A reward receipt already exists for this claim")]
+    RewardReceiptExists,
+    
+    #[msg("Reward token vault is not configured")]
+    RewardVaultNotSet,
+    
+    #[msg("Reward vault must be empty to change its token")]
+    VaultNotEmpty,
+    
+    #[msg("Reward vault balance is too low")]
+    InsufficientVaultBalance,
+    
+    #[msg("Wallet does not meet the staking eligibility requirement")]
+    AccountNotEligible,
+    
+    #[msg("Collection has no reward pool")]
+    RewardPoolNotConfigured,
+    
+    #[msg("Collection rewards are already frozen")]
+    CollectionRewardsFrozen,
+    
+    #[msg("Daily ticket emission cap reached; try again when the window rolls over")]
+    DailyEmissionCapReached,
+    
+    #[msg("Position has settled tickets that are not yet minted or paid out")]
+    TicketsNotPaidOut,
+    #[msg("Per-user counter account does not belong to the position's owner")]
+    InvalidUserCounter,
+    #[msg("Collection has reached the limit of recorded deactivations")]
+    InactivePeriodsFull,
+    #[msg("Position has a pending emergency unlock request")]
+    EmergencyRequestPending,
+    #[msg("Collection validation has not expired")]
+    ValidationNotExpired,
+    #[msg("Position was already counted in this or a later snapshot")]
+    PositionAlreadySnapshotted,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::naffles_staking::*;
+    use super::*;
+    use anchor_lang::solana_program::entrypoint::{ProgramResult, MAX_PERMITTED_DATA_INCREASE, SUCCESS};
+    use anchor_lang::solana_program::instruction::Instruction;
+    use anchor_lang::solana_program::native_token::LAMPORTS_PER_SOL;
+    use anchor_lang::solana_program::program_stubs::{set_syscall_stubs, SyscallStubs};
+    use anchor_lang::solana_program::sysvar::instructions::{
+        construct_instructions_data, BorrowedAccountMeta, BorrowedInstruction,
+    };
+    use anchor_lang::InstructionData;
+    use anchor_lang::Event;
+    use std::cell::{Cell, RefCell};
+    use std::sync::Once;
+    
+    const T0: i64 = 1_700_000_000;
+    
+    fn collection() -> CollectionAccount {
+        CollectionAccount::new(Pubkey::new_unique(), [1_000, 2_000, 6_000], 255)
+    }
+    
+    // A six-month position staked at T0 whose multiplier is 1x, so a full term pays
+    // exactly the tier's tickets
+    fn position(collection: &CollectionAccount) -> StakingPosition {
+        let mut position = StakingPositionV0 {
+            owner: Pubkey::new_unique(),
+            nft_mint: Pubkey::new_unique(),
+            collection_mint: collection.collection_mint,
+            staked_at: T0,
+            unlock_at: T0 + SIX_MONTHS,
+            duration: 0,
+            is_active: true,
+            total_rewards_earned: 0,
+            bump: 255,
+        }
+        .upgrade(collection)
+        .unwrap();
+        position.locked_multiplier = BASIS_POINTS;
+        position
+    }
+    
+    // In-process runtime for instruction tests. Accounts live in leaked buffers laid out
+    // the way the loader serializes them (data length word before the data, original
+    // length before the key), so realloc and `close` work; the clock comes from NOW, and
+    // CPIs into the system and token programs are emulated.
+    thread_local! {
+        static NOW: Cell<i64> = const { Cell::new(T0) };
+        // Events logged by successful instructions, oldest first
+        static EVENTS: RefCell<Vec<Vec<u8>>> = const { RefCell::new(Vec::new()) };
+    }
+    
+    struct TestSyscalls;
+    
+    impl SyscallStubs for TestSyscalls {
+        fn sol_log(&self, _message: &str) {}
+        
+        fn sol_log_data(&self, fields: &[&[u8]]) {
+            EVENTS.with(|events| events.borrow_mut().extend(fields.iter().map(|field| field.to_vec())));
+        }
+        
+        fn sol_get_clock_sysvar(&self, var_addr: *mut u8) -> u64 {
+            let clock = Clock {
+                unix_timestamp: NOW.with(Cell::get),
+                ..Clock::default()
+            };
+            unsafe { *(var_addr as *mut Clock) = clock };
+            SUCCESS
+        }
+        
+        fn sol_get_rent_sysvar(&self, var_addr: *mut u8) -> u64 {
+            unsafe { *(var_addr as *mut Rent) = Rent::default() };
+            SUCCESS
+        }
+        
+        fn sol_invoke_signed(
+            &self,
+            instruction: &Instruction,
+            account_infos: &[AccountInfo],
+            _signers_seeds: &[&[&[u8]]],
+        ) -> ProgramResult {
+            emulate_cpi(instruction, account_infos)
+        }
+    }
+    
+    fn emulate_cpi(instruction: &Instruction, account_infos: &[AccountInfo]) -> ProgramResult {
+        let account = |index: usize| {
+            let key = instruction.accounts[index].pubkey;
+            account_infos
+                .iter()
+                .find(|info| *info.key == key)
+                .ok_or(ProgramError::NotEnoughAccountKeys)
+        };
+        let data = &instruction.data;
+        let u64_at = |offset: usize| u64::from_le_bytes(data[offset..offset + 8].try_into().unwrap());
+        let pubkey_at = |offset: usize| Pubkey::try_from(&data[offset..offset + 32]).unwrap();
+        
+        if instruction.program_id == system_program::ID {
+            match u32::from_le_bytes(data[0..4].try_into().unwrap()) {
+                // CreateAccount { lamports, space, owner }
+                0 => {
+                    move_lamports(account(0)?, account(1)?, u64_at(4))?;
+                    account(1)?.realloc(u64_at(12) as usize, true)?;
+                    account(1)?.assign(&pubkey_at(20));
+                }
+                // Assign { owner }
+                1 => account(0)?.assign(&pubkey_at(4)),
+                // Transfer { lamports }
+                2 => move_lamports(account(0)?, account(1)?, u64_at(4))?,
+                // Allocate { space }
+                8 => account(0)?.realloc(u64_at(4) as usize, true)?,
+                _ => return Err(ProgramError::InvalidInstructionData),
+            }
+        } else if instruction.program_id == anchor_spl::token::ID {
+            let amount = u64_at(1) as i128;
+            match data[0] {
+                // Transfer { amount }: source, destination, authority
+                3 => {
+                    add_to_u64(account(0)?, TOKEN_AMOUNT_OFFSET, -amount)?;
+                    add_to_u64(account(1)?, TOKEN_AMOUNT_OFFSET, amount)?;
+                }
+                // MintTo { amount }: mint, destination, authority
+                7 => {
+                    add_to_u64(account(0)?, MINT_SUPPLY_OFFSET, amount)?;
+                    add_to_u64(account(1)?, TOKEN_AMOUNT_OFFSET, amount)?;
+                }
+                _ => return Err(ProgramError::InvalidInstructionData),
+            }
+        }
+        Ok(())
+    }
+    
+    const TOKEN_AMOUNT_OFFSET: usize = 64;
+    const MINT_SUPPLY_OFFSET: usize = 36;
+    
+    fn move_lamports(from: &AccountInfo, to: &AccountInfo, lamports: u64) -> ProgramResult {
+        let remaining = from.lamports().checked_sub(lamports).ok_or(ProgramError::InsufficientFunds)?;
+        **from.try_borrow_mut_lamports()? = remaining;
+        **to.try_borrow_mut_lamports()? += lamports;
+        Ok(())
+    }
+    
+    fn add_to_u64(info: &AccountInfo, offset: usize, delta: i128) -> ProgramResult {
+        let mut data = info.try_borrow_mut_data()?;
+        let value = u64::from_le_bytes(data[offset..offset + 8].try_into().unwrap()) as i128 + delta;
+        let value = u64::try_from(value).map_err(|_| ProgramError::InsufficientFunds)?;
+        data[offset..offset + 8].copy_from_slice(&value.to_le_bytes());
+        Ok(())
+    }
+    
+    #[repr(C)]
+    struct KeySlot {
+        original_data_len: u32,
+        key: Pubkey,
+    }
+    
+    struct TestEnv {
+        accounts: HashMap<Pubkey, AccountInfo<'static>>,
+    }
+    
+    impl TestEnv {
+        fn new() -> Self {
+            static SYSCALLS: Once = Once::new();
+            SYSCALLS.call_once(|| {
+                set_syscall_stubs(Box::new(TestSyscalls));
+            });
+            set_time(T0);
+            
+            let mut env = Self { accounts: HashMap::new() };
+            for program in [
+                crate::ID,
+                system_program::ID,
+                anchor_spl::token::ID,
+                anchor_spl::associated_token::ID,
+                mpl_token_metadata::ID,
+            ] {
+                env.put(program, 1, Pubkey::default(), &[], true);
+            }
+            env
+        }
+        
+        fn put(&mut self, key: Pubkey, lamports: u64, owner: Pubkey, data: &[u8], executable: bool) {
+            let slot = Box::leak(Box::new(KeySlot {
+                original_data_len: data.len() as u32,
+                key,
+            }));
+            let buffer = vec![0u8; 8 + data.len() + MAX_PERMITTED_DATA_INCREASE].leak();
+            buffer[..8].copy_from_slice(&(data.len() as u64).to_le_bytes());
+            buffer[8..8 + data.len()].copy_from_slice(data);
+            let info = AccountInfo::new(
+                &slot.key,
+                false,
+                false,
+                Box::leak(Box::new(lamports)),
+                &mut buffer[8..8 + data.len()],
+                Box::leak(Box::new(owner)),
+                executable,
+                0,
+            );
+            self.accounts.insert(key, info);
+        }
+        
+        fn wallet(&mut self) -> Pubkey {
+            let key = Pubkey::new_unique();
+            self.put(key, 100 * LAMPORTS_PER_SOL, system_program::ID, &[], false);
+            key
+        }
+        
+        fn put_state<T: AccountSerialize>(&mut self, key: Pubkey, state: &T) {
+            let mut data = Vec::new();
+            state.try_serialize(&mut data).unwrap();
+            self.put(key, Rent::default().minimum_balance(data.len()), crate::ID, &data, false);
+        }
+        
+        fn put_mint(&mut self, key: Pubkey, mint_authority: Option<Pubkey>, supply: u64, decimals: u8) {
+            let mut data = [0u8; 82];
+            if let Some(mint_authority) = mint_authority {
+                data[0] = 1;
+                data[4..36].copy_from_slice(mint_authority.as_ref());
+            }
+            data[MINT_SUPPLY_OFFSET..MINT_SUPPLY_OFFSET + 8].copy_from_slice(&supply.to_le_bytes());
+            data[44] = decimals;
+            data[45] = 1;
+            self.put(key, Rent::default().minimum_balance(82), anchor_spl::token::ID, &data, false);
+        }
+        
+        fn put_token_account(&mut self, key: Pubkey, mint: Pubkey, owner: Pubkey, amount: u64) {
+            let mut data = [0u8; 165];
+            data[0..32].copy_from_slice(mint.as_ref());
+            data[32..64].copy_from_slice(owner.as_ref());
+            data[TOKEN_AMOUNT_OFFSET..TOKEN_AMOUNT_OFFSET + 8].copy_from_slice(&amount.to_le_bytes());
+            // AccountState::Initialized
+            data[108] = 1;
+            self.put(key, Rent::default().minimum_balance(165), anchor_spl::token::ID, &data, false);
+        }
+        
+        fn state<T: AccountDeserialize>(&self, key: &Pubkey) -> T {
+            T::try_deserialize(&mut &self.accounts[key].data.borrow()[..]).unwrap()
+        }
+        
+        fn update<T: AccountSerialize + AccountDeserialize>(&self, key: &Pubkey, change: impl FnOnce(&mut T)) {
+            let mut state = self.state::<T>(key);
+            change(&mut state);
+            let mut data = self.accounts[key].data.borrow_mut();
+            state.try_serialize(&mut &mut data[..]).unwrap();
+        }
+        
+        fn lamports(&self, key: &Pubkey) -> u64 {
+            self.accounts.get(key).map_or(0, |info| info.lamports())
+        }
+        
+        fn token_amount(&self, key: &Pubkey) -> u64 {
+            let data = self.accounts[key].data.borrow();
+            u64::from_le_bytes(data[TOKEN_AMOUNT_OFFSET..TOKEN_AMOUNT_OFFSET + 8].try_into().unwrap())
+        }
+        
+        fn execute(&mut self, accounts: impl ToAccountMetas, args: impl InstructionData) -> ProgramResult {
+            self.execute_with_remaining(accounts, Vec::new(), args)
+        }
+        
+        fn execute_with_remaining(
+            &mut self,
+            accounts: impl ToAccountMetas,
+            remaining_accounts: Vec<AccountMeta>,
+            args: impl InstructionData,
+        ) -> ProgramResult {
+            let mut metas = accounts.to_account_metas(None);
+            metas.extend(remaining_accounts);
+            let data = args.data();
+            let instructions_sysvar = construct_instructions_data(&[BorrowedInstruction {
+                program_id: &crate::ID,
+                accounts: metas
+                    .iter()
+                    .map(|meta| BorrowedAccountMeta {
+                        pubkey: &meta.pubkey,
+                        is_signer: meta.is_signer,
+                        is_writable: meta.is_writable,
+                    })
+                    .collect(),
+                data: &data,
+            }]);
+            self.put(sysvar::instructions::ID, 1, sysvar::ID, &instructions_sysvar, false);
+            
+            let mut infos = Vec::with_capacity(metas.len());
+            for meta in metas.iter() {
+                if !self.accounts.contains_key(&meta.pubkey) {
+                    self.put(meta.pubkey, 0, system_program::ID, &[], false);
+                }
+                let mut info = self.accounts[&meta.pubkey].clone();
+                info.is_signer = meta.is_signer;
+                info.is_writable = meta.is_writable;
+                infos.push(info);
+            }
+            
+            // A failed transaction leaves no trace, as on chain
+            let snapshot: Vec<(u64, Pubkey, Vec<u8>)> = infos
+                .iter()
+                .map(|info| (info.lamports(), *info.owner, info.data.borrow().to_vec()))
+                .collect();
+            let logged_events = EVENTS.with(|events| events.borrow().len());
+            let result = crate::entry(&crate::ID, infos.clone().leak(), &data);
+            if result.is_err() {
+                EVENTS.with(|events| events.borrow_mut().truncate(logged_events));
+                for (info, (lamports, owner, data)) in infos.iter().zip(snapshot) {
+                    **info.lamports.borrow_mut() = lamports;
+                    info.assign(&owner);
+                    info.realloc(data.len(), false).unwrap();
+                    info.data.borrow_mut().copy_from_slice(&data);
+                }
+            }
+            result
+        }
+    }
+    
+    fn set_time(current_time: i64) {
+        NOW.with(|now| now.set(current_time));
+    }
+    
+    // Every `T` event emitted so far on this thread, oldest first
+    fn events<T: Event + AnchorDeserialize>() -> Vec<T> {
+        EVENTS.with(|events| {
+            events
+                .borrow()
+                .iter()
+                .filter(|data| data.starts_with(&T::DISCRIMINATOR))
+                .map(|data| T::deserialize(&mut &data[8..]).unwrap())
+                .collect()
+        })
+    }
+    
+    fn pda(seeds: &[&[u8]]) -> Pubkey {
+        Pubkey::find_program_address(seeds, &crate::ID).0
+    }
+    
+    fn staking_error(error: StakingError) -> ProgramError {
+        anchor_lang::error::Error::from(error).into()
+    }
+    
+    // An initialized program with one collection (1_000 / 2_000 / 6_000 tickets) added by
+    // the authority
+    struct Staking {
+        env: TestEnv,
+        authority: Pubkey,
+        staking_program: Pubkey,
+        collection_mint: Pubkey,
+        collection_account: Pubkey,
+    }
+    
+    impl Staking {
+        fn new() -> Self {
+            let mut env = TestEnv::new();
+            let authority = env.wallet();
+            let staking_program = pda(&[b"staking_program"]);
+            env.execute(
+                crate::accounts::Initialize {
+                    staking_program,
+                    authority,
+                    system_program: system_program::ID,
+                },
+                crate::instruction::Initialize { multi_sig_threshold: 1 },
+            )
+            .unwrap();
+            
+            let mut staking = Self {
+                env,
+                authority,
+                staking_program,
+                collection_mint: Pubkey::default(),
+                collection_account: Pubkey::default(),
+            };
+            let collection_mint = Pubkey::new_unique();
+            staking.add_collection(authority, collection_mint).unwrap();
+            staking.collection_mint = collection_mint;
+            staking.collection_account = pda(&[b"collection", collection_mint.as_ref()]);
+            staking
+        }
+        
+        // add_collection signed by `authority`, passing its admin record unless it is the
+        // program authority
+        fn add_collection(&mut self, authority: Pubkey, collection_mint: Pubkey) -> ProgramResult {
+            let admin_account = (authority != self.authority).then(|| pda(&[b"admin", authority.as_ref()]));
+            self.env.execute(
+                crate::accounts::AddCollection {
+                    staking_program: self.staking_program,
+                    collection_account: pda(&[b"collection", collection_mint.as_ref()]),
+                    collection_mint,
+                    treasury: self.program().treasury,
+                    admin_account,
+                    authority,
+                    system_program: system_program::ID,
+                },
+                crate::instruction::AddCollection {
+                    collection_mint,
+                    six_month_tickets: 1_000,
+                    twelve_month_tickets: 2_000,
+                    three_year_tickets: 6_000,
+                    max_stake: 0,
+                },
+            )
+        }
+        
+        fn add_admin(&mut self, admin: Pubkey) -> ProgramResult {
+            self.env.execute(
+                crate::accounts::AddAdmin {
+                    staking_program: self.staking_program,
+                    admin_account: pda(&[b"admin", admin.as_ref()]),
+                    admin,
+                    authority: self.authority,
+                    system_program: system_program::ID,
+                },
+                crate::instruction::AddAdmin { admin },
+            )
+        }
+        
+        // An UpdateProgramConfig instruction signed by `authority`
+        fn update_program_config(&mut self, authority: Pubkey, args: impl InstructionData) -> ProgramResult {
+            self.env.execute(
+                crate::accounts::UpdateProgramConfig {
+                    staking_program: self.staking_program,
+                    authority,
+                },
+                args,
+            )
+        }
+        
+        // validate_collection signed by `admin` with its admin record
+        fn validate_collection(&mut self, admin: Pubkey, collection_mint: Pubkey, validated: bool) -> ProgramResult {
+            self.env.execute(
+                crate::accounts::ValidateCollection {
+                    staking_program: self.staking_program,
+                    collection_account: pda(&[b"collection", collection_mint.as_ref()]),
+                    admin_account: pda(&[b"admin", admin.as_ref()]),
+                    authority: admin,
+                },
+                crate::instruction::ValidateCollection { validated },
+            )
+        }
+        
+        // An UpdateCollectionConfig instruction on the default collection, signed by `admin`
+        fn update_collection_config(&mut self, admin: Pubkey, args: impl InstructionData) -> ProgramResult {
+            self.env.execute(
+                crate::accounts::UpdateCollectionConfig {
+                    staking_program: self.staking_program,
+                    collection_account: self.collection_account,
+                    admin_account: pda(&[b"admin", admin.as_ref()]),
+                    admin,
+                },
+                args,
+            )
+        }
+        
+        // pause_contract signed by `signer`, passing `admin`'s admin record
+        fn pause_as(&mut self, signer: Pubkey, admin: Pubkey) -> ProgramResult {
+            self.env.execute(
+                crate::accounts::PauseContract {
+                    staking_program: self.staking_program,
+                    admin_account: pda(&[b"admin", admin.as_ref()]),
+                    admin: signer,
+                },
+                crate::instruction::PauseContract {},
+            )
+        }
+        
+        // unpause_contract approval signed by `signer`, passing `admin`'s admin record
+        fn unpause_as(&mut self, signer: Pubkey, admin: Pubkey) -> ProgramResult {
+            let paused_at = self.program().paused_at;
+            self.env.execute(
+                crate::accounts::UnpauseContract {
+                    staking_program: self.staking_program,
+                    unpause_approval: pda(&[b"unpause_approval", paused_at.to_le_bytes().as_ref()]),
+                    admin_account: pda(&[b"admin", admin.as_ref()]),
+                    admin: signer,
+                    system_program: system_program::ID,
+                },
+                crate::instruction::UnpauseContract {},
+            )
+        }
+        
+        // remove_admin (`active` false) or reactivate_admin for `admin`, signed by `signer`
+        fn set_admin_status(&mut self, signer: Pubkey, admin: Pubkey, active: bool) -> ProgramResult {
+            let accounts = crate::accounts::SetAdminStatus {
+                staking_program: self.staking_program,
+                admin_account: pda(&[b"admin", admin.as_ref()]),
+                authority: signer,
+            };
+            if active {
+                self.env.execute(accounts, crate::instruction::ReactivateAdmin {})
+            } else {
+                self.env.execute(accounts, crate::instruction::RemoveAdmin {})
+            }
+        }
+        
+        // admin_unlock on `owner`'s position, signed by `signer` with `admin`'s admin record
+        fn admin_unlock_as(&mut self, signer: Pubkey, admin: Pubkey, owner: Pubkey, nft_mint: Pubkey) -> ProgramResult {
+            let staking_position = position_key(&nft_mint, &owner);
+            self.env.execute(
+                crate::accounts::AdminUnlock {
+                    staking_program: self.staking_program,
+                    collection_account: self.collection_account,
+                    staking_position,
+                    user_stake_count: self.user_stake_count_key(owner),
+                    user_stats: pda(&[b"user_stats", owner.as_ref()]),
+                    emergency_request: pda(&[b"emergency_request", staking_position.as_ref()]),
+                    active_emergency_requests: pda(&[b"active_emergency_requests"]),
+                    admin_account: pda(&[b"admin", admin.as_ref()]),
+                    program_token_account: token_account(&self.staking_program, &nft_mint),
+                    owner_token_account: token_account(&owner, &nft_mint),
+                    admin: signer,
+                    token_program: anchor_spl::token::ID,
+                    system_program: system_program::ID,
+                },
+                crate::instruction::AdminUnlock {
+                    reason: "lost wallet".to_string(),
+                },
+            )
+        }
+        
+        fn cancel_emergency_unlock(&mut self, signer: Pubkey, requester: Pubkey, owner: Pubkey, nft_mint: Pubkey) -> ProgramResult {
+            let staking_position = position_key(&nft_mint, &owner);
+            self.env.execute(
+                crate::accounts::CancelEmergencyUnlock {
+                    staking_program: self.staking_program,
+                    staking_position,
+                    emergency_request: pda(&[b"emergency_request", staking_position.as_ref()]),
+                    active_emergency_requests: pda(&[b"active_emergency_requests"]),
+                    requester,
+                    signer,
+                },
+                crate::instruction::CancelEmergencyUnlock {},
+            )
+        }
+        
+        fn program(&self) -> StakingProgram {
+            self.env.state(&self.staking_program)
+        }
+        
+        // A wallet holding one NFT of the collection (membership already verified), plus the
+        // program's empty vault account for it. Returns (wallet, nft_mint).
+        fn nft_holder(&mut self) -> (Pubkey, Pubkey) {
+            let user = self.env.wallet();
+            let nft_mint = Pubkey::new_unique();
+            self.env.put_mint(nft_mint, None, 1, 0);
+            self.env.put_token_account(token_account(&user, &nft_mint), nft_mint, user, 1);
+            self.env.put_token_account(token_account(&self.staking_program, &nft_mint), nft_mint, self.staking_program, 0);
+            let (verified_nft, bump) = Pubkey::find_program_address(&[b"verified_nft", nft_mint.as_ref()], &crate::ID);
+            self.env.put_state(
+                verified_nft,
+                &VerifiedNft {
+                    nft_mint,
+                    collection_mint: self.collection_mint,
+                    verified_at: T0,
+                    bump,
+                },
+            );
+            (user, nft_mint)
+        }
+        
+        fn stake(&mut self, user: Pubkey, nft_mint: Pubkey, duration: u8) -> ProgramResult {
+            let program_token_account = token_account(&self.staking_program, &nft_mint);
+            self.stake_into(user, nft_mint, duration, program_token_account)
+        }
+        
+        // stake_nft sending the NFT to `program_token_account`
+        fn stake_into(&mut self, user: Pubkey, nft_mint: Pubkey, duration: u8, program_token_account: Pubkey) -> ProgramResult {
+            self.env.execute(
+                crate::accounts::StakeNft {
+                    staking_program: self.staking_program,
+                    collection_account: self.collection_account,
+                    staking_position: position_key(&nft_mint, &user),
+                    user_stake_count: self.user_stake_count_key(user),
+                    user_stats: pda(&[b"user_stats", user.as_ref()]),
+                    nft_mint,
+                    user_token_account: token_account(&user, &nft_mint),
+                    program_token_account,
+                    verified_nft: Some(pda(&[b"verified_nft", nft_mint.as_ref()])),
+                    nft_metadata: None,
+                    blocked_mint: pda(&[b"blocked_mint", nft_mint.as_ref()]),
+                    treasury: self.program().treasury,
+                    user,
+                    token_program: anchor_spl::token::ID,
+                    system_program: system_program::ID,
+                },
+                crate::instruction::StakeNft {
+                    duration,
+                    terms_hash: [0u8; 32],
+                },
+            )
+        }
+        
+        // claim_nft signed by `signer` for the position `owner` opened on `nft_mint`
+        fn claim_nft(&mut self, signer: Pubkey, owner: Pubkey, nft_mint: Pubkey) -> ProgramResult {
+            self.claim_position(signer, owner, nft_mint, position_key(&nft_mint, &owner))
+        }
+        
+        // claim_nft for a position now owned by `owner`, at its original address
+        fn claim_position(&mut self, signer: Pubkey, owner: Pubkey, nft_mint: Pubkey, staking_position: Pubkey) -> ProgramResult {
+            self.env.execute(
+                crate::accounts::ClaimNft {
+                    staking_program: self.staking_program,
+                    collection_account: self.collection_account,
+                    staking_position,
+                    owner,
+                    user_stake_count: self.user_stake_count_key(owner),
+                    user_stats: pda(&[b"user_stats", owner.as_ref()]),
+                    user_token_account: token_account(&owner, &nft_mint),
+                    program_token_account: token_account(&self.staking_program, &nft_mint),
+                    user: signer,
+                    token_program: anchor_spl::token::ID,
+                    system_program: system_program::ID,
+                },
+                crate::instruction::ClaimNft {},
+            )
+        }
+        // unstake_early by the position's owner; `payout` passes the ticket-mint accounts
+        fn unstake_early(&mut self, user: Pubkey, nft_mint: Pubkey, payout: bool) -> ProgramResult {
+            let ticket_mint = self.program().ticket_mint;
+            self.env.execute(
+                crate::accounts::UnstakeEarly {
+                    staking_program: self.staking_program,
+                    collection_account: self.collection_account,
+                    staking_position: position_key(&nft_mint, &user),
+                    user_stake_count: self.user_stake_count_key(user),
+                    user_stats: pda(&[b"user_stats", user.as_ref()]),
+                    user_token_account: token_account(&user, &nft_mint),
+                    program_token_account: token_account(&self.staking_program, &nft_mint),
+                    ticket_mint: payout.then_some(ticket_mint),
+                    user_ticket_account: payout.then(|| token_account(&user, &ticket_mint)),
+                    ticket_mint_authority: payout.then(|| pda(&[b"ticket_mint_authority"])),
+                    reward_vault: None,
+                    user_reward_account: None,
+                    user,
+                    token_program: anchor_spl::token::ID,
+                    system_program: system_program::ID,
+                },
+                crate::instruction::UnstakeEarly {},
+            )
+        }
+        
+        fn stake_rewards(&mut self, user: Pubkey, nft_mint: Pubkey, amount: u64) -> ProgramResult {
+            let staking_position = position_key(&nft_mint, &user);
+            self.env.execute(
+                crate::accounts::StakeRewards {
+                    staking_program: self.staking_program,
+                    collection_account: self.collection_account,
+                    staking_position,
+                    reward_stake: pda(&[b"reward_stake", staking_position.as_ref()]),
+                    user,
+                    system_program: system_program::ID,
+                },
+                crate::instruction::StakeRewards { duration: 0, amount },
+            )
+        }
+        
+        fn claim_reward_stake(&mut self, user: Pubkey, nft_mint: Pubkey) -> ProgramResult {
+            let staking_position = position_key(&nft_mint, &user);
+            self.env.execute(
+                crate::accounts::ClaimRewardStake {
+                    staking_program: self.staking_program,
+                    staking_position,
+                    reward_stake: pda(&[b"reward_stake", staking_position.as_ref()]),
+                    user_stats: pda(&[b"user_stats", user.as_ref()]),
+                    user,
+                    system_program: system_program::ID,
+                },
+                crate::instruction::ClaimRewardStake {},
+            )
+        }
+        
+        fn user_stake_count_key(&self, user: Pubkey) -> Pubkey {
+            pda(&[b"user_stake_count", self.collection_mint.as_ref(), user.as_ref()])
+        }
+        
+        fn user_stake_count(&self, user: Pubkey) -> u64 {
+            self.env.state::<UserStakeCount>(&self.user_stake_count_key(user)).count
+        }
+        
+        fn transfer_position(&mut self, owner: Pubkey, nft_mint: Pubkey, new_owner: Pubkey) -> ProgramResult {
+            self.env.execute(
+                crate::accounts::TransferPosition {
+                    collection_account: self.collection_account,
+                    staking_position: position_key(&nft_mint, &owner),
+                    owner_stake_count: self.user_stake_count_key(owner),
+                    new_owner_stake_count: self.user_stake_count_key(new_owner),
+                    owner_stats: pda(&[b"user_stats", owner.as_ref()]),
+                    new_owner_stats: pda(&[b"user_stats", new_owner.as_ref()]),
+                    owner,
+                    system_program: system_program::ID,
+                },
+                crate::instruction::TransferPosition { new_owner },
+            )
+        }
+        
+        fn set_claim_delegate(&mut self, owner: Pubkey, nft_mint: Pubkey, delegate: Option<Pubkey>) -> ProgramResult {
+            self.env.execute(
+                crate::accounts::SetClaimDelegate {
+                    staking_position: position_key(&nft_mint, &owner),
+                    owner,
+                },
+                crate::instruction::SetClaimDelegate { delegate },
+            )
+        }
+        
+        fn claim_rewards(&mut self, user: Pubkey, nft_mint: Pubkey) -> ProgramResult {
+            self.claim_rewards_at_least(user, nft_mint, 0)
+        }
+        
+        fn claim_rewards_at_least(&mut self, user: Pubkey, nft_mint: Pubkey, min_rewards_out: u64) -> ProgramResult {
+            self.env.execute(
+                crate::accounts::ClaimRewards {
+                    staking_program: self.staking_program,
+                    collection_account: self.collection_account,
+                    staking_position: position_key(&nft_mint, &user),
+                    user,
+                    treasury: self.program().treasury,
+                    sysvar_instructions: sysvar::instructions::ID,
+                    reward_receipt: None,
+                    user_stats: pda(&[b"user_stats", user.as_ref()]),
+                    system_program: system_program::ID,
+                },
+                crate::instruction::ClaimRewards { min_rewards_out },
+            )
+        }
+        
+        fn user_stats(&self, user: Pubkey) -> UserStats {
+            self.env.state(&pda(&[b"user_stats", user.as_ref()]))
+        }
+        
+        // sweep_stale_positions over `positions` (nft_mint, owner)
+        fn sweep(&mut self, positions: &[(Pubkey, Pubkey)]) -> ProgramResult {
+            let mut remaining_accounts = Vec::new();
+            for (nft_mint, owner) in positions {
+                remaining_accounts.push(AccountMeta::new(position_key(nft_mint, owner), false));
+                remaining_accounts.push(AccountMeta::new(self.user_stake_count_key(*owner), false));
+                remaining_accounts.push(AccountMeta::new(pda(&[b"user_stats", owner.as_ref()]), false));
+            }
+            self.env.execute_with_remaining(
+                crate::accounts::SweepStalePositions {
+                    staking_program: self.staking_program,
+                    collection_account: self.collection_account,
+                },
+                remaining_accounts,
+                crate::instruction::SweepStalePositions {},
+            )
+        }
+        
+        // Sets a ticket mint (authority: the ticket_mint_authority PDA) and gives `user` a
+        // token account for it. Returns the user's ticket account.
+        fn enable_ticket_mint(&mut self, user: Pubkey) -> Pubkey {
+            let ticket_mint = Pubkey::new_unique();
+            self.env.put_mint(ticket_mint, Some(pda(&[b"ticket_mint_authority"])), 0, 0);
+            let user_ticket_account = token_account(&user, &ticket_mint);
+            self.env.put_token_account(user_ticket_account, ticket_mint, user, 0);
+            let staking_program = self.staking_program;
+            self.env.update::<StakingProgram>(&staking_program, |program| program.ticket_mint = ticket_mint);
+            user_ticket_account
+        }
+        
+        fn claim_tickets(&mut self, user: Pubkey, nft_mint: Pubkey) -> ProgramResult {
+            let ticket_mint = self.program().ticket_mint;
+            self.env.execute(
+                crate::accounts::ClaimTickets {
+                    staking_program: self.staking_program,
+                    staking_position: position_key(&nft_mint, &user),
+                    ticket_mint,
+                    user_ticket_account: token_account(&user, &ticket_mint),
+                    ticket_mint_authority: pda(&[b"ticket_mint_authority"]),
+                    user,
+                    token_program: anchor_spl::token::ID,
+                },
+                crate::instruction::ClaimTickets {},
+            )
+        }
+        
+        // swap_staked_nft moving `user`'s position from `old_mint` to `new_mint`, proven by
+        // the new mint's VerifiedNft
+        fn swap(&mut self, user: Pubkey, old_mint: Pubkey, new_mint: Pubkey) -> ProgramResult {
+            let staking_position = position_key(&old_mint, &user);
+            self.env.execute(
+                crate::accounts::SwapStakedNft {
+                    staking_program: self.staking_program,
+                    collection_account: self.collection_account,
+                    staking_position,
+                    new_staking_position: position_key(&new_mint, &user),
+                    program_old_token_account: token_account(&self.staking_program, &old_mint),
+                    user_old_token_account: token_account(&user, &old_mint),
+                    new_nft_mint: new_mint,
+                    verified_nft: Some(pda(&[b"verified_nft", new_mint.as_ref()])),
+                    new_nft_metadata: None,
+                    user_new_token_account: token_account(&user, &new_mint),
+                    program_new_token_account: token_account(&self.staking_program, &new_mint),
+                    blocked_mint: pda(&[b"blocked_mint", new_mint.as_ref()]),
+                    emergency_request: pda(&[b"emergency_request", staking_position.as_ref()]),
+                    user,
+                    token_program: anchor_spl::token::ID,
+                    system_program: system_program::ID,
+                },
+                crate::instruction::SwapStakedNft {},
+            )
+        }
+        
+        fn relock(&mut self, user: Pubkey, nft_mint: Pubkey, duration: u8) -> ProgramResult {
+            self.env.execute(
+                crate::accounts::Relock {
+                    staking_program: self.staking_program,
+                    collection_account: self.collection_account,
+                    staking_position: position_key(&nft_mint, &user),
+                    user_stake_count: self.user_stake_count_key(user),
+                    user_stats: pda(&[b"user_stats", user.as_ref()]),
+                    treasury: self.program().treasury,
+                    user,
+                    system_program: system_program::ID,
+                },
+                crate::instruction::Relock { duration },
+            )
+        }
+        
+        fn set_collection_active(&mut self, active: bool) -> ProgramResult {
+            self.env.execute(
+                crate::accounts::SetCollectionActive {
+                    staking_program: self.staking_program,
+                    collection_account: self.collection_account,
+                    authority: self.authority,
+                },
+                crate::instruction::SetCollectionActive { active },
+            )
+        }
+        
+        // Proposes `action` as `admin`; returns the proposal's action hash
+        fn propose(&mut self, admin: Pubkey, action: MultiSigAction) -> [u8; 32] {
+            let action_hash = action.hash(0).unwrap();
+            self.env
+                .execute(
+                    crate::accounts::ProposeAction {
+                        staking_program: self.staking_program,
+                        proposal: pda(&[b"proposal", action_hash.as_ref()]),
+                        active_emergency_requests: pda(&[b"active_emergency_requests"]),
+                        admin_account: pda(&[b"admin", admin.as_ref()]),
+                        admin,
+                        system_program: system_program::ID,
+                    },
+                    crate::instruction::ProposeAction { nonce: 0, action_hash, action },
+                )
+                .unwrap();
+            action_hash
+        }
+        
+        fn confirm(&mut self, admin: Pubkey, action_hash: [u8; 32]) -> ProgramResult {
+            self.env.execute(
+                crate::accounts::ConfirmAction {
+                    staking_program: self.staking_program,
+                    proposal: pda(&[b"proposal", action_hash.as_ref()]),
+                    admin_account: pda(&[b"admin", admin.as_ref()]),
+                    admin,
+                },
+                crate::instruction::ConfirmAction {},
+            )
+        }
+        
+        // ExecuteAction signed by `admin` with no action accounts filled in
+        fn execute_accounts(&self, admin: Pubkey, action_hash: [u8; 32], proposer: Pubkey) -> crate::accounts::ExecuteAction {
+            crate::accounts::ExecuteAction {
+                staking_program: self.staking_program,
+                proposal: pda(&[b"proposal", action_hash.as_ref()]),
+                proposer,
+                active_emergency_requests: pda(&[b"active_emergency_requests"]),
+                admin_account: pda(&[b"admin", admin.as_ref()]),
+                admin,
+                collection_account: None,
+                staking_position: None,
+                program_token_account: None,
+                owner_token_account: None,
+                user_stake_count: None,
+                user_stats: None,
+                position_owner: None,
+                token_program: None,
+                system_program: system_program::ID,
+            }
+        }
+        
+        fn execute_action(&mut self, accounts: crate::accounts::ExecuteAction, confirmers: &[Pubkey]) -> ProgramResult {
+            let confirmers = confirmers
+                .iter()
+                .map(|admin| AccountMeta::new_readonly(pda(&[b"admin", admin.as_ref()]), false))
+                .collect();
+            self.env.execute_with_remaining(accounts, confirmers, crate::instruction::ExecuteAction {})
+        }
+        
+        // `count` admins, with the threshold set to `threshold`
+        fn multisig(&mut self, count: usize, threshold: u8) -> Vec<Pubkey> {
+            let program = self.staking_program;
+            self.env.update::<StakingProgram>(&program, |program| program.multi_sig_threshold = threshold);
+            (0..count)
+                .map(|_| {
+                    let admin = self.env.wallet();
+                    self.add_admin(admin).unwrap();
+                    admin
+                })
+                .collect()
+        }
+    }
     
-    #[account(
-        init,
-        payer = authority,
-        space = 8 + 32 + 8 + 8 + 8 + 8 + 8 + 8 + 1 + 1 + 8 + 1,
-        seeds = [b"collection", collection_mint.key().as_ref()],
-        bump
-    )]
-    pub collection_account: Account<'info, CollectionAccount>,
+    fn position_key(nft_mint: &Pubkey, owner: &Pubkey) -> Pubkey {
+        pda(&[b"staking_position", nft_mint.as_ref(), owner.as_ref()])
+    }
     
-    /// CHECK: This is the collection mint being added
-    pub collection_mint: AccountInfo<'info>,
+    // Deterministic token account address for `owner` and `mint`
+    fn token_account(owner: &Pubkey, mint: &Pubkey) -> Pubkey {
+        get_associated_token_address(owner, mint)
+    }
     
-    #[account(mut)]
-    pub authority: Signer<'info>,
+    #[test]
+    fn accrual_stops_at_unlock() {
+        let collection = collection();
+        let position = position(&collection);
+        assert_eq!(calculate_rewards(&position, &collection, T0 + SIX_MONTHS / 2).unwrap(), 500);
+        assert_eq!(calculate_rewards(&position, &collection, T0 + SIX_MONTHS).unwrap(), 1_000);
+        assert_eq!(calculate_rewards(&position, &collection, T0 + THREE_YEARS).unwrap(), 1_000);
+    }
     
-    pub system_program: Program<'info, System>,
-}
-
-#[derive(Accounts)]
-pub struct StakeNft<'info> {
-    #[account(mut)]
-    pub staking_program: Account<'info, StakingProgram>,
+    #[test]
+    fn pause_running_past_unlock_only_removes_time_inside_the_term() {
+        let collection = collection();
+        let mut position = position(&collection);
+        position.accrual_paused = true;
+        position.accrual_paused_at = T0 + SIX_MONTHS / 2;
+        
+        // Still paused long after unlock: the first half of the term is all that accrued
+        assert_eq!(calculate_rewards(&position, &collection, T0 + THREE_YEARS).unwrap(), 500);
+        
+        let paused_for = position.resume_accrual(T0 + TWELVE_MONTHS);
+        assert_eq!(paused_for, TWELVE_MONTHS - SIX_MONTHS / 2);
+        assert_eq!(position.accrual_paused_total, SIX_MONTHS / 2);
+        assert_eq!(calculate_rewards(&position, &collection, T0 + THREE_YEARS).unwrap(), 500);
+    }
     
-    #[account(mut)]
-    pub collection_account: Account<'info, CollectionAccount>,
+    #[test]
+    fn resumed_pause_inside_the_term_still_earns_the_rest() {
+        let collection = collection();
+        let mut position = position(&collection);
+        position.accrual_paused = true;
+        position.accrual_paused_at = T0 + SIX_MONTHS / 4;
+        position.resume_accrual(T0 + SIX_MONTHS / 2);
+        
+        assert_eq!(calculate_rewards(&position, &collection, T0 + SIX_MONTHS).unwrap(), 750);
+        assert_eq!(calculate_rewards(&position, &collection, T0 + THREE_YEARS).unwrap(), 750);
+    }
     
-    #[account(
-        init,
-        payer = user,
-        space = 8 + 32 + 32 + 32 + 8 + 8 + 1 + 1 + 8 + 1,
-        seeds = [b"staking_position", nft_mint.key().as_ref(), user.key().as_ref()],
-        bump
-    )]
-    pub staking_position: Account<'info, StakingPosition>,
+    #[test]
+    fn late_validation_earns_pro_rata() {
+        let mut collection = collection();
+        collection.accrue_from_validation = true;
+        let position = position(&collection);
+        assert_eq!(calculate_rewards(&position, &collection, T0 + THREE_YEARS).unwrap(), 0);
+        
+        collection.validated_at = T0 + SIX_MONTHS / 4 * 3;
+        assert_eq!(calculate_rewards(&position, &collection, T0 + SIX_MONTHS).unwrap(), 250);
+        assert_eq!(calculate_rewards(&position, &collection, T0 + THREE_YEARS).unwrap(), 250);
+        
+        // Validated only after unlock: nothing of the term was served validated
+        collection.validated_at = T0 + TWELVE_MONTHS;
+        assert_eq!(calculate_rewards(&position, &collection, T0 + THREE_YEARS).unwrap(), 0);
+    }
     
-    /// CHECK: This is the NFT mint being staked
-    pub nft_mint: AccountInfo<'info>,
+    #[test]
+    fn lapsed_early_adopter_bonus_never_pays_twice() {
+        let mut collection = collection();
+        let mut position = position(&collection);
+        collection.is_validated = true;
+        collection.validated_at = T0 + 1;
+        collection.validation_expires_at = T0 + SIX_MONTHS / 2 + 1;
+        collection.early_adopter_bonus_bps = 1_000;
+        
+        let accrued = calculate_rewards(&position, &collection, T0 + SIX_MONTHS / 2).unwrap();
+        assert_eq!(accrued, 550);
+        position.mark_settled(accrued);
+        
+        // The lapse drops accrual below what was already settled
+        let accrued = calculate_rewards(&position, &collection, T0 + SIX_MONTHS / 2 + 2).unwrap();
+        assert!(accrued < 550);
+        position.mark_settled(accrued);
+        assert_eq!(position.rewards_claimed, 550);
+        
+        // Renewed: the rest of the term settles once, with nothing paid twice
+        collection.validation_expires_at = 0;
+        let accrued = calculate_rewards(&position, &collection, T0 + SIX_MONTHS).unwrap();
+        assert_eq!(accrued - position.rewards_claimed, 550);
+    }
     
-    #[account(mut)]
-    pub user_token_account: Account<'info, TokenAccount>,
+    fn dynamic_collection(reward_pool_remaining: u64) -> CollectionAccount {
+        let mut collection = collection();
+        collection.dynamic_multiplier = true;
+        collection.reward_pool = 10_000;
+        collection.reward_pool_remaining = reward_pool_remaining;
+        collection
+    }
     
-    #[account(mut)]
-    pub program_token_account: Account<'info, TokenAccount>,
+    #[test]
+    fn dynamic_multiplier_full_pool_pays_the_whole_multiplier() {
+        let collection = dynamic_collection(10_000);
+        let mut position = position(&collection);
+        position.locked_multiplier = 15_000;
+        assert_eq!(unsettled_rewards(&position, &collection, T0 + SIX_MONTHS).unwrap(), (1_500, 1_500));
+    }
     
-    #[account(mut)]
-    pub user: Signer<'info>,
+    #[test]
+    fn dynamic_multiplier_empty_pool_pays_one_x() {
+        let collection = dynamic_collection(0);
+        let mut position = position(&collection);
+        position.locked_multiplier = 15_000;
+        assert_eq!(unsettled_rewards(&position, &collection, T0 + SIX_MONTHS).unwrap(), (1_500, 1_000));
+    }
     
-    pub token_program: Program<'info, Token>,
-    pub system_program: Program<'info, System>,
-}
-
-#[derive(Accounts)]
-pub struct ClaimNft<'info> {
-    #[account(mut)]
-    pub staking_program: Account<'info, StakingProgram>,
+    #[test]
+    fn dynamic_multiplier_never_scales_below_the_floor() {
+        let mut collection = dynamic_collection(0);
+        let mut position = position(&collection);
+        assert_eq!(unsettled_rewards(&position, &collection, T0 + SIX_MONTHS).unwrap().1, 1_000);
+        
+        // A collection floor lifts the multiplier, and the dynamic scaling can't take it back
+        // below the floor
+        collection.min_multiplier_bps = 12_000;
+        collection.reward_pool_remaining = 5_000;
+        position.locked_multiplier = 11_000;
+        assert_eq!(unsettled_rewards(&position, &collection, T0 + SIX_MONTHS).unwrap(), (1_200, 1_200));
+        assert!(collection.is_decay_floored(12_000));
+    }
     
-    #[account(mut)]
-    pub collection_account: Account<'info, CollectionAccount>,
+    #[test]
+    fn dynamic_multiplier_applies_only_to_new_accrual() {
+        let mut collection = dynamic_collection(10_000);
+        let mut position = position(&collection);
+        position.locked_multiplier = 15_000;
+        
+        let (accrued, unsettled) = unsettled_rewards(&position, &collection, T0 + SIX_MONTHS / 2).unwrap();
+        assert_eq!(unsettled, 750);
+        position.mark_settled(accrued);
+        
+        // Draining the pool only affects the half accrued after the first settlement
+        collection.reward_pool_remaining = 0;
+        assert_eq!(unsettled_rewards(&position, &collection, T0 + SIX_MONTHS).unwrap().1, 500);
+    }
     
-    #[account(mut)]
-    pub staking_position: Account<'info, StakingPosition>,
+    #[test]
+    fn extending_six_months_to_three_years_moves_unlock() {
+        let collection = collection();
+        let mut position = position(&collection);
+        position.extend(2, 15_000).unwrap();
+        assert_eq!(position.unlock_at, position.staked_at + THREE_YEARS);
+        assert_eq!(position.duration, 2);
+        assert_eq!(position.reward_tier, 2);
+        assert_eq!(position.locked_multiplier, 15_000);
+        assert!(position.extend(2, 15_000).is_err());
+        assert!(position.extend(1, 12_500).is_err());
+    }
     
-    #[account(mut)]
-    pub user_token_account: Account<'info, TokenAccount>,
+    #[test]
+    fn extending_pays_the_new_full_term_once() {
+        for curve in [CURVE_LINEAR, CURVE_CLIFF, CURVE_BACK_LOADED] {
+            let mut collection = collection();
+            collection.distribution_curve = curve;
+            let mut position = position(&collection);
+            
+            let (accrued, settled) = unsettled_rewards(&position, &collection, T0 + SIX_MONTHS).unwrap();
+            assert_eq!(settled, 1_000);
+            position.mark_settled(accrued);
+            position.extend(2, BASIS_POINTS).unwrap();
+            
+            // The longer term restarts lower on the curve; nothing is paid twice
+            assert_eq!(unsettled_rewards(&position, &collection, T0 + SIX_MONTHS + 1).unwrap().1, 0);
+            let (_, rest) = unsettled_rewards(&position, &collection, T0 + THREE_YEARS).unwrap();
+            assert_eq!(settled + rest, 6_000);
+        }
+    }
     
-    #[account(mut)]
-    pub program_token_account: Account<'info, TokenAccount>,
+    fn loyalty_collection() -> CollectionAccount {
+        let mut collection = collection();
+        collection.loyalty_bonus_bps_per_month = 1_000;
+        collection.max_loyalty_bps = 3_000;
+        collection
+    }
     
-    #[account(mut)]
-    pub user: Signer<'info>,
+    #[test]
+    fn loyalty_bonus_needs_a_full_month_past_unlock() {
+        let collection = loyalty_collection();
+        let position = position(&collection);
+        assert_eq!(calculate_rewards(&position, &collection, T0 + SIX_MONTHS).unwrap(), 1_000);
+        assert_eq!(calculate_rewards(&position, &collection, T0 + SIX_MONTHS + LOYALTY_MONTH - 1).unwrap(), 1_000);
+    }
     
-    pub token_program: Program<'info, Token>,
-}
-
-#[derive(Accounts)]
-pub struct AdminUnlock<'info> {
-    #[account(mut)]
-    pub staking_program: Account<'info, StakingProgram>,
+    #[test]
+    fn loyalty_bonus_applies_only_to_time_past_unlock() {
+        let collection = loyalty_collection();
+        let position = position(&collection);
+        // A six-month term is six loyalty months, so a month's accrual is 1_000 / 6 tickets
+        // and the first month past unlock earns 10% of it; the term itself is unchanged
+        let after_one_month = calculate_rewards(&position, &collection, T0 + SIX_MONTHS + LOYALTY_MONTH).unwrap();
+        assert_eq!(after_one_month, 1_000 + 16);
+    }
     
-    #[account(mut)]
-    pub collection_account: Account<'info, CollectionAccount>,
+    #[test]
+    fn loyalty_bonus_rate_stops_at_the_cap() {
+        let collection = loyalty_collection();
+        let position = position(&collection);
+        // Months 1-3 earn 10%, 20% and 30% of a month's accrual; months 4 and 5 stay at 30%
+        let after_five_months = calculate_rewards(&position, &collection, T0 + SIX_MONTHS + 5 * LOYALTY_MONTH).unwrap();
+        assert_eq!(after_five_months, 1_000 + 1_000 * 12 / 60);
+        assert_eq!(collection.loyalty_bonus_for(&position, T0 + SIX_MONTHS + 5 * LOYALTY_MONTH), 3_000);
+    }
     
-    #[account(mut)]
-    pub staking_position: Account<'info, StakingPosition>,
+    #[test]
+    fn claimed_nft_can_be_staked_again() {
+        let mut staking = Staking::new();
+        let (user, nft_mint) = staking.nft_holder();
+        staking.stake(user, nft_mint, 0).unwrap();
+        assert_eq!(staking.env.token_amount(&token_account(&user, &nft_mint)), 0);
+        
+        set_time(T0 + SIX_MONTHS);
+        staking.claim_nft(user, user, nft_mint).unwrap();
+        assert_eq!(staking.env.lamports(&position_key(&nft_mint, &user)), 0);
+        assert_eq!(staking.env.token_amount(&token_account(&user, &nft_mint)), 1);
+        
+        staking.stake(user, nft_mint, 0).unwrap();
+        let position: StakingPosition = staking.env.state(&position_key(&nft_mint, &user));
+        assert!(position.is_active);
+        assert_eq!(position.staked_at, T0 + SIX_MONTHS);
+    }
     
-    #[account(
-        init_if_needed,
-        payer = admin,
-        space = 8 + 32 + 8 + 200 + 1 + 1,
-        seeds = [b"emergency_request", staking_position.key().as_ref()],
-        bump
-    )]
-    pub emergency_request: Account<'info, EmergencyRequest>,
+    #[test]
+    fn claim_nft_keeps_the_position_while_tickets_are_unpaid() {
+        let mut staking = Staking::new();
+        let (user, nft_mint) = staking.nft_holder();
+        staking.stake(user, nft_mint, 0).unwrap();
+        let position = position_key(&nft_mint, &user);
+        staking.env.update::<StakingPosition>(&position, |position| position.total_rewards_earned = 1_000);
+        set_time(T0 + SIX_MONTHS);
+        
+        // Tickets credited off-chain don't hold the claim up
+        let program = staking.staking_program;
+        staking.env.update::<StakingProgram>(&program, |program| program.ticket_mint = Pubkey::new_unique());
+        assert_eq!(
+            staking.claim_nft(user, user, nft_mint),
+            Err(staking_error(StakingError::TicketsNotPaidOut))
+        );
+        
+        staking.env.update::<StakingPosition>(&position, |position| position.tickets_minted = 1_000);
+        staking.claim_nft(user, user, nft_mint).unwrap();
+        assert_eq!(staking.env.lamports(&position), 0);
+    }
+    
+    #[test]
+    fn unstake_early_pays_out_tickets_left_after_the_penalty() {
+        let mut staking = Staking::new();
+        let (user, nft_mint) = staking.nft_holder();
+        staking.stake(user, nft_mint, 0).unwrap();
+        let position = position_key(&nft_mint, &user);
+        staking.env.update::<StakingPosition>(&position, |position| position.total_rewards_earned = 1_000);
+        let program = staking.staking_program;
+        staking.env.update::<StakingProgram>(&program, |program| program.early_unstake_penalty_bps = 1_000);
+        let user_ticket_account = staking.enable_ticket_mint(user);
+        set_time(T0 + 60);
+        
+        // Closing without the payout accounts would drop the tickets
+        assert_eq!(
+            staking.unstake_early(user, nft_mint, false),
+            Err(staking_error(StakingError::TicketsNotPaidOut))
+        );
+        
+        staking.unstake_early(user, nft_mint, true).unwrap();
+        assert_eq!(staking.env.token_amount(&user_ticket_account), 900);
+        assert_eq!(staking.env.token_amount(&token_account(&user, &nft_mint)), 1);
+        assert_eq!(staking.env.lamports(&position), 0);
+    }
+    
+    #[test]
+    fn matured_reward_stake_settles_its_yield() {
+        let mut staking = Staking::new();
+        let (user, nft_mint) = staking.nft_holder();
+        staking.stake(user, nft_mint, 0).unwrap();
+        let position = position_key(&nft_mint, &user);
+        staking.env.update::<StakingPosition>(&position, |position| position.total_rewards_earned = 1_000);
+        
+        staking.stake_rewards(user, nft_mint, 500).unwrap();
+        assert_eq!(staking.env.state::<StakingPosition>(&position).mintable_tickets(), 500);
+        set_time(T0 + SIX_MONTHS);
+        staking.claim_reward_stake(user, nft_mint).unwrap();
+        
+        let yield_rewards = 500 * REWARD_STAKE_MULTIPLIERS[0] / BASIS_POINTS - 500;
+        let settled: StakingPosition = staking.env.state(&position);
+        assert_eq!(settled.rewards_staked, 0);
+        assert_eq!(settled.total_rewards_earned, 1_000 + yield_rewards);
+        assert_eq!(settled.mintable_tickets(), 1_000 + yield_rewards);
+        assert_eq!(staking.program().outstanding_rewards, yield_rewards);
+    }
+    
+    #[test]
+    fn rewards_cannot_be_staked_from_an_inactive_collection() {
+        let mut staking = Staking::new();
+        let (user, nft_mint) = staking.nft_holder();
+        staking.stake(user, nft_mint, 0).unwrap();
+        let position = position_key(&nft_mint, &user);
+        staking.env.update::<StakingPosition>(&position, |position| position.total_rewards_earned = 1_000);
+        let collection_account = staking.collection_account;
+        staking.env.update::<CollectionAccount>(&collection_account, |collection| collection.is_active = false);
+        
+        assert_eq!(
+            staking.stake_rewards(user, nft_mint, 500),
+            Err(staking_error(StakingError::CollectionNotActive))
+        );
+    }
+    
+    #[test]
+    fn swept_position_frees_its_owners_stake_slot_once() {
+        let mut staking = Staking::new();
+        let collection_account = staking.collection_account;
+        staking.env.update::<CollectionAccount>(&collection_account, |collection| collection.max_per_user = 1);
+        let (user, first_nft) = staking.nft_holder();
+        let second_nft = Pubkey::new_unique();
+        staking.env.put_mint(second_nft, None, 1, 0);
+        staking.env.put_token_account(token_account(&user, &second_nft), second_nft, user, 1);
+        staking.env.put_token_account(token_account(&staking.staking_program, &second_nft), second_nft, staking.staking_program, 0);
+        let (verified_nft, bump) = Pubkey::find_program_address(&[b"verified_nft", second_nft.as_ref()], &crate::ID);
+        staking.env.put_state(
+            verified_nft,
+            &VerifiedNft {
+                nft_mint: second_nft,
+                collection_mint: staking.collection_mint,
+                verified_at: T0,
+                bump,
+            },
+        );
+        
+        staking.stake(user, first_nft, 0).unwrap();
+        assert_eq!(
+            staking.stake(user, second_nft, 0),
+            Err(staking_error(StakingError::UserStakeLimitReached))
+        );
+        
+        set_time(T0 + SIX_MONTHS + STALE_POSITION_GRACE);
+        staking.sweep(&[(first_nft, user)]).unwrap();
+        assert_eq!(staking.user_stake_count(user), 0);
+        staking.stake(user, second_nft, 0).unwrap();
+        assert_eq!(staking.user_stake_count(user), 1);
+        
+        // Claiming the swept position doesn't release the slot a second time
+        staking.claim_nft(user, user, first_nft).unwrap();
+        assert_eq!(staking.user_stake_count(user), 1);
+        assert_eq!(staking.program().total_staked, 1);
+    }
+    
+    #[test]
+    fn transferred_position_moves_the_owner_counters() {
+        let mut staking = Staking::new();
+        let collection_account = staking.collection_account;
+        staking.env.update::<CollectionAccount>(&collection_account, |collection| {
+            collection.positions_transferable = true;
+            collection.max_per_user = 1;
+        });
+        let (user, nft_mint) = staking.nft_holder();
+        let (buyer, buyer_nft) = staking.nft_holder();
+        staking.stake(user, nft_mint, 0).unwrap();
+        staking.stake(buyer, buyer_nft, 0).unwrap();
+        
+        // The buyer is at the per-user limit already
+        assert_eq!(
+            staking.transfer_position(user, nft_mint, buyer),
+            Err(staking_error(StakingError::UserStakeLimitReached))
+        );
+        
+        let recipient = staking.env.wallet();
+        staking.transfer_position(user, nft_mint, recipient).unwrap();
+        assert_eq!(staking.user_stake_count(user), 0);
+        assert_eq!(staking.user_stake_count(recipient), 1);
+        assert_eq!(staking.user_stats(user).currently_staked, 0);
+        assert_eq!(staking.user_stats(user).lifetime_staked, 1);
+        assert_eq!(staking.user_stats(recipient).currently_staked, 1);
+        assert_eq!(staking.user_stats(recipient).lifetime_staked, 0);
+        
+        set_time(T0 + SIX_MONTHS);
+        staking.env.put_token_account(token_account(&recipient, &nft_mint), nft_mint, recipient, 0);
+        staking
+            .claim_position(recipient, recipient, nft_mint, position_key(&nft_mint, &user))
+            .unwrap();
+        assert_eq!(staking.user_stake_count(recipient), 0);
+        assert_eq!(staking.user_stats(recipient).currently_staked, 0);
+        assert_eq!(staking.user_stake_count(user), 0);
+    }
+    
+    #[test]
+    fn user_stats_follow_a_stake_and_claim_cycle() {
+        let mut staking = Staking::new();
+        let (user, nft_mint) = staking.nft_holder();
+        staking.stake(user, nft_mint, 0).unwrap();
+        let stats = staking.user_stats(user);
+        assert_eq!((stats.lifetime_staked, stats.currently_staked, stats.lifetime_rewards), (1, 1, 0));
+        
+        set_time(T0 + SIX_MONTHS);
+        staking.claim_rewards(user, nft_mint).unwrap();
+        let settled = staking.env.state::<StakingPosition>(&position_key(&nft_mint, &user)).total_rewards_earned;
+        assert!(settled >= 1_000);
+        assert_eq!(staking.user_stats(user).lifetime_rewards, settled);
+        
+        staking.claim_nft(user, user, nft_mint).unwrap();
+        let stats = staking.user_stats(user);
+        assert_eq!((stats.lifetime_staked, stats.currently_staked, stats.lifetime_rewards), (1, 0, settled));
+        
+        staking.stake(user, nft_mint, 0).unwrap();
+        let stats = staking.user_stats(user);
+        assert_eq!((stats.lifetime_staked, stats.currently_staked), (2, 1));
+    }
+    
+    #[test]
+    fn user_stats_count_reward_stake_yield_and_sweeps() {
+        let mut staking = Staking::new();
+        let (user, nft_mint) = staking.nft_holder();
+        staking.stake(user, nft_mint, 0).unwrap();
+        staking.env.update::<StakingPosition>(&position_key(&nft_mint, &user), |position| {
+            position.total_rewards_earned = 1_000;
+        });
+        staking.stake_rewards(user, nft_mint, 500).unwrap();
+        set_time(T0 + SIX_MONTHS);
+        staking.claim_reward_stake(user, nft_mint).unwrap();
+        let yield_rewards = 500 * REWARD_STAKE_MULTIPLIERS[0] / BASIS_POINTS - 500;
+        assert_eq!(staking.user_stats(user).lifetime_rewards, yield_rewards);
+        
+        // A swept position leaves currently_staked once, and claiming it doesn't again
+        set_time(T0 + SIX_MONTHS + STALE_POSITION_GRACE);
+        staking.sweep(&[(nft_mint, user)]).unwrap();
+        assert_eq!(staking.user_stats(user).currently_staked, 0);
+        staking.claim_nft(user, user, nft_mint).unwrap();
+        let stats = staking.user_stats(user);
+        assert_eq!((stats.lifetime_staked, stats.currently_staked), (1, 0));
+    }
+    
+    #[test]
+    fn claim_nft_rejects_another_collections_account() {
+        let mut staking = Staking::new();
+        let (user, nft_mint) = staking.nft_holder();
+        staking.stake(user, nft_mint, 0).unwrap();
+        let other_mint = Pubkey::new_unique();
+        staking.add_collection(staking.authority, other_mint).unwrap();
+        set_time(T0 + SIX_MONTHS);
+        
+        let own_collection = staking.collection_account;
+        staking.collection_account = pda(&[b"collection", other_mint.as_ref()]);
+        assert_eq!(
+            staking.claim_nft(user, user, nft_mint),
+            Err(staking_error(StakingError::CollectionNotFound))
+        );
+        
+        staking.collection_account = own_collection;
+        staking.claim_nft(user, user, nft_mint).unwrap();
+    }
+    
+    #[test]
+    fn only_the_authority_or_an_admin_adds_collections() {
+        let mut staking = Staking::new();
+        // An outsider has no admin record to pass
+        let outsider = staking.env.wallet();
+        assert_eq!(
+            staking.add_collection(outsider, Pubkey::new_unique()),
+            Err(anchor_lang::error::Error::from(ErrorCode::AccountNotInitialized).into())
+        );
+        let collection_mint = Pubkey::new_unique();
+        let result = staking.env.execute(
+            crate::accounts::AddCollection {
+                staking_program: staking.staking_program,
+                collection_account: pda(&[b"collection", collection_mint.as_ref()]),
+                collection_mint,
+                treasury: staking.program().treasury,
+                admin_account: None,
+                authority: outsider,
+                system_program: system_program::ID,
+            },
+            crate::instruction::AddCollection {
+                collection_mint,
+                six_month_tickets: 1_000,
+                twelve_month_tickets: 2_000,
+                three_year_tickets: 6_000,
+                max_stake: 0,
+            },
+        );
+        assert_eq!(result, Err(staking_error(StakingError::Unauthorized)));
+        
+        // Nor can the outsider appoint itself
+        let authority = staking.authority;
+        staking.authority = outsider;
+        assert_eq!(staking.add_admin(outsider), Err(staking_error(StakingError::Unauthorized)));
+        staking.authority = authority;
+    }
+    
+    #[test]
+    fn admin_pays_the_onboarding_fee_to_the_treasury() {
+        let mut staking = Staking::new();
+        let treasury = staking.env.wallet();
+        let program = staking.staking_program;
+        staking.env.update::<StakingProgram>(&program, |program| {
+            program.treasury = treasury;
+            program.collection_onboarding_fee = LAMPORTS_PER_SOL;
+        });
+        let admin = staking.env.wallet();
+        staking.add_admin(admin).unwrap();
+        
+        let (admin_before, treasury_before) = (staking.env.lamports(&admin), staking.env.lamports(&treasury));
+        staking.add_collection(admin, Pubkey::new_unique()).unwrap();
+        let rent = Rent::default().minimum_balance(CollectionAccount::LEN);
+        assert_eq!(staking.env.lamports(&treasury), treasury_before + LAMPORTS_PER_SOL);
+        assert_eq!(staking.env.lamports(&admin), admin_before - LAMPORTS_PER_SOL - rent);
+        
+        // The authority is exempt
+        let authority = staking.authority;
+        staking.add_collection(authority, Pubkey::new_unique()).unwrap();
+        assert_eq!(staking.env.lamports(&treasury), treasury_before + LAMPORTS_PER_SOL);
+    }
+    
+    #[test]
+    fn initialize_rejects_an_unreachable_threshold() {
+        let mut env = TestEnv::new();
+        let authority = env.wallet();
+        let accounts = || crate::accounts::Initialize {
+            staking_program: pda(&[b"staking_program"]),
+            authority,
+            system_program: system_program::ID,
+        };
+        let threshold = MAX_PROPOSAL_CONFIRMATIONS as u8 + 1;
+        assert_eq!(
+            env.execute(accounts(), crate::instruction::Initialize { multi_sig_threshold: threshold }),
+            Err(staking_error(StakingError::InvalidThreshold))
+        );
+        env.execute(accounts(), crate::instruction::Initialize { multi_sig_threshold: threshold - 1 }).unwrap();
+    }
+    
+    #[test]
+    fn add_collection_proposal_executes_at_the_threshold() {
+        for threshold in 1..=3u8 {
+            let mut staking = Staking::new();
+            let admins = staking.multisig(3, threshold);
+            let collection_mint = Pubkey::new_unique();
+            let action_hash = staking.propose(
+                admins[0],
+                MultiSigAction::AddCollection { collection_mint, tickets: [1_000, 2_000, 6_000] },
+            );
+            let accounts = |staking: &Staking| {
+                let mut accounts = staking.execute_accounts(admins[0], action_hash, admins[0]);
+                accounts.collection_account = Some(pda(&[b"collection", collection_mint.as_ref()]));
+                accounts
+            };
+            
+            for confirmed in 1..threshold as usize {
+                let confirmers = &admins[..confirmed];
+                assert_eq!(
+                    staking.execute_action(accounts(&staking), confirmers),
+                    Err(staking_error(StakingError::InsufficientMultiSigConfirmations))
+                );
+                // Naming an admin who hasn't confirmed doesn't help
+                assert_eq!(
+                    staking.execute_action(accounts(&staking), &admins[..confirmed + 1]),
+                    Err(staking_error(StakingError::InsufficientMultiSigConfirmations))
+                );
+                staking.confirm(admins[confirmed], action_hash).unwrap();
+            }
+            
+            let proposer_before = staking.env.lamports(&admins[0]);
+            staking.execute_action(accounts(&staking), &admins[..threshold as usize]).unwrap();
+            let collection: CollectionAccount = staking.env.state(&pda(&[b"collection", collection_mint.as_ref()]));
+            assert_eq!(collection.collection_mint, collection_mint);
+            assert_eq!(staking.program().total_collections, 2);
+            // The proposal is closed to its proposer
+            let proposal = pda(&[b"proposal", action_hash.as_ref()]);
+            assert_eq!(staking.env.lamports(&proposal), 0);
+            assert!(staking.env.lamports(&admins[0]) > proposer_before);
+        }
+    }
+    
+    #[test]
+    fn deactivated_admins_confirmation_no_longer_counts() {
+        let mut staking = Staking::new();
+        let admins = staking.multisig(3, 2);
+        let collection_mint = Pubkey::new_unique();
+        let action_hash = staking.propose(
+            admins[0],
+            MultiSigAction::AddCollection { collection_mint, tickets: [1_000, 2_000, 6_000] },
+        );
+        staking.confirm(admins[1], action_hash).unwrap();
+        staking.env.update::<AdminAccount>(&pda(&[b"admin", admins[1].as_ref()]), |admin| admin.is_active = false);
+        
+        let accounts = |staking: &Staking| {
+            let mut accounts = staking.execute_accounts(admins[0], action_hash, admins[0]);
+            accounts.collection_account = Some(pda(&[b"collection", collection_mint.as_ref()]));
+            accounts
+        };
+        assert_eq!(
+            staking.execute_action(accounts(&staking), &admins[..2]),
+            Err(staking_error(StakingError::Unauthorized))
+        );
+        
+        staking.confirm(admins[2], action_hash).unwrap();
+        staking.execute_action(accounts(&staking), &[admins[0], admins[2]]).unwrap();
+    }
+    
+    #[test]
+    fn multisig_unlock_waits_for_the_emergency_delay_and_closes_the_position() {
+        let mut staking = Staking::new();
+        let admins = staking.multisig(2, 2);
+        let (user, nft_mint) = staking.nft_holder();
+        staking.stake(user, nft_mint, 2).unwrap();
+        let staking_position = position_key(&nft_mint, &user);
+        let action_hash = staking.propose(admins[0], MultiSigAction::AdminUnlock { staking_position });
+        let proposal = pda(&[b"proposal", action_hash.as_ref()]);
+        let registry = pda(&[b"active_emergency_requests"]);
+        assert_eq!(staking.env.state::<ActiveEmergencyRequests>(&registry).requests, vec![proposal]);
+        staking.confirm(admins[1], action_hash).unwrap();
+        
+        let accounts = |staking: &Staking| {
+            let mut accounts = staking.execute_accounts(admins[1], action_hash, admins[0]);
+            accounts.collection_account = Some(staking.collection_account);
+            accounts.staking_position = Some(staking_position);
+            accounts.program_token_account = Some(token_account(&staking.staking_program, &nft_mint));
+            accounts.owner_token_account = Some(token_account(&user, &nft_mint));
+            accounts.user_stake_count = Some(staking.user_stake_count_key(user));
+            accounts.user_stats = Some(pda(&[b"user_stats", user.as_ref()]));
+            accounts.position_owner = Some(user);
+            accounts.token_program = Some(anchor_spl::token::ID);
+            accounts
+        };
+        
+        set_time(T0 + EMERGENCY_DELAY - 1);
+        assert_eq!(
+            staking.execute_action(accounts(&staking), &admins),
+            Err(staking_error(StakingError::EmergencyDelayNotMet))
+        );
+        
+        set_time(T0 + EMERGENCY_DELAY);
+        staking.execute_action(accounts(&staking), &admins).unwrap();
+        assert_eq!(staking.env.token_amount(&token_account(&user, &nft_mint)), 1);
+        assert_eq!(staking.env.lamports(&staking_position), 0);
+        assert!(staking.env.state::<ActiveEmergencyRequests>(&registry).requests.is_empty());
+        assert_eq!(staking.user_stake_count(user), 0);
+        assert_eq!(staking.user_stats(user).currently_staked, 0);
+        assert_eq!(staking.program().total_staked, 0);
+    }
+    
+    #[test]
+    fn vault_claims_share_the_daily_emission_cap() {
+        let mut staking = Staking::new();
+        let (user, nft_mint) = staking.nft_holder();
+        staking.stake(user, nft_mint, 0).unwrap();
+        let position = position_key(&nft_mint, &user);
+        staking.env.update::<StakingPosition>(&position, |position| position.total_rewards_earned = 1_000);
+        
+        let reward_token_mint = Pubkey::new_unique();
+        staking.env.put_mint(reward_token_mint, None, 10_000, 0);
+        let reward_vault = pda(&[b"reward_vault"]);
+        staking.env.put_token_account(reward_vault, reward_token_mint, staking.staking_program, 10_000);
+        let user_reward_account = token_account(&user, &reward_token_mint);
+        staking.env.put_token_account(user_reward_account, reward_token_mint, user, 0);
+        let program = staking.staking_program;
+        staking.env.update::<StakingProgram>(&program, |program| {
+            program.reward_token_mint = reward_token_mint;
+            program.vault_balance = 10_000;
+            program.max_tickets_per_day = 600;
+        });
+        let claim = |staking: &mut Staking| {
+            staking.env.execute(
+                crate::accounts::ClaimVaultRewards {
+                    staking_program: staking.staking_program,
+                    staking_position: position,
+                    reward_vault,
+                    user_reward_account,
+                    user,
+                    token_program: anchor_spl::token::ID,
+                },
+                crate::instruction::ClaimVaultRewards {},
+            )
+        };
+        
+        // Within the cap the whole amount would pay; here 600 of 1_000 fit
+        set_time(T0 + 60);
+        claim(&mut staking).unwrap();
+        assert_eq!(staking.env.token_amount(&user_reward_account), 600);
+        assert_eq!(staking.env.state::<StakingPosition>(&position).mintable_tickets(), 400);
+        
+        // The window is used up
+        assert_eq!(claim(&mut staking), Err(staking_error(StakingError::DailyEmissionCapReached)));
+        
+        // A new window pays the rest, which is within the cap
+        set_time(T0 + 60 + SECONDS_PER_DAY as i64);
+        claim(&mut staking).unwrap();
+        assert_eq!(staking.env.token_amount(&user_reward_account), 1_000);
+        assert_eq!(staking.program().tickets_emitted_today, 400);
+        assert_eq!(staking.program().vault_balance, 9_000);
+    }
+    
+    #[test]
+    fn relock_carries_rewards_and_loyalty_into_the_second_period() {
+        let mut staking = Staking::new();
+        let collection_account = staking.collection_account;
+        staking.env.update::<CollectionAccount>(&collection_account, |collection| {
+            collection.loyalty_bonus_bps_per_month = 1_000;
+            collection.max_loyalty_bps = 3_000;
+        });
+        let (user, nft_mint) = staking.nft_holder();
+        staking.stake(user, nft_mint, 0).unwrap();
+        let position = position_key(&nft_mint, &user);
+        
+        let relocked_at = T0 + SIX_MONTHS + 2 * LOYALTY_MONTH;
+        let unsettled_at = |staking: &Staking, current_time: i64| {
+            let state: StakingPosition = staking.env.state(&position);
+            unsettled_rewards(&state, &staking.env.state(&staking.collection_account), current_time).unwrap().1
+        };
+        let at_unlock = unsettled_at(&staking, T0 + SIX_MONTHS);
+        let expected = unsettled_at(&staking, relocked_at);
+        assert!(expected > at_unlock);
+        
+        set_time(relocked_at);
+        staking.relock(user, nft_mint, 0).unwrap();
+        let relocked: StakingPosition = staking.env.state(&position);
+        assert_eq!((relocked.carried_rewards, relocked.total_rewards_earned), (expected, expected));
+        assert_eq!(relocked.unlock_at, relocked_at + SIX_MONTHS);
+        
+        // The second period accrues from zero on top of what was carried
+        set_time(relocked_at + SIX_MONTHS);
+        staking.claim_rewards(user, nft_mint).unwrap();
+        let claimed: StakingPosition = staking.env.state(&position);
+        assert_eq!(claimed.carried_rewards, expected);
+        assert!(claimed.total_rewards_earned - expected >= 1_000);
+    }
+    
+    #[test]
+    fn relock_applies_the_claim_gates() {
+        let mut staking = Staking::new();
+        let (user, nft_mint) = staking.nft_holder();
+        staking.stake(user, nft_mint, 0).unwrap();
+        set_time(T0 + SIX_MONTHS);
+        let program = staking.staking_program;
+        let collection_account = staking.collection_account;
+        
+        staking.env.update::<StakingProgram>(&program, |program| program.paused_instructions = PAUSE_CLAIM_REWARDS);
+        assert_eq!(staking.relock(user, nft_mint, 0), Err(staking_error(StakingError::ContractPaused)));
+        staking.env.update::<StakingProgram>(&program, |program| {
+            program.paused_instructions = 0;
+            program.require_validation = true;
+        });
+        assert_eq!(staking.relock(user, nft_mint, 0), Err(staking_error(StakingError::CollectionNotValidated)));
+        staking.env.update::<StakingProgram>(&program, |program| program.require_validation = false);
+        staking.env.update::<CollectionAccount>(&collection_account, |collection| collection.rewards_frozen = true);
+        assert_eq!(staking.relock(user, nft_mint, 0), Err(staking_error(StakingError::CollectionRewardsFrozen)));
+        staking.env.update::<CollectionAccount>(&collection_account, |collection| collection.rewards_frozen = false);
+        
+        // A swept position only comes back under the collection's cap
+        set_time(T0 + SIX_MONTHS + STALE_POSITION_GRACE);
+        staking.sweep(&[(nft_mint, user)]).unwrap();
+        let (other, other_mint) = staking.nft_holder();
+        staking.stake(other, other_mint, 0).unwrap();
+        staking.env.update::<CollectionAccount>(&collection_account, |collection| collection.max_stake = 1);
+        assert_eq!(staking.relock(user, nft_mint, 0), Err(staking_error(StakingError::CollectionStakeCapReached)));
+        staking.env.update::<CollectionAccount>(&collection_account, |collection| collection.max_stake = 0);
+        
+        // The claim fee goes to the treasury
+        let treasury = staking.env.wallet();
+        staking.env.update::<StakingProgram>(&program, |program| {
+            program.treasury = treasury;
+            program.claim_fee_lamports = 5_000;
+        });
+        let treasury_before = staking.env.lamports(&treasury);
+        staking.relock(user, nft_mint, 0).unwrap();
+        assert_eq!(staking.env.lamports(&treasury), treasury_before + 5_000);
+        assert_eq!(staking.user_stake_count(user), 1);
+    }
+    
+    #[test]
+    fn layout_lens_end_at_the_current_layout() {
+        let lens = StakingPosition::LAYOUT_LENS;
+        assert_eq!(lens[StakingPosition::CURRENT_VERSION as usize - 1], StakingPosition::LEN);
+        assert!(lens.windows(2).all(|pair| pair[0] < pair[1]) && StakingPositionV0::LEN < lens[0]);
+    }
+    
+    #[test]
+    fn migrate_position_upgrades_each_layout_by_version() {
+        let mut staking = Staking::new();
+        let (user, nft_mint) = staking.nft_holder();
+        staking.stake(user, nft_mint, 0).unwrap();
+        let position = position_key(&nft_mint, &user);
+        staking.env.update::<StakingPosition>(&position, |position| {
+            position.total_rewards_earned = 500;
+            position.tickets_minted = 200;
+            position.last_claim_at = T0 + 60;
+            position.carried_rewards = 100;
+            position.version = 1;
+        });
+        let current: StakingPosition = staking.env.state(&position);
+        let payer = staking.env.wallet();
+        let migrate = |staking: &mut Staking| {
+            staking.env.execute(
+                crate::accounts::MigratePosition {
+                    staking_position: position,
+                    collection_account: staking.collection_account,
+                    payer,
+                    system_program: system_program::ID,
+                },
+                crate::instruction::MigratePosition {},
+            )
+        };
+        
+        // A current-size account written before versioning only gets its version bumped
+        migrate(&mut staking).unwrap();
+        let migrated: StakingPosition = staking.env.state(&position);
+        assert_eq!(migrated.version, StakingPosition::CURRENT_VERSION);
+        assert_eq!((migrated.tickets_minted, migrated.carried_rewards), (200, 100));
+        assert_eq!(migrate(&mut staking), Err(staking_error(StakingError::PositionAlreadyMigrated)));
+        
+        // An older layout: the current fields minus the `dropped` bytes before the bump
+        let fields = current.try_to_vec().unwrap();
+        let old_layout = |version: usize, dropped: usize| {
+            let mut data = <StakingPosition as anchor_lang::Discriminator>::DISCRIMINATOR.to_vec();
+            data.extend(&fields[..fields.len() - dropped - 1]);
+            data.push(current.bump);
+            data.resize(StakingPosition::LAYOUT_LENS[version - 1], 0);
+            data
+        };
+        
+        // Layout 4 ends at tickets_minted: last_claim_at, swept, carried_rewards and
+        // last_snapshot_id are new
+        let data = old_layout(4, 8 + 1 + 8 + 8);
+        let rent = Rent::default().minimum_balance(data.len());
+        staking.env.put(position, rent, crate::ID, &data, false);
+        migrate(&mut staking).unwrap();
+        let migrated: StakingPosition = staking.env.state(&position);
+        assert_eq!(staking.env.lamports(&position), Rent::default().minimum_balance(StakingPosition::LEN));
+        assert_eq!(migrated.version, StakingPosition::CURRENT_VERSION);
+        assert_eq!((migrated.total_rewards_earned, migrated.tickets_minted), (500, 200));
+        assert_eq!((migrated.last_claim_at, migrated.swept, migrated.carried_rewards), (0, false, 0));
+        assert_eq!((migrated.owner, migrated.bump), (user, current.bump));
+        
+        // Layout 3 predates claim_tickets, so earlier settlements count as paid
+        staking.env.put(position, rent, crate::ID, &old_layout(3, 8 + 8 + 1 + 8 + 8), false);
+        migrate(&mut staking).unwrap();
+        let migrated: StakingPosition = staking.env.state(&position);
+        assert_eq!((migrated.tickets_minted, migrated.bump), (500, current.bump));
+    }
+    
+    #[test]
+    fn deactivated_collection_refuses_stakes_but_allows_claims() {
+        let mut staking = Staking::new();
+        let (user, nft_mint) = staking.nft_holder();
+        staking.stake(user, nft_mint, 0).unwrap();
+        staking.set_collection_active(false).unwrap();
+        
+        let (late, late_mint) = staking.nft_holder();
+        assert_eq!(staking.stake(late, late_mint, 0), Err(staking_error(StakingError::CollectionNotActive)));
+        
+        set_time(T0 + SIX_MONTHS);
+        staking.claim_rewards(user, nft_mint).unwrap();
+        staking.claim_nft(user, user, nft_mint).unwrap();
+        assert_eq!(staking.env.token_amount(&token_account(&user, &nft_mint)), 1);
+    }
+    
+    #[test]
+    fn reactivation_keeps_the_inactive_period_unpaid() {
+        let mut staking = Staking::new();
+        let collection_account = staking.collection_account;
+        staking.env.update::<CollectionAccount>(&collection_account, |collection| {
+            collection.honor_existing_on_deactivate = false;
+        });
+        let (user, nft_mint) = staking.nft_holder();
+        staking.stake(user, nft_mint, 0).unwrap();
+        
+        let month = LOYALTY_MONTH;
+        set_time(T0 + month);
+        staking.set_collection_active(false).unwrap();
+        set_time(T0 + 2 * month);
+        staking.set_collection_active(true).unwrap();
+        let collection: CollectionAccount = staking.env.state(&collection_account);
+        assert_eq!(collection.inactive_periods, vec![InactivePeriod { start: T0 + month, end: T0 + 2 * month }]);
+        assert_eq!(collection.deactivated_at, 0);
+        
+        // At unlock the position has accrued for the term less the inactive month
+        set_time(T0 + SIX_MONTHS);
+        let position: StakingPosition = staking.env.state(&position_key(&nft_mint, &user));
+        let mut never_deactivated = collection.clone();
+        never_deactivated.inactive_periods.clear();
+        let expected = calculate_rewards(&position, &never_deactivated, T0 + SIX_MONTHS - month).unwrap();
+        assert!(expected < calculate_rewards(&position, &never_deactivated, T0 + SIX_MONTHS).unwrap());
+        assert_eq!(calculate_rewards(&position, &collection, T0 + SIX_MONTHS).unwrap(), expected);
+        
+        // A position staked after the period isn't charged for it
+        let (late, late_mint) = staking.nft_holder();
+        staking.stake(late, late_mint, 0).unwrap();
+        let late_position: StakingPosition = staking.env.state(&position_key(&late_mint, &late));
+        let unlock = T0 + 2 * SIX_MONTHS;
+        assert_eq!(
+            calculate_rewards(&late_position, &collection, unlock).unwrap(),
+            calculate_rewards(&late_position, &never_deactivated, unlock).unwrap()
+        );
+        
+        // Under the honor policy the period is paid
+        let mut honored = collection.clone();
+        honored.honor_existing_on_deactivate = true;
+        assert_eq!(
+            calculate_rewards(&position, &honored, T0 + SIX_MONTHS).unwrap(),
+            calculate_rewards(&position, &never_deactivated, T0 + SIX_MONTHS).unwrap()
+        );
+    }
+    
+    #[test]
+    fn deactivations_past_the_period_limit_fold_the_oldest() {
+        let mut staking = Staking::new();
+        let collection_account = staking.collection_account;
+        staking.env.update::<CollectionAccount>(&collection_account, |collection| {
+            collection.honor_existing_on_deactivate = false;
+        });
+        let (user, nft_mint) = staking.nft_holder();
+        staking.stake(user, nft_mint, 0).unwrap();
+        
+        // Ten one-day deactivations, each followed by a day active
+        let day = 24 * 60 * 60;
+        for period in 0..MAX_INACTIVE_PERIODS as i64 + 2 {
+            set_time(T0 + (2 * period + 1) * day);
+            staking.set_collection_active(false).unwrap();
+            set_time(T0 + (2 * period + 2) * day);
+            staking.set_collection_active(true).unwrap();
+        }
+        let collection: CollectionAccount = staking.env.state(&collection_account);
+        assert_eq!(collection.inactive_periods.len(), MAX_INACTIVE_PERIODS);
+        assert_eq!(collection.inactive_periods[0], InactivePeriod { start: T0 + 5 * day, end: T0 + 6 * day });
+        assert_eq!(collection.folded_inactive_span, InactivePeriod { start: T0 + day, end: T0 + 4 * day });
+        assert_eq!(collection.folded_inactive_seconds, 2 * day);
+        
+        // The position spans every period, so all ten days stay unpaid
+        let position: StakingPosition = staking.env.state(&position_key(&nft_mint, &user));
+        let mut never_deactivated = collection.clone();
+        never_deactivated.inactive_periods.clear();
+        never_deactivated.folded_inactive_seconds = 0;
+        assert_eq!(collection.inactive_between(T0, T0 + SIX_MONTHS), 10 * day);
+        assert_eq!(
+            calculate_rewards(&position, &collection, T0 + SIX_MONTHS).unwrap(),
+            calculate_rewards(&position, &never_deactivated, T0 + SIX_MONTHS - 10 * day).unwrap()
+        );
+        
+        // A window starting inside the folded span is charged no more than it could hold
+        assert_eq!(collection.folded_inactive_between(T0 + 2 * day, T0 + SIX_MONTHS), day);
+        assert_eq!(collection.folded_inactive_between(T0 + 4 * day, T0 + SIX_MONTHS), 0);
+        
+        // Deactivating again still succeeds
+        set_time(T0 + 30 * day);
+        staking.set_collection_active(false).unwrap();
+        assert!(!staking.env.state::<CollectionAccount>(&collection_account).is_active);
+    }
+    
+    #[test]
+    fn prefunded_collection_address_can_still_be_created() {
+        let mut staking = Staking::new();
+        let admins = staking.multisig(1, 1);
+        let collection_mint = Pubkey::new_unique();
+        let collection_account = pda(&[b"collection", collection_mint.as_ref()]);
+        staking.env.put(collection_account, 1_000, system_program::ID, &[], false);
+        
+        let action = MultiSigAction::AddCollection { collection_mint, tickets: [1_000, 2_000, 6_000] };
+        let action_hash = staking.propose(admins[0], action.clone());
+        let mut accounts = staking.execute_accounts(admins[0], action_hash, admins[0]);
+        accounts.collection_account = Some(collection_account);
+        staking.execute_action(accounts, &admins).unwrap();
+        assert_eq!(staking.env.lamports(&collection_account), Rent::default().minimum_balance(CollectionAccount::LEN));
+        assert_eq!(staking.env.state::<CollectionAccount>(&collection_account).collection_mint, collection_mint);
+        
+        // An existing collection is still refused
+        let action_hash = staking.propose(admins[0], action);
+        let mut accounts = staking.execute_accounts(admins[0], action_hash, admins[0]);
+        accounts.collection_account = Some(collection_account);
+        assert_eq!(
+            staking.execute_action(accounts, &admins),
+            Err(staking_error(StakingError::CollectionAlreadyExists))
+        );
+    }
+    
+    #[test]
+    fn prefunded_receipt_address_can_still_be_created() {
+        let mut staking = Staking::new();
+        let (user, nft_mint) = staking.nft_holder();
+        staking.stake(user, nft_mint, 0).unwrap();
+        let staking_position = position_key(&nft_mint, &user);
+        let reward_receipt = pda(&[
+            b"reward_receipt",
+            staking_position.as_ref(),
+            &T0.to_le_bytes(),
+            &0u64.to_le_bytes(),
+        ]);
+        staking.env.put(reward_receipt, 1, system_program::ID, &[], false);
+        
+        set_time(T0 + SIX_MONTHS);
+        staking
+            .env
+            .execute(
+                crate::accounts::ClaimRewards {
+                    staking_program: staking.staking_program,
+                    collection_account: staking.collection_account,
+                    staking_position,
+                    user,
+                    treasury: staking.program().treasury,
+                    sysvar_instructions: sysvar::instructions::ID,
+                    reward_receipt: Some(reward_receipt),
+                    user_stats: pda(&[b"user_stats", user.as_ref()]),
+                    system_program: system_program::ID,
+                },
+                crate::instruction::ClaimRewards { min_rewards_out: 0 },
+            )
+            .unwrap();
+        let receipt: RewardReceipt = staking.env.state(&reward_receipt);
+        assert_eq!(receipt.amount, staking.env.state::<StakingPosition>(&staking_position).total_rewards_earned);
+        assert_eq!(staking.env.lamports(&reward_receipt), Rent::default().minimum_balance(RewardReceipt::LEN));
+    }
+    
+    #[test]
+    fn six_month_position_at_full_term_earns_the_tickets_times_1_1() {
+        let mut staking = Staking::new();
+        let (user, nft_mint) = staking.nft_holder();
+        staking.stake(user, nft_mint, 0).unwrap();
+        
+        set_time(T0 + SIX_MONTHS);
+        staking.claim_rewards(user, nft_mint).unwrap();
+        let position: StakingPosition = staking.env.state(&position_key(&nft_mint, &user));
+        assert_eq!(position.total_rewards_earned, 1_000 * 11_000 / BASIS_POINTS);
+        assert_eq!(position.rewards_claimed, 1_100);
+        
+        // Nothing more accrues past the term
+        set_time(T0 + SIX_MONTHS + LOYALTY_MONTH);
+        staking.claim_rewards(user, nft_mint).unwrap();
+        assert_eq!(staking.env.state::<StakingPosition>(&position_key(&nft_mint, &user)).total_rewards_earned, 1_100);
+    }
+    
+    #[test]
+    fn swap_moves_the_position_to_the_new_mints_address() {
+        let mut staking = Staking::new();
+        let (user, old_mint) = staking.nft_holder();
+        let (_, new_mint) = staking.nft_holder();
+        staking.env.put_token_account(token_account(&user, &new_mint), new_mint, user, 1);
+        set_time(T0);
+        staking.stake(user, old_mint, 0).unwrap();
+        let staked: StakingPosition = staking.env.state(&position_key(&old_mint, &user));
+        
+        set_time(T0 + SIX_MONTHS / 2);
+        staking.swap(user, old_mint, new_mint).unwrap();
+        assert_eq!(staking.env.lamports(&position_key(&old_mint, &user)), 0);
+        let swapped: StakingPosition = staking.env.state(&position_key(&new_mint, &user));
+        assert_eq!(swapped.nft_mint, new_mint);
+        assert_eq!(swapped.owner, user);
+        assert_eq!(swapped.staked_at, staked.staked_at);
+        assert_eq!(swapped.unlock_at, staked.unlock_at);
+        assert_eq!(swapped.locked_multiplier, staked.locked_multiplier);
+        assert_eq!(swapped.bump, Pubkey::find_program_address(&[b"staking_position", new_mint.as_ref(), user.as_ref()], &crate::ID).1);
+        assert_eq!(staking.env.token_amount(&token_account(&user, &old_mint)), 1);
+        assert_eq!(staking.env.token_amount(&token_account(&staking.staking_program, &old_mint)), 0);
+        assert_eq!(staking.env.token_amount(&token_account(&user, &new_mint)), 0);
+        assert_eq!(staking.env.token_amount(&token_account(&staking.staking_program, &new_mint)), 1);
+        
+        // A reward stake is keyed by the position's address and would be stranded
+        staking.env.update::<StakingPosition>(&position_key(&new_mint, &user), |position| position.rewards_staked = 100);
+        assert_eq!(staking.swap(user, new_mint, old_mint), Err(staking_error(StakingError::RewardsStillStaked)));
+    }
+    
+    #[test]
+    fn pending_rewards_mid_term_and_at_full_term() {
+        let collection = collection();
+        let mut position = position(&collection);
+        
+        let mid_term = pending_rewards_view(&position, &collection, T0 + SIX_MONTHS / 2).unwrap();
+        assert_eq!((mid_term.accrued, mid_term.pending, mid_term.full_term), (500, 500, 1_000));
+        assert!(!mid_term.is_full_term);
+        
+        // A mid-term settlement leaves only the second half pending
+        position.rewards_claimed = 500;
+        let full_term = pending_rewards_view(&position, &collection, T0 + SIX_MONTHS).unwrap();
+        assert_eq!((full_term.accrued, full_term.pending, full_term.full_term), (1_000, 500, 1_000));
+        assert!(full_term.is_full_term);
+        
+        let past_unlock = pending_rewards_view(&position, &collection, T0 + THREE_YEARS).unwrap();
+        assert_eq!((past_unlock.accrued, past_unlock.pending), (1_000, 500));
+    }
+    
+    #[test]
+    fn claim_reverts_when_a_dropped_multiplier_pays_below_the_minimum() {
+        let mut staking = Staking::new();
+        let (user, nft_mint) = staking.nft_holder();
+        staking.stake(user, nft_mint, 0).unwrap();
+        
+        // Quoted at the full 1.1x; by execution half the dynamic pool is gone, so the bonus
+        // above 1x halves to 1.05x
+        staking.env.update::<CollectionAccount>(&staking.collection_account, |collection| {
+            collection.dynamic_multiplier = true;
+            collection.reward_pool = 10_000;
+            collection.reward_pool_remaining = 5_000;
+        });
+        set_time(T0 + SIX_MONTHS);
+        assert_eq!(
+            staking.claim_rewards_at_least(user, nft_mint, 1_100),
+            Err(staking_error(StakingError::SlippageExceeded))
+        );
+        assert_eq!(staking.env.state::<StakingPosition>(&position_key(&nft_mint, &user)).total_rewards_earned, 0);
+        
+        staking.claim_rewards_at_least(user, nft_mint, 1_050).unwrap();
+        assert_eq!(staking.env.state::<StakingPosition>(&position_key(&nft_mint, &user)).total_rewards_earned, 1_050);
+    }
     
-    pub admin_account: Account<'info, AdminAccount>,
+    #[test]
+    fn stake_and_claim_events_report_the_display_and_settlement_multipliers() {
+        let mut staking = Staking::new();
+        let (user, nft_mint) = staking.nft_holder();
+        staking.stake(user, nft_mint, 0).unwrap();
+        assert_eq!(events::<NftStaked>()[0].display_multiplier, 11_000);
+        
+        // A later config change doesn't reach the position: it settles at its locked 1.1x
+        staking.env.update::<CollectionAccount>(&staking.collection_account, |collection| {
+            collection.six_month_multiplier = 15_000;
+        });
+        set_time(T0 + SIX_MONTHS / 2);
+        staking.claim_rewards(user, nft_mint).unwrap();
+        let claimed = events::<RewardsClaimed>();
+        assert_eq!((claimed[0].settlement_multiplier, claimed[0].amount), (11_000, 550));
+        
+        // Settlement can still fall below the displayed multiplier when a dynamic pool is
+        // half spent
+        staking.env.update::<CollectionAccount>(&staking.collection_account, |collection| {
+            collection.dynamic_multiplier = true;
+            collection.reward_pool = 10_000;
+            collection.reward_pool_remaining = 5_000;
+        });
+        set_time(T0 + SIX_MONTHS);
+        staking.claim_rewards(user, nft_mint).unwrap();
+        let claimed = events::<RewardsClaimed>();
+        assert_eq!((claimed[1].settlement_multiplier, claimed[1].amount), (10_500, 525));
+        assert_eq!(events::<NftStaked>()[0].display_multiplier, 11_000);
+    }
     
-    #[account(mut)]
-    pub program_token_account: Account<'info, TokenAccount>,
+    #[test]
+    fn delegate_claims_the_nft_into_the_owners_account() {
+        let mut staking = Staking::new();
+        let (user, nft_mint) = staking.nft_holder();
+        let delegate = staking.env.wallet();
+        let stranger = staking.env.wallet();
+        staking.stake(user, nft_mint, 0).unwrap();
+        staking.set_claim_delegate(user, nft_mint, Some(delegate)).unwrap();
+        assert_eq!(events::<ClaimDelegateUpdated>()[0].delegate, Some(delegate));
+        
+        set_time(T0 + SIX_MONTHS);
+        assert_eq!(
+            staking.claim_nft(stranger, user, nft_mint),
+            Err(staking_error(StakingError::NotPositionOwner))
+        );
+        staking.claim_nft(delegate, user, nft_mint).unwrap();
+        assert_eq!(staking.env.token_amount(&token_account(&user, &nft_mint)), 1);
+        assert_eq!(staking.env.token_amount(&token_account(&staking.staking_program, &nft_mint)), 0);
+        assert_eq!(staking.env.lamports(&position_key(&nft_mint, &user)), 0);
+    }
     
-    #[account(mut)]
-    pub owner_token_account: Account<'info, TokenAccount>,
+    #[test]
+    fn stakes_lock_up_to_exactly_the_max_lock_duration() {
+        let mut staking = Staking::new();
+        assert_eq!(staking.program().max_lock_duration, THREE_YEARS);
+        let stranger = staking.env.wallet();
+        assert_eq!(
+            staking.update_program_config(stranger, crate::instruction::SetMaxLockDuration { max_lock_duration: TWELVE_MONTHS }),
+            Err(staking_error(StakingError::Unauthorized))
+        );
+        
+        let authority = staking.authority;
+        staking
+            .update_program_config(authority, crate::instruction::SetMaxLockDuration { max_lock_duration: TWELVE_MONTHS })
+            .unwrap();
+        let (user, twelve_month_mint) = staking.nft_holder();
+        staking.stake(user, twelve_month_mint, 1).unwrap();
+        let (user, three_year_mint) = staking.nft_holder();
+        assert_eq!(staking.stake(user, three_year_mint, 2), Err(staking_error(StakingError::LockTooLong)));
+        
+        // One second under the twelve-month term
+        staking
+            .update_program_config(authority, crate::instruction::SetMaxLockDuration { max_lock_duration: TWELVE_MONTHS - 1 })
+            .unwrap();
+        let (user, nft_mint) = staking.nft_holder();
+        assert_eq!(staking.stake(user, nft_mint, 1), Err(staking_error(StakingError::LockTooLong)));
+        staking.stake(user, nft_mint, 0).unwrap();
+    }
     
-    #[account(mut)]
-    pub admin: Signer<'info>,
+    #[test]
+    fn anomalous_clock_readings_are_rejected_or_accrue_nothing() {
+        let mut staking = Staking::new();
+        let (user, nft_mint) = staking.nft_holder();
+        
+        set_time(0);
+        assert_eq!(staking.stake(user, nft_mint, 0), Err(staking_error(StakingError::InvalidClock)));
+        set_time(-1);
+        assert_eq!(staking.stake(user, nft_mint, 0), Err(staking_error(StakingError::InvalidClock)));
+        
+        set_time(T0);
+        staking.stake(user, nft_mint, 0).unwrap();
+        
+        // A clock behind the stake time counts as no time elapsed
+        set_time(T0 - 3_600);
+        let position: StakingPosition = staking.env.state(&position_key(&nft_mint, &user));
+        let collection: CollectionAccount = staking.env.state(&staking.collection_account);
+        assert_eq!(elapsed_since(T0, T0 - 3_600), 0);
+        assert_eq!(calculate_rewards(&position, &collection, T0 - 3_600).unwrap(), 0);
+        let view = pending_rewards_view(&position, &collection, T0 - 3_600).unwrap();
+        assert_eq!((view.accrued, view.pending), (0, 0));
+        staking.claim_rewards(user, nft_mint).unwrap();
+        assert_eq!(staking.env.state::<StakingPosition>(&position_key(&nft_mint, &user)).total_rewards_earned, 0);
+    }
     
-    pub token_program: Program<'info, Token>,
-    pub system_program: Program<'info, System>,
-}
-
-#[derive(Accounts)]
-pub struct PauseContract<'info> {
-    #[account(mut)]
-    pub staking_program: Account<'info, StakingProgram>,
+    #[test]
+    fn staking_waits_for_the_minimum_validated_collections() {
+        let mut staking = Staking::new();
+        let authority = staking.authority;
+        let admin = staking.env.wallet();
+        staking.add_admin(admin).unwrap();
+        let second_collection = Pubkey::new_unique();
+        staking.add_collection(authority, second_collection).unwrap();
+        staking
+            .update_program_config(authority, crate::instruction::SetMinValidatedCollections { min_validated_collections: 2 })
+            .unwrap();
+        let (user, nft_mint) = staking.nft_holder();
+        assert_eq!(staking.stake(user, nft_mint, 0), Err(staking_error(StakingError::ProgramNotReady)));
+        
+        // Only admins' validations count
+        let stranger = staking.env.wallet();
+        assert_eq!(
+            staking.env.execute(
+                crate::accounts::ValidateCollection {
+                    staking_program: staking.staking_program,
+                    collection_account: staking.collection_account,
+                    admin_account: pda(&[b"admin", admin.as_ref()]),
+                    authority: stranger,
+                },
+                crate::instruction::ValidateCollection { validated: true },
+            ),
+            Err(staking_error(StakingError::Unauthorized))
+        );
+        staking.validate_collection(admin, staking.collection_mint, true).unwrap();
+        assert_eq!(staking.stake(user, nft_mint, 0), Err(staking_error(StakingError::ProgramNotReady)));
+        
+        staking.validate_collection(admin, second_collection, true).unwrap();
+        assert_eq!(staking.program().validated_collections, 2);
+        staking.stake(user, nft_mint, 0).unwrap();
+        
+        // Withdrawing a validation closes staking again
+        staking.validate_collection(admin, second_collection, false).unwrap();
+        let (user, nft_mint) = staking.nft_holder();
+        assert_eq!(staking.stake(user, nft_mint, 0), Err(staking_error(StakingError::ProgramNotReady)));
+    }
     
-    pub admin_account: Account<'info, AdminAccount>,
+    #[test]
+    fn pool_decay_stops_at_the_multiplier_floor() {
+        let mut staking = Staking::new();
+        let admin = staking.env.wallet();
+        staking.add_admin(admin).unwrap();
+        assert_eq!(
+            staking.update_collection_config(admin, crate::instruction::SetMinMultiplier { min_multiplier_bps: 11_001 }),
+            Err(staking_error(StakingError::InvalidBasisPoints))
+        );
+        staking
+            .update_collection_config(admin, crate::instruction::SetMinMultiplier { min_multiplier_bps: 10_800 })
+            .unwrap();
+        let (user, nft_mint) = staking.nft_holder();
+        staking.stake(user, nft_mint, 0).unwrap();
+        
+        // A tenth of the pool left would decay the 1.1x multiplier to 1.01x
+        staking.env.update::<CollectionAccount>(&staking.collection_account, |collection| {
+            collection.dynamic_multiplier = true;
+            collection.reward_pool = 100_000;
+            collection.reward_pool_remaining = 10_000;
+        });
+        set_time(T0 + SIX_MONTHS);
+        staking.claim_rewards(user, nft_mint).unwrap();
+        assert_eq!(events::<RewardsClaimed>()[0].amount, 1_080);
+        let floored = events::<MultiplierFloored>();
+        assert_eq!((floored[0].locked_multiplier, floored[0].floored_multiplier), (11_000, 10_800));
+    }
     
-    pub admin: Signer<'info>,
-}
-
-#[derive(Accounts)]
-pub struct UnpauseContract<'info> {
-    #[account(mut)]
-    pub staking_program: Account<'info, StakingProgram>,
+    #[test]
+    fn each_distribution_curve_at_quarter_points_of_the_term() {
+        let expected = [
+            (CURVE_LINEAR, [0, 250, 500, 750, 1_000]),
+            (CURVE_CLIFF, [0, 0, 0, 0, 1_000]),
+            // full * f^2, rounded down
+            (CURVE_BACK_LOADED, [0, 62, 250, 562, 1_000]),
+        ];
+        for (curve, accrued) in expected {
+            let mut collection = collection();
+            collection.distribution_curve = curve;
+            let position = position(&collection);
+            for (quarter, tickets) in accrued.into_iter().enumerate() {
+                let at = T0 + SIX_MONTHS * quarter as i64 / 4;
+                assert_eq!(calculate_rewards(&position, &collection, at).unwrap(), tickets, "curve {} at {}/4", curve, quarter);
+            }
+            assert!(calculate_rewards(&position, &collection, T0 + SIX_MONTHS - 1).unwrap() < 1_000);
+        }
+    }
     
-    pub admin_account: Account<'info, AdminAccount>,
+    #[test]
+    fn unpause_takes_the_threshold_of_distinct_admin_approvals() {
+        let mut staking = Staking::new();
+        let authority = staking.authority;
+        let admins = [staking.env.wallet(), staking.env.wallet()];
+        for admin in admins {
+            staking.add_admin(admin).unwrap();
+        }
+        assert_eq!(
+            staking.update_program_config(admins[0], crate::instruction::SetUnpauseThreshold { unpause_threshold: 2 }),
+            Err(staking_error(StakingError::Unauthorized))
+        );
+        staking
+            .update_program_config(authority, crate::instruction::SetUnpauseThreshold { unpause_threshold: 2 })
+            .unwrap();
+        
+        // One admin pauses alone
+        set_time(T0 + 60);
+        staking.pause_as(admins[0], admins[0]).unwrap();
+        assert!(staking.program().is_paused);
+        
+        staking.unpause_as(admins[0], admins[0]).unwrap();
+        assert!(staking.program().is_paused);
+        assert_eq!(
+            staking.unpause_as(admins[0], admins[0]),
+            Err(staking_error(StakingError::AlreadyApproved))
+        );
+        staking.unpause_as(admins[1], admins[1]).unwrap();
+        assert!(!staking.program().is_paused);
+        let progress = events::<UnpauseApprovalProgress>();
+        assert_eq!(
+            progress.iter().map(|event| (event.approvals, event.threshold)).collect::<Vec<_>>(),
+            [(1, 2), (2, 2)]
+        );
+        
+        // Approvals don't carry over to the next pause
+        set_time(T0 + 120);
+        staking.pause_as(admins[1], admins[1]).unwrap();
+        staking.unpause_as(admins[0], admins[0]).unwrap();
+        assert!(staking.program().is_paused);
+    }
     
-    pub admin: Signer<'info>,
-}
-
-#[derive(Accounts)]
-pub struct UpdateCollectionRewards<'info> {
-    pub staking_program: Account<'info, StakingProgram>,
+    #[test]
+    fn pause_and_unpause_reject_signers_without_an_active_admin_record() {
+        let mut staking = Staking::new();
+        let authority = staking.authority;
+        let admin = staking.env.wallet();
+        let stranger = staking.env.wallet();
+        staking.add_admin(admin).unwrap();
+        
+        // Someone else's admin record doesn't authorize the signer
+        assert_eq!(staking.pause_as(stranger, admin), Err(staking_error(StakingError::Unauthorized)));
+        assert!(staking.pause_as(stranger, stranger).is_err());
+        assert!(!staking.program().is_paused);
+        
+        staking.pause_as(admin, admin).unwrap();
+        assert_eq!(staking.unpause_as(stranger, admin), Err(staking_error(StakingError::Unauthorized)));
+        staking.unpause_as(admin, admin).unwrap();
+        
+        // A deactivated admin's own record no longer passes
+        staking.set_admin_status(authority, admin, false).unwrap();
+        assert_eq!(staking.pause_as(admin, admin), Err(staking_error(StakingError::Unauthorized)));
+        assert!(!staking.program().is_paused);
+    }
     
-    #[account(mut)]
-    pub collection_account: Account<'info, CollectionAccount>,
+    #[test]
+    fn removed_admin_cannot_pause_until_reactivated() {
+        let mut staking = Staking::new();
+        let authority = staking.authority;
+        let admin = staking.env.wallet();
+        staking.add_admin(admin).unwrap();
+        staking.add_admin(authority).unwrap();
+        
+        assert_eq!(staking.set_admin_status(admin, admin, false), Err(staking_error(StakingError::Unauthorized)));
+        assert_eq!(
+            staking.set_admin_status(authority, authority, false),
+            Err(staking_error(StakingError::CannotRemoveAuthority))
+        );
+        
+        staking.set_admin_status(authority, admin, false).unwrap();
+        let record: AdminAccount = staking.env.state(&pda(&[b"admin", admin.as_ref()]));
+        assert!(!record.is_active);
+        assert_eq!(staking.pause_as(admin, admin), Err(staking_error(StakingError::Unauthorized)));
+        
+        staking.set_admin_status(authority, admin, true).unwrap();
+        staking.pause_as(admin, admin).unwrap();
+        assert!(staking.program().is_paused);
+    }
     
-    pub admin_account: Account<'info, AdminAccount>,
+    #[test]
+    fn stakes_open_at_the_scheduled_start_time() {
+        let mut staking = Staking::new();
+        let authority = staking.authority;
+        let launch = T0 + 3_600;
+        staking
+            .update_program_config(authority, crate::instruction::SetStakingStartsAt { staking_starts_at: launch })
+            .unwrap();
+        let (user, nft_mint) = staking.nft_holder();
+        
+        set_time(launch - 1);
+        assert_eq!(staking.stake(user, nft_mint, 0), Err(staking_error(StakingError::StakingNotStarted)));
+        
+        set_time(launch);
+        staking.stake(user, nft_mint, 0).unwrap();
+        assert_eq!(staking.env.state::<StakingPosition>(&position_key(&nft_mint, &user)).staked_at, launch);
+    }
     
-    pub authority: Signer<'info>,
-}
-
-#[derive(Accounts)]
-pub struct ValidateCollection<'info> {
-    pub staking_program: Account<'info, StakingProgram>,
+    #[test]
+    fn claim_tickets_mints_settled_rewards_once() {
+        let mut staking = Staking::new();
+        let (user, nft_mint) = staking.nft_holder();
+        staking.stake(user, nft_mint, 0).unwrap();
+        let user_ticket_account = staking.enable_ticket_mint(user);
+        
+        set_time(T0 + SIX_MONTHS / 2);
+        staking.claim_rewards(user, nft_mint).unwrap();
+        staking.claim_tickets(user, nft_mint).unwrap();
+        assert_eq!(staking.env.token_amount(&user_ticket_account), 550);
+        
+        // Nothing new settled, so a second claim mints nothing
+        staking.claim_tickets(user, nft_mint).unwrap();
+        assert_eq!(staking.env.token_amount(&user_ticket_account), 550);
+        assert_eq!(events::<TicketsClaimed>().iter().map(|event| event.amount).collect::<Vec<_>>(), [550, 0]);
+        
+        set_time(T0 + SIX_MONTHS);
+        staking.claim_rewards(user, nft_mint).unwrap();
+        staking.claim_tickets(user, nft_mint).unwrap();
+        assert_eq!(staking.env.token_amount(&user_ticket_account), 1_100);
+        let position: StakingPosition = staking.env.state(&position_key(&nft_mint, &user));
+        assert_eq!(position.tickets_minted, position.total_rewards_earned);
+    }
     
-    #[account(mut)]
-    pub collection_account: Account<'info, CollectionAccount>,
+    #[test]
+    fn claims_reopen_exactly_when_the_cooldown_ends() {
+        let mut staking = Staking::new();
+        let admin = staking.env.wallet();
+        staking.add_admin(admin).unwrap();
+        assert_eq!(
+            staking.update_collection_config(admin, crate::instruction::SetClaimCooldown { claim_cooldown: -1 }),
+            Err(staking_error(StakingError::InvalidDuration))
+        );
+        staking
+            .update_collection_config(admin, crate::instruction::SetClaimCooldown { claim_cooldown: 3_600 })
+            .unwrap();
+        let (user, nft_mint) = staking.nft_holder();
+        staking.stake(user, nft_mint, 0).unwrap();
+        
+        // The first claim isn't held back
+        let first_claim = T0 + SIX_MONTHS / 4;
+        set_time(first_claim);
+        staking.claim_rewards(user, nft_mint).unwrap();
+        assert_eq!(events::<RewardsClaimed>()[0].next_claim_at, first_claim + 3_600);
+        
+        set_time(first_claim + 3_599);
+        assert_eq!(staking.claim_rewards(user, nft_mint), Err(staking_error(StakingError::ClaimCooldownActive)));
+        set_time(first_claim + 3_600);
+        staking.claim_rewards(user, nft_mint).unwrap();
+        assert_eq!(events::<RewardsClaimed>()[1].next_claim_at, first_claim + 7_200);
+    }
     
-    pub admin_account: Account<'info, AdminAccount>,
+    #[test]
+    fn drifted_stake_counters_fail_cleanly_instead_of_wrapping() {
+        let mut staking = Staking::new();
+        let (user, nft_mint) = staking.nft_holder();
+        staking.stake(user, nft_mint, 0).unwrap();
+        set_time(T0 + SIX_MONTHS);
+        
+        let staking_program = staking.staking_program;
+        staking.env.update::<StakingProgram>(&staking_program, |program| program.total_staked = 0);
+        assert_eq!(staking.claim_nft(user, user, nft_mint), Err(staking_error(StakingError::ArithmeticOverflow)));
+        assert_eq!(staking.program().total_staked, 0);
+        
+        staking.env.update::<StakingProgram>(&staking_program, |program| program.total_staked = 1);
+        staking.env.update::<CollectionAccount>(&staking.collection_account, |collection| collection.total_staked = 0);
+        assert_eq!(staking.claim_nft(user, user, nft_mint), Err(staking_error(StakingError::ArithmeticOverflow)));
+        assert!(staking.env.state::<StakingPosition>(&position_key(&nft_mint, &user)).is_active);
+    }
     
-    pub authority: Signer<'info>,
-}
-
-// Events
-#[event]
-pub struct NftStaked {
-    pub user: Pubkey,
-    pub nft_mint: Pubkey,
-    pub collection_mint: Pubkey,
-    pub duration: u8,
-    pub unlock_at: i64,
-}
-
-#[event]
-pub struct NftClaimed {
-    pub user: Pubkey,
-    pub nft_mint: Pubkey,
-    pub collection_mint: Pubkey,
-}
-
-#[event]
-pub struct EmergencyUnlock {
-    pub admin: Pubkey,
-    pub user: Pubkey,
-    pub nft_mint: Pubkey,
-    pub reason: String,
-}
-
-#[event]
-pub struct CollectionAdded {
-    pub collection_mint: Pubkey,
-    pub six_month_tickets: u64,
-    pub twelve_month_tickets: u64,
-    pub three_year_tickets: u64,
-}
-
-#[event]
-pub struct CollectionUpdated {
-    pub collection_mint: Pubkey,
-    pub six_month_tickets: u64,
-    pub twelve_month_tickets: u64,
-    pub three_year_tickets: u64,
-}
-
-#[event]
-pub struct SecurityViolation {
-    pub violation_type: String,
-    pub violator: Pubkey,
-    pub details: String,
-}
-
-#[event]
-pub struct AdminAction {
-    pub admin: Pubkey,
-    pub action: String,
-    pub data: String,
-}
-
-#[event]
-pub struct EmergencyAction {
-    pub admin: Pubkey,
-    pub action: String,
-    pub reason: String,
-}
-
-// Error codes
-#[error_code]
-pub enum StakingError {
-    #[msg("Contract is paused")]
-    ContractPaused,
+    #[test]
+    fn early_claim_penalty_shrinks_to_zero_at_unlock() {
+        let mut staking = Staking::new();
+        let admin = staking.env.wallet();
+        staking.add_admin(admin).unwrap();
+        staking
+            .update_collection_config(admin, crate::instruction::SetEarlyClaimPenalty { early_claim_penalty_bps: 2_000 })
+            .unwrap();
+        
+        // (claim time, accrued at 1.1x, penalty, paid); the penalty rate is 20% of the share
+        // of the term still to run
+        let claims = [
+            (T0 + SIX_MONTHS / 4, 275, 41, 234),
+            (T0 + SIX_MONTHS / 2, 550, 55, 495),
+            (T0 + SIX_MONTHS * 3 / 4, 825, 41, 784),
+            (T0 + SIX_MONTHS, 1_100, 0, 1_100),
+        ];
+        let holders: Vec<_> = claims.iter().map(|_| staking.nft_holder()).collect();
+        for (user, nft_mint) in holders.iter() {
+            staking.stake(*user, *nft_mint, 0).unwrap();
+        }
+        for ((claim_at, accrued, penalty, paid), (user, nft_mint)) in claims.into_iter().zip(holders) {
+            set_time(claim_at);
+            staking.claim_rewards(user, nft_mint).unwrap();
+            let claimed = events::<RewardsClaimed>().pop().unwrap();
+            assert_eq!((claimed.penalty, claimed.amount), (penalty, paid));
+            let position: StakingPosition = staking.env.state(&position_key(&nft_mint, &user));
+            assert_eq!((position.rewards_claimed, position.total_rewards_earned), (accrued, paid));
+        }
+    }
     
-    #[msg("Invalid staking duration")]
-    InvalidDuration,
+    #[test]
+    fn oversized_and_malformed_reasons_are_rejected() {
+        let mut staking = Staking::new();
+        let admin = staking.env.wallet();
+        staking.add_admin(admin).unwrap();
+        let freeze = |staking: &mut Staking, reason: String| {
+            staking.update_collection_config(admin, crate::instruction::FreezeCollectionRewards { reason })
+        };
+        
+        for reason in [
+            "x".repeat(MAX_REASON_LEN + 1),
+            // Length is counted in bytes: 99 two-byte characters are 198 bytes
+            "é".repeat(99),
+            "fraud\nreport".to_string(),
+            "fraud\u{1b}[31m".to_string(),
+            "tab\tseparated".to_string(),
+        ] {
+            assert_eq!(freeze(&mut staking, reason), Err(staking_error(StakingError::InvalidReason)));
+        }
+        assert_eq!(freeze(&mut staking, String::new()), Err(staking_error(StakingError::ReasonRequired)));
+        assert!(!staking.env.state::<CollectionAccount>(&staking.collection_account).rewards_frozen);
+        
+        freeze(&mut staking, "x".repeat(MAX_REASON_LEN)).unwrap();
+        assert!(staking.env.state::<CollectionAccount>(&staking.collection_account).rewards_frozen);
+        assert!(validate_reason(&"x".repeat(MAX_GRANT_REASON_LEN + 1), MAX_GRANT_REASON_LEN).is_err());
+    }
     
-    #[msg("Collection not active")]
-    CollectionNotActive,
+    #[test]
+    fn half_ticket_accruals_round_toward_the_favored_side() {
+        let mut collection = collection();
+        let mut position = position(&collection);
+        // 1_000 tickets over the term: every SIX_MONTHS / 1_000 seconds vests one ticket
+        let per_ticket = SIX_MONTHS / 1_000;
+        
+        for (elapsed, protocol, user) in [(per_ticket / 2, 0, 1), (per_ticket * 5 / 2, 2, 3), (per_ticket * 3, 3, 3)] {
+            collection.favor_user = false;
+            assert_eq!(calculate_rewards(&position, &collection, T0 + elapsed).unwrap(), protocol);
+            collection.favor_user = true;
+            assert_eq!(calculate_rewards(&position, &collection, T0 + elapsed).unwrap(), user);
+        }
+        
+        // The full-term amount rounds too: 1_001 tickets at 1.5x is 1_501.5
+        collection.six_month_tickets = 1_001;
+        position.locked_multiplier = 15_000;
+        collection.favor_user = false;
+        assert_eq!(calculate_rewards(&position, &collection, T0 + SIX_MONTHS).unwrap(), 1_501);
+        collection.favor_user = true;
+        assert_eq!(calculate_rewards(&position, &collection, T0 + SIX_MONTHS).unwrap(), 1_502);
+    }
     
-    #[msg("Position not active")]
-    PositionNotActive,
+    #[test]
+    fn vault_accounts_not_owned_by_the_program_are_rejected() {
+        let mut staking = Staking::new();
+        let (user, nft_mint) = staking.nft_holder();
+        let attacker = staking.env.wallet();
+        let attacker_account = token_account(&attacker, &nft_mint);
+        staking.env.put_token_account(attacker_account, nft_mint, attacker, 0);
+        
+        assert_eq!(
+            staking.stake_into(user, nft_mint, 0, attacker_account),
+            Err(staking_error(StakingError::InvalidRecipient))
+        );
+        staking.stake(user, nft_mint, 0).unwrap();
+        
+        // Claiming from an account the program doesn't own, even one holding the NFT
+        staking.env.put_token_account(attacker_account, nft_mint, attacker, 1);
+        set_time(T0 + SIX_MONTHS);
+        assert_eq!(
+            staking.env.execute(
+                crate::accounts::ClaimNft {
+                    staking_program: staking.staking_program,
+                    collection_account: staking.collection_account,
+                    staking_position: position_key(&nft_mint, &user),
+                    owner: user,
+                    user_stake_count: staking.user_stake_count_key(user),
+                    user_stats: pda(&[b"user_stats", user.as_ref()]),
+                    user_token_account: token_account(&user, &nft_mint),
+                    program_token_account: attacker_account,
+                    user,
+                    token_program: anchor_spl::token::ID,
+                    system_program: system_program::ID,
+                },
+                crate::instruction::ClaimNft {},
+            ),
+            Err(staking_error(StakingError::InvalidRecipient))
+        );
+        staking.claim_nft(user, user, nft_mint).unwrap();
+        assert_eq!(staking.env.token_amount(&token_account(&user, &nft_mint)), 1);
+    }
     
-    #[msg("Not position owner")]
-    NotPositionOwner,
+    #[test]
+    fn cancelled_emergency_unlock_restarts_the_delay_when_requested_again() {
+        let mut staking = Staking::new();
+        let admin = staking.env.wallet();
+        let stranger = staking.env.wallet();
+        staking.add_admin(admin).unwrap();
+        let (user, nft_mint) = staking.nft_holder();
+        staking.stake(user, nft_mint, 0).unwrap();
+        let emergency_request = pda(&[b"emergency_request", position_key(&nft_mint, &user).as_ref()]);
+        let registry = pda(&[b"active_emergency_requests"]);
+        
+        staking.admin_unlock_as(admin, admin, user, nft_mint).unwrap();
+        assert_eq!(staking.env.state::<ActiveEmergencyRequests>(&registry).requests, [emergency_request]);
+        assert_eq!(
+            staking.cancel_emergency_unlock(stranger, admin, user, nft_mint),
+            Err(staking_error(StakingError::Unauthorized))
+        );
+        
+        set_time(T0 + 3_600);
+        staking.cancel_emergency_unlock(admin, admin, user, nft_mint).unwrap();
+        assert_eq!(staking.env.lamports(&emergency_request), 0);
+        assert!(staking.env.state::<ActiveEmergencyRequests>(&registry).requests.is_empty());
+        
+        // Requesting again starts a new request rather than resuming the cancelled one
+        set_time(T0 + 7_200);
+        staking.admin_unlock_as(admin, admin, user, nft_mint).unwrap();
+        assert_eq!(staking.env.state::<EmergencyRequest>(&emergency_request).requested_at, T0 + 7_200);
+        set_time(T0 + EMERGENCY_DELAY);
+        assert_eq!(
+            staking.admin_unlock_as(admin, admin, user, nft_mint),
+            Err(staking_error(StakingError::EmergencyDelayNotMet))
+        );
+        set_time(T0 + 7_200 + EMERGENCY_DELAY);
+        staking.admin_unlock_as(admin, admin, user, nft_mint).unwrap();
+        assert_eq!(staking.env.token_amount(&token_account(&user, &nft_mint)), 1);
+    }
     
-    #[msg("Staking period not completed")]
-    StakingPeriodNotCompleted,
+    #[test]
+    fn random_signer_cannot_start_an_emergency_unlock() {
+        let mut staking = Staking::new();
+        let admin = staking.env.wallet();
+        let stranger = staking.env.wallet();
+        staking.add_admin(admin).unwrap();
+        let (user, nft_mint) = staking.nft_holder();
+        staking.stake(user, nft_mint, 0).unwrap();
+        let emergency_request = pda(&[b"emergency_request", position_key(&nft_mint, &user).as_ref()]);
+        
+        // A real admin's record, signed by someone else
+        assert_eq!(
+            staking.admin_unlock_as(stranger, admin, user, nft_mint),
+            Err(staking_error(StakingError::Unauthorized))
+        );
+        // The stranger's own admin address has no record
+        assert!(staking.admin_unlock_as(stranger, stranger, user, nft_mint).is_err());
+        assert_eq!(staking.env.lamports(&emergency_request), 0);
+        
+        set_time(T0 + EMERGENCY_DELAY);
+        assert_eq!(
+            staking.admin_unlock_as(stranger, admin, user, nft_mint),
+            Err(staking_error(StakingError::Unauthorized))
+        );
+        assert!(staking.env.state::<StakingPosition>(&position_key(&nft_mint, &user)).is_active);
+    }
     
-    #[msg("Reason required for emergency action")]
-    ReasonRequired,
+    #[test]
+    fn batch_paths_stop_at_the_max_batch_size() {
+        let mut staking = Staking::new();
+        let authority = staking.authority;
+        assert_eq!(
+            staking.update_program_config(authority, crate::instruction::SetMaxBatchSize { max_batch_size: 0 }),
+            Err(staking_error(StakingError::InvalidAmount))
+        );
+        staking
+            .update_program_config(authority, crate::instruction::SetMaxBatchSize { max_batch_size: 2 })
+            .unwrap();
+        let holders: Vec<_> = (0..3).map(|_| staking.nft_holder()).collect();
+        for (user, nft_mint) in holders.iter() {
+            staking.stake(*user, *nft_mint, 0).unwrap();
+        }
+        let positions = |count: usize| -> Vec<AccountMeta> {
+            holders[..count]
+                .iter()
+                .map(|(user, nft_mint)| AccountMeta::new_readonly(position_key(nft_mint, user), false))
+                .collect()
+        };
+        
+        for count in [3, 2] {
+            let status = staking.env.execute_with_remaining(
+                crate::accounts::GetBatchPositionStatus {
+                    staking_program: staking.staking_program,
+                    collection_account: staking.collection_account,
+                },
+                positions(count),
+                crate::instruction::BatchPositionStatus {},
+            );
+            let liability = staking.env.execute_with_remaining(
+                crate::accounts::TotalLiability {
+                    staking_program: staking.staking_program,
+                    collection_account: staking.collection_account,
+                },
+                positions(count),
+                crate::instruction::TotalLiability {},
+            );
+            let expected = if count > 2 { Err(staking_error(StakingError::BatchTooLarge)) } else { Ok(()) };
+            assert_eq!((status, liability), (expected.clone(), expected));
+        }
+        assert_eq!(events::<BatchPositionStatus>()[0].statuses.len(), 2);
+        
+        // Sweeps count position groups, not accounts
+        let swept: Vec<_> = holders.iter().map(|(user, nft_mint)| (*nft_mint, *user)).collect();
+        set_time(T0 + SIX_MONTHS + STALE_POSITION_GRACE);
+        assert_eq!(staking.sweep(&swept), Err(staking_error(StakingError::BatchTooLarge)));
+        staking.sweep(&swept[..2]).unwrap();
+    }
     
-    #[msg("Emergency delay not met")]
-    EmergencyDelayNotMet,
+    #[test]
+    fn require_validation_only_admits_validated_collections() {
+        let mut staking = Staking::new();
+        let authority = staking.authority;
+        let admin = staking.env.wallet();
+        staking.add_admin(admin).unwrap();
+        let (user, nft_mint) = staking.nft_holder();
+        
+        staking
+            .update_program_config(authority, crate::instruction::SetRequireValidation { require_validation: true })
+            .unwrap();
+        assert_eq!(staking.stake(user, nft_mint, 0), Err(staking_error(StakingError::CollectionNotValidated)));
+        
+        staking.validate_collection(admin, staking.collection_mint, true).unwrap();
+        staking.stake(user, nft_mint, 0).unwrap();
+        
+        // Withdrawn validation closes the collection again until the flag is turned off
+        staking.validate_collection(admin, staking.collection_mint, false).unwrap();
+        let (user, nft_mint) = staking.nft_holder();
+        assert_eq!(staking.stake(user, nft_mint, 0), Err(staking_error(StakingError::CollectionNotValidated)));
+        staking
+            .update_program_config(authority, crate::instruction::SetRequireValidation { require_validation: false })
+            .unwrap();
+        staking.stake(user, nft_mint, 0).unwrap();
+    }
     
-    #[msg("Emergency request already executed")]
-    EmergencyRequestAlreadyExecuted,
+    #[test]
+    fn frozen_collection_stops_accruing_at_the_freeze() {
+        let mut staking = Staking::new();
+        let admin = staking.env.wallet();
+        staking.add_admin(admin).unwrap();
+        let (user, nft_mint) = staking.nft_holder();
+        staking.stake(user, nft_mint, 0).unwrap();
+        
+        set_time(T0 + SIX_MONTHS / 2);
+        staking
+            .update_collection_config(
+                admin,
+                crate::instruction::FreezeCollectionRewards {
+                    reason: "counterfeit mints".to_string(),
+                },
+            )
+            .unwrap();
+        assert_eq!(events::<SecurityViolation>()[0].violation_type, "collectionFrozen");
+        
+        // Rewards accrued up to the freeze still settle, nothing after it
+        set_time(T0 + SIX_MONTHS);
+        staking.claim_rewards(user, nft_mint).unwrap();
+        assert_eq!(staking.env.state::<StakingPosition>(&position_key(&nft_mint, &user)).total_rewards_earned, 550);
+        set_time(T0 + THREE_YEARS);
+        staking.claim_rewards(user, nft_mint).unwrap();
+        assert_eq!(staking.env.state::<StakingPosition>(&position_key(&nft_mint, &user)).total_rewards_earned, 550);
+        
+        let user_ticket_account = staking.enable_ticket_mint(user);
+        staking.claim_tickets(user, nft_mint).unwrap();
+        assert_eq!(staking.env.token_amount(&user_ticket_account), 550);
+        staking.claim_nft(user, user, nft_mint).unwrap();
+        assert_eq!(staking.env.token_amount(&token_account(&user, &nft_mint)), 1);
+    }
     
-    #[msg("Unauthorized access")]
-    Unauthorized,
+    #[test]
+    fn only_active_admins_update_collection_rewards() {
+        let mut staking = Staking::new();
+        let authority = staking.authority;
+        let admin = staking.env.wallet();
+        let stranger = staking.env.wallet();
+        staking.add_admin(admin).unwrap();
+        let update = |staking: &mut Staking, signer: Pubkey, admin: Pubkey| {
+            staking.env.execute(
+                crate::accounts::UpdateCollectionRewards {
+                    staking_program: staking.staking_program,
+                    collection_account: staking.collection_account,
+                    admin_account: pda(&[b"admin", admin.as_ref()]),
+                    authority: signer,
+                },
+                crate::instruction::UpdateCollectionRewards {
+                    six_month_tickets: 1_000_000,
+                    twelve_month_tickets: 2_000_000,
+                    three_year_tickets: 6_000_000,
+                },
+            )
+        };
+        
+        assert_eq!(update(&mut staking, stranger, admin), Err(staking_error(StakingError::Unauthorized)));
+        assert!(update(&mut staking, stranger, stranger).is_err());
+        staking.set_admin_status(authority, admin, false).unwrap();
+        assert_eq!(update(&mut staking, admin, admin), Err(staking_error(StakingError::Unauthorized)));
+        assert_eq!(staking.env.state::<CollectionAccount>(&staking.collection_account).six_month_tickets, 1_000);
+        
+        staking.set_admin_status(authority, admin, true).unwrap();
+        update(&mut staking, admin, admin).unwrap();
+        assert_eq!(staking.env.state::<CollectionAccount>(&staking.collection_account).six_month_tickets, 1_000_000);
+    }
     
-    #[msg("Invalid recipient address")]
-    InvalidRecipient,
+    #[test]
+    fn repausing_does_not_push_back_the_auto_unpause() {
+        let mut staking = Staking::new();
+        let admin = staking.env.wallet();
+        staking.add_admin(admin).unwrap();
+        let auto_unpause = |staking: &mut Staking| {
+            let caller = staking.env.wallet();
+            staking.env.execute(
+                crate::accounts::TryAutoUnpause { staking_program: staking.staking_program, caller },
+                crate::instruction::TryAutoUnpause {},
+            )
+        };
+        
+        set_time(T0 + 60);
+        staking.pause_as(admin, admin).unwrap();
+        set_time(T0 + 60 + naffles_staking::AUTO_UNPAUSE_DELAY - 1);
+        assert_eq!(staking.pause_as(admin, admin), Err(staking_error(StakingError::ContractPaused)));
+        assert_eq!(staking.program().paused_at, T0 + 60);
+        assert_eq!(auto_unpause(&mut staking), Err(staking_error(StakingError::AutoUnpauseNotDue)));
+        
+        set_time(T0 + 60 + naffles_staking::AUTO_UNPAUSE_DELAY);
+        auto_unpause(&mut staking).unwrap();
+        assert!(!staking.program().is_paused);
+    }
     
-    #[msg("Collection already exists")]
-    CollectionAlreadyExists,
+    #[test]
+    fn unpause_approvals_survive_a_rejected_repause() {
+        let mut staking = Staking::new();
+        let authority = staking.authority;
+        let admins = [staking.env.wallet(), staking.env.wallet()];
+        for admin in admins {
+            staking.add_admin(admin).unwrap();
+        }
+        staking
+            .update_program_config(authority, crate::instruction::SetUnpauseThreshold { unpause_threshold: 2 })
+            .unwrap();
+        
+        set_time(T0 + 60);
+        staking.pause_as(admins[0], admins[0]).unwrap();
+        staking.unpause_as(admins[0], admins[0]).unwrap();
+        
+        // The second pause is rejected, so the first approval still counts toward this pause
+        set_time(T0 + 120);
+        assert_eq!(staking.pause_as(admins[1], admins[1]), Err(staking_error(StakingError::ContractPaused)));
+        assert_eq!(staking.program().paused_at, T0 + 60);
+        staking.unpause_as(admins[1], admins[1]).unwrap();
+        assert!(!staking.program().is_paused);
+    }
     
-    #[msg("Collection not found")]
-    CollectionNotFound,
+    #[test]
+    fn expired_validations_stop_counting_toward_the_minimum() {
+        let mut staking = Staking::new();
+        let authority = staking.authority;
+        let admin = staking.env.wallet();
+        staking.add_admin(admin).unwrap();
+        let second_collection = Pubkey::new_unique();
+        staking.add_collection(authority, second_collection).unwrap();
+        staking
+            .update_program_config(authority, crate::instruction::SetMinValidatedCollections { min_validated_collections: 2 })
+            .unwrap();
+        let month = LOYALTY_MONTH;
+        staking
+            .update_collection_config(admin, crate::instruction::SetValidationValidityPeriod { validity_period: month })
+            .unwrap();
+        staking.validate_collection(admin, staking.collection_mint, true).unwrap();
+        staking.validate_collection(admin, second_collection, true).unwrap();
+        let (user, nft_mint) = staking.nft_holder();
+        staking.stake(user, nft_mint, 0).unwrap();
+        
+        // The lapsed collection no longer satisfies the minimum for its own stakes
+        set_time(T0 + month);
+        let (user, nft_mint) = staking.nft_holder();
+        assert_eq!(staking.stake(user, nft_mint, 0), Err(staking_error(StakingError::ProgramNotReady)));
+        
+        // Expiring it drops the count for every collection
+        let second_account = pda(&[b"collection", second_collection.as_ref()]);
+        let expire = |staking: &mut Staking, collection_account: Pubkey| {
+            let caller = staking.env.wallet();
+            staking.env.execute(
+                crate::accounts::ExpireCollectionValidation {
+                    staking_program: staking.staking_program,
+                    collection_account,
+                    caller,
+                },
+                crate::instruction::ExpireCollectionValidation {},
+            )
+        };
+        assert_eq!(expire(&mut staking, second_account), Err(staking_error(StakingError::ValidationNotExpired)));
+        let collection_account = staking.collection_account;
+        expire(&mut staking, collection_account).unwrap();
+        assert_eq!(staking.program().validated_collections, 1);
+        assert!(!staking.env.state::<CollectionAccount>(&collection_account).is_validated);
+        assert_eq!(expire(&mut staking, collection_account), Err(staking_error(StakingError::CollectionNotValidated)));
+        
+        // Validating again restores the count
+        staking.validate_collection(admin, staking.collection_mint, true).unwrap();
+        assert_eq!(staking.program().validated_collections, 2);
+        staking.stake(user, nft_mint, 0).unwrap();
+    }
     
-    #[msg("Insufficient multi-sig confirmations")]
-    InsufficientMultiSigConfirmations,
+    #[test]
+    fn a_transferred_position_counts_once_per_snapshot() {
+        let mut staking = Staking::new();
+        let authority = staking.authority;
+        let (user, nft_mint) = staking.nft_holder();
+        staking.stake(user, nft_mint, 0).unwrap();
+        let position = position_key(&nft_mint, &user);
+        staking.env.update::<StakingPosition>(&position, |position| position.total_rewards_earned = 500);
+        let buyer = staking.env.wallet();
+        let snapshot = |staking: &mut Staking, snapshot_id: u64, user: Pubkey, position: AccountMeta| {
+            staking.env.execute_with_remaining(
+                crate::accounts::SnapshotUserRewards {
+                    staking_program: staking.staking_program,
+                    reward_snapshot: pda(&[b"reward_snapshot", snapshot_id.to_le_bytes().as_ref(), user.as_ref()]),
+                    authority,
+                    system_program: system_program::ID,
+                },
+                vec![position],
+                crate::instruction::SnapshotUserRewards { snapshot_id, user },
+            )
+        };
+        
+        assert_eq!(
+            snapshot(&mut staking, 1, user, AccountMeta::new_readonly(position, false)),
+            Err(staking_error(StakingError::InvalidPosition))
+        );
+        snapshot(&mut staking, 1, user, AccountMeta::new(position, false)).unwrap();
+        assert_eq!(staking.env.state::<StakingPosition>(&position).last_snapshot_id, 1);
+        
+        // The buyer can't count the same position again under the same campaign
+        staking.transfer_position(user, nft_mint, buyer).unwrap();
+        assert_eq!(
+            snapshot(&mut staking, 1, buyer, AccountMeta::new(position, false)),
+            Err(staking_error(StakingError::PositionAlreadySnapshotted))
+        );
+        snapshot(&mut staking, 2, buyer, AccountMeta::new(position, false)).unwrap();
+        let taken: RewardSnapshot = staking.env.state(&pda(&[b"reward_snapshot", 2u64.to_le_bytes().as_ref(), buyer.as_ref()]));
+        assert_eq!((taken.total_rewards_earned, taken.positions_counted), (500, 1));
+    }
+    
+    #[test]
+    fn admin_created_positions_are_counted_for_their_owner() {
+        let mut staking = Staking::new();
+        let authority = staking.authority;
+        staking
+            .update_program_config(authority, crate::instruction::SetMigrationWindow { migration_ends_at: T0 + 60 })
+            .unwrap();
+        let owner = staking.env.wallet();
+        let stake_cost = |staking: &mut Staking| {
+            staking
+                .env
+                .execute(
+                    crate::accounts::GetStakeCost {
+                        staking_program: staking.staking_program,
+                        collection_account: staking.collection_account,
+                        user: owner,
+                        user_stake_count: staking.user_stake_count_key(owner),
+                        user_stats: pda(&[b"user_stats", owner.as_ref()]),
+                    },
+                    crate::instruction::StakeCost {},
+                )
+                .unwrap();
+            events::<StakeCost>().pop().unwrap()
+        };
+        let rent = Rent::default();
+        let first = stake_cost(&mut staking);
+        assert_eq!(
+            first.counter_rent,
+            rent.minimum_balance(UserStakeCount::LEN) + rent.minimum_balance(UserStats::LEN)
+        );
+        assert_eq!(first.total_lamports, first.position_rent + first.counter_rent + first.stake_fee_lamports);
+        
+        let nft_mint = Pubkey::new_unique();
+        let program_token_account = Pubkey::new_unique();
+        staking.env.put_token_account(program_token_account, nft_mint, staking.staking_program, 1);
+        staking
+            .env
+            .execute(
+                crate::accounts::AdminCreatePosition {
+                    staking_program: staking.staking_program,
+                    collection_account: staking.collection_account,
+                    staking_position: position_key(&nft_mint, &owner),
+                    user_stake_count: staking.user_stake_count_key(owner),
+                    user_stats: pda(&[b"user_stats", owner.as_ref()]),
+                    program_token_account,
+                    authority,
+                    system_program: system_program::ID,
+                },
+                crate::instruction::AdminCreatePosition { owner, nft_mint, duration: 0, staked_at: T0 },
+            )
+            .unwrap();
+        assert_eq!(staking.user_stake_count(owner), 1);
+        let stats = staking.user_stats(owner);
+        assert_eq!((stats.user, stats.lifetime_staked, stats.currently_staked), (owner, 1, 1));
+        
+        // The counters exist now, so a further stake only costs the position
+        assert_eq!(stake_cost(&mut staking).counter_rent, 0);
+    }
 }
\ No newline at end of file